@@ -1,17 +1,109 @@
+#[cfg(feature = "bindings")]
 extern crate cbindgen;
 
 use std::env;
+use std::path::{Path, PathBuf};
 
 fn main() {
-    let _crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    // Everything the build script writes goes here, copy it out with create_bindings.ps1
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed=build.rs");
 
     println!("Doing cdylib magic!");
     cdylib_link_lines::metabuild();
 
+    #[cfg(feature = "bindings")]
+    write_bindings(&crate_dir, &out_dir);
+
+    write_pkg_config(&crate_dir, &out_dir);
+}
+
+///
+/// Generates `bindings.hpp` and `bindings.h` in `out_dir`, only with the `bindings` feature
+/// since expanding the macros needs nightly and is slow
+///
+#[cfg(feature = "bindings")]
+fn write_bindings(crate_dir: &Path, out_dir: &Path) {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
     println!("Bindings generating");
-    // cbindgen::generate(crate_dir)
-    //     .expect("Unable to generate bindings")
-    //     .write_to_file("shared/bindings.hpp");
+    let cpp_config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("Unable to read cbindgen.toml");
+
+    // C has no namespaces, so every global name gets a BeatStar prefix and enum
+    // variants are prefixed with their enum name (e.g. BeatStarSongDiffs_Easy)
+    let mut c_config = cpp_config.clone();
+    c_config.language = cbindgen::Language::C;
+    c_config.namespace = None;
+    c_config.style = cbindgen::Style::Both;
+    c_config.cpp_compat = true;
+    c_config.pragma_once = false;
+    c_config.include_guard = Some("SONG_DATA_CORE_BINDINGS_H".to_string());
+    c_config.enumeration.prefix_with_name = true;
+    for (name, c_name) in [
+        ("SongDiffs", "BeatStarSongDiffs"),
+        ("RustCStringWrapper", "BeatStarCString"),
+        ("UnixTime", "BeatStarUnixTime"),
+        ("MAX_NOTE_SCORE", "BEATSTAR_MAX_NOTE_SCORE"),
+    ] {
+        c_config
+            .export
+            .rename
+            .insert(name.to_string(), c_name.to_string());
+    }
+
+    generate_bindings(crate_dir, cpp_config, &out_dir.join("bindings.hpp"));
+    generate_bindings(crate_dir, c_config, &out_dir.join("bindings.h"));
 
     println!("Generated bindings");
 }
+
+#[cfg(feature = "bindings")]
+fn generate_bindings(crate_dir: &Path, config: cbindgen::Config, output: &Path) {
+    // Expanding macros needs nightly, don't fail the library build if it's missing
+    match cbindgen::generate_with_config(crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(output);
+        }
+        Err(e) => println!(
+            "cargo:warning=Unable to generate {0}: {1}",
+            output.display(),
+            e
+        ),
+    }
+}
+
+///
+/// Writes a pkg-config file for the built library, so non C++ hosts can find
+/// the headers and library. create_bindings.ps1 copies it next to the library.
+///
+fn write_pkg_config(crate_dir: &Path, out_dir: &Path) {
+    // OUT_DIR is target/<profile>/build/<package>-<hash>/out
+    let lib_dir = match out_dir.ancestors().nth(3) {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let lib_name = env::var("CARGO_PKG_NAME").unwrap();
+    let pc = format!(
+        "prefix={lib_dir}\n\
+         libdir=${{prefix}}\n\
+         includedir={include_dir}\n\
+         \n\
+         Name: {lib_name}\n\
+         Description: Beat Saber song data (stars, pp, diffs) from the scrapped BeatSaver data\n\
+         Version: {version}\n\
+         Libs: -L${{libdir}} -l{lib_name}\n\
+         Cflags: -I${{includedir}}\n",
+        lib_dir = lib_dir.display(),
+        include_dir = crate_dir.join("shared").display(),
+        version = env::var("CARGO_PKG_VERSION").unwrap(),
+    );
+
+    if let Err(e) = std::fs::write(out_dir.join(format!("{lib_name}.pc")), pc) {
+        println!("cargo:warning=Unable to write pkg-config file: {e}");
+    }
+}
@@ -19,31 +19,34 @@ int main() {
 
     auto diff = song_data_core::BeatStarSong_DiffGet(song, 0);
 
-    cout << "Got the stars: " << diff->diff.string_data << " " << diff->stars << std::endl;
+    auto diffInfo = song_data_core::BeatStarSongDifficultyStats_GetInfo(diff);
+
+    cout << "Got the stars: " << diffInfo.diff.data << " " << diffInfo.stars << std::endl;
 
     auto song2 = song_data_core::Beatstar_GetSong("B9BED84A127130BF80AFF18DB677EDD215CE0AB5");
 
     if (song2) {
         for (int i = 0; i < 3; i++) {
-            auto charMapLen = song_data_core::BeatStarSong_map_CharacteristicsLen(song2);
+            cout << "Got the chars for song: " << song_data_core::BeatStarSong_GetInfo(song2).song_name.data << std::endl;
 
-            cout << "Got the chars for song: " << song2->song_name.string_data << " with size: " << charMapLen << std::endl;
+            auto charIter = song_data_core::Beatstar_CharacteristicIterNew(song2);
 
-            for (int j = 0; j < charMapLen; j++)
+            while (auto characteristic = song_data_core::Beatstar_CharacteristicIterNext(charIter))
             {
-                const song_data_core::BeatStarCharacteristics* charMapKey = song_data_core::BeatStarSong_map_CharacteristicsKeyGet(song2, j);
-
-                auto diffMapLen = song_data_core::BeatStarSong_map_Characteristics_DifficultyStatsLen(song2, charMapKey);
+                cout << "   Got the diffs for char: " << song_data_core::BeatStarSongCharacteristic_Name(characteristic) << std::endl;
 
-                cout << "   Got the diffs for char: " << charMapKey << " with size: " << diffMapLen << std::endl;
+                auto diffIter = song_data_core::Beatstar_DifficultyIterNew(characteristic);
 
-                for (int k = 0; k < diffMapLen; k++) {
-                    auto diffKey = song_data_core::BeatStarSong_map_Characteristics_DifficultyStatsGetStrKey(song2, charMapKey, k);
-                    auto diffData = song_data_core::BeatStarSong_map_Characteristics_DifficultyStatsGet(song2, charMapKey, diffKey);
+                while (auto diffData = song_data_core::Beatstar_DifficultyIterNext(diffIter)) {
+                    auto diffDataInfo = song_data_core::BeatStarSongDifficultyStats_GetInfo(diffData);
 
-                    cout << "       Got the diff: " << diffData->diff.string_data << " with pp: " << diffData->approximate_pp_value << std::endl;
+                    cout << "       Got the diff: " << diffDataInfo.diff.data << " with pp: " << diffDataInfo.approximate_pp_value << std::endl;
                 }
+
+                song_data_core::Beatstar_DifficultyIterFree(diffIter);
             }
+
+            song_data_core::Beatstar_CharacteristicIterFree(charIter);
         }
     }
 
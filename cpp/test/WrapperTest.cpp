@@ -0,0 +1,188 @@
+// Exercises easybindings.hpp against the songs.json fixture, zipped by CMake.
+// Usage: SongDataCoreRust_WrapperTest <songs.zip>
+
+#include "easybindings.hpp"
+
+#include <cstdlib>
+#include <iostream>
+#include <string>
+#include <vector>
+
+using namespace song_data_core;
+
+static int failures = 0;
+
+#define CHECK(expr)                                                               \
+    do                                                                            \
+    {                                                                             \
+        if (!(expr))                                                              \
+        {                                                                         \
+            std::cerr << __FILE__ << ":" << __LINE__ << ": failed " #expr "\n"; \
+            failures++;                                                           \
+        }                                                                         \
+    } while (false)
+
+static void missingFileThrows()
+{
+    try
+    {
+        Database::load("/nonexistent/songs.zip");
+        CHECK(!"expected an Error");
+    }
+    catch (const Error &e)
+    {
+        CHECK(e.code == BeatStarErrorCode::Io);
+        CHECK(std::string(e.what()).size() > 0);
+    }
+}
+
+static void songs(const Database &db)
+{
+    CHECK(db.size() == 2);
+
+    std::vector<std::string_view> hashes;
+    for (auto song : db.songs())
+        hashes.push_back(song.hash());
+
+    CHECK(hashes.size() == 2);
+    CHECK(hashes[0] == "AAAA000000000000000000000000000000000001");
+    CHECK(hashes[1] == "BBBB000000000000000000000000000000000002");
+
+    auto song = db.song("BBBB000000000000000000000000000000000002");
+    CHECK(song.has_value());
+    CHECK(song->songName() == "Other Song");
+    CHECK(song->songSubName() == "Remix");
+    CHECK(song->bpm() == 200.0f);
+    CHECK(song->allDifficulties().size() == 2);
+
+    CHECK(!db.song("0000000000000000000000000000000000000000").has_value());
+}
+
+static void characteristicsAndDifficulties(const Database &db)
+{
+    auto song = db.song("AAAA000000000000000000000000000000000001").value();
+
+    std::vector<BeatStarCharacteristics> characteristics;
+    for (const auto &characteristic : song.characteristics())
+        characteristics.push_back(characteristic.kind());
+
+    CHECK(characteristics.size() == 2);
+    CHECK(characteristics[0] == BeatStarCharacteristics::Standard);
+    CHECK(characteristics[1] == BeatStarCharacteristics::OneSaber);
+
+    std::vector<SongDiffs> diffs;
+    for (const auto &diff : song.characteristic("Standard")->difficulties())
+        diffs.push_back(diff.difficulty());
+
+    CHECK(diffs.size() == 2);
+    CHECK(diffs[0] == SongDiffs::Expert);
+    CHECK(diffs[1] == SongDiffs::ExpertPlus);
+
+    auto expertPlus = song.difficulty(BeatStarCharacteristics::Standard, SongDiffs::ExpertPlus);
+    CHECK(expertPlus.has_value());
+    CHECK(expertPlus->name() == "ExpertPlus");
+    CHECK(expertPlus->stars() == 8.25f);
+    CHECK(expertPlus->ranked());
+
+    std::vector<std::string_view> requirements;
+    for (auto requirement : expertPlus->requirements())
+        requirements.push_back(requirement);
+
+    CHECK(requirements.size() == 1);
+    CHECK(requirements[0] == "Noodle Extensions");
+
+    CHECK(!song.difficulty(BeatStarCharacteristics::Lawless, SongDiffs::Easy).has_value());
+
+    auto other = db.song("BBBB000000000000000000000000000000000002").value();
+    std::vector<std::string_view> names;
+    for (const auto &characteristic : other.characteristics())
+        names.push_back(characteristic.name());
+
+    CHECK(names.size() == 2);
+    CHECK(names[1] == "Ghost");
+
+    auto ghost = other.difficulty("Ghost", SongDiffs::Expert);
+    CHECK(ghost.has_value());
+    CHECK(ghost->characteristic() == BeatStarCharacteristics::Custom);
+    CHECK(ghost->characteristicName() == "Ghost");
+    CHECK(ghost->maxScore() == Beatstar_MaxScoreForNotes(410));
+    CHECK(ghost->accuracyToScore(100.0f) == ghost->maxScore());
+}
+
+static void queries(const Database &db)
+{
+    auto filter = Beatstar_DifficultyFilterDefault();
+    filter.ranked_only = true;
+    filter.min_stars = 5.0f;
+
+    auto list = db.query(filter);
+    CHECK(list.size() == 2);
+    for (const auto &[song, diff] : list)
+    {
+        CHECK(song.hash() == "AAAA000000000000000000000000000000000001");
+        CHECK(diff.ranked());
+        CHECK(diff.stars() >= 5.0f);
+    }
+
+    auto recent = db.recentlyRanked(1);
+    CHECK(recent.size() == 1);
+    for (const auto &[song, diff] : recent)
+        CHECK(diff.difficulty() == SongDiffs::ExpertPlus);
+
+    CHECK(db.statsJson().find("\"song_count\":2") != std::string_view::npos);
+}
+
+static void ownedDatabases(const Database &global, const std::string &path)
+{
+    auto first = OwnedDatabase::open(path);
+    auto second = OwnedDatabase::open(path);
+    CHECK(first.view().raw() != second.view().raw());
+    CHECK(first.view().raw() != global.raw());
+    CHECK(first.view().size() == global.size());
+
+    auto song = first.song("ff01");
+    CHECK(song.has_value());
+    CHECK(song->hash() == "BBBB000000000000000000000000000000000002");
+    CHECK(!second.song("0000").has_value());
+
+    try
+    {
+        OwnedDatabase::open("/nonexistent/songs.zip");
+        CHECK(!"expected an Error");
+    }
+    catch (const Error &e)
+    {
+        CHECK(e.code == BeatStarErrorCode::Io);
+    }
+}
+
+int main(int argc, char **argv)
+{
+    if (argc < 2)
+    {
+        std::cerr << "Usage: " << argv[0] << " <songs.zip>\n";
+        return EXIT_FAILURE;
+    }
+
+    checkAbi();
+    CHECK(Beatstar_AbiVersion() == BEATSTAR_ABI_VERSION);
+
+    // Before loading, the database can only be loaded once per process
+    missingFileThrows();
+
+    auto db = loadDatabaseAsync(argv[1]).get();
+
+    songs(db);
+    characteristicsAndDifficulties(db);
+    queries(db);
+    ownedDatabases(db, argv[1]);
+
+    if (failures)
+    {
+        std::cerr << failures << " check(s) failed\n";
+        return EXIT_FAILURE;
+    }
+
+    std::cout << "All checks passed\n";
+    return EXIT_SUCCESS;
+}
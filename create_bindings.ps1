@@ -1 +1,14 @@
-cbindgen --config cbindgen.toml --crate song_data_core_rust --output ./shared/bindings.hpp
\ No newline at end of file
+# build.rs only writes to its OUT_DIR, generate the headers there and copy them to shared/
+# along with the pkg-config file next to the library. Needs the nightly toolchain.
+$outDir = cargo build --features bindings --message-format=json |
+    ConvertFrom-Json |
+    Where-Object { $_.reason -eq "build-script-executed" -and $_.package_id -like "*song_data_core_rust*" } |
+    Select-Object -Last 1 -ExpandProperty out_dir
+
+if (-not $outDir) {
+    Write-Error "cargo build failed"
+    Exit 1
+}
+
+Copy-Item "$outDir/bindings.hpp", "$outDir/bindings.h" ./shared/
+Copy-Item "$outDir/song_data_core_rust.pc" ./target/debug/
@@ -0,0 +1,1450 @@
+#ifndef SONG_DATA_CORE_BINDINGS_H
+#define SONG_DATA_CORE_BINDINGS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ *
+ * Version of the C ABI. Bump it whenever an exported function signature,
+ * struct layout or enum order changes, so stale headers fail `Beatstar_CheckAbi`
+ *
+ */
+#define BEATSTAR_ABI_VERSION 5
+
+/**
+ * Highest score a single note can give before the combo multiplier
+ */
+#define BEATSTAR_MAX_NOTE_SCORE 115
+
+/**
+ *
+ * How much the net votes (upvotes minus downvotes) of a song must move to be reported
+ *
+ */
+#define BEATSTAR_MIN_VOTE_SWING 10
+
+typedef enum BeatStarCharacteristics {
+  BeatStarCharacteristics_Unknown,
+  BeatStarCharacteristics_Standard,
+  BeatStarCharacteristics_OneSaber,
+  BeatStarCharacteristics_NoArrows,
+  BeatStarCharacteristics_Lightshow,
+  BeatStarCharacteristics_Degree90,
+  BeatStarCharacteristics_Degree360,
+  BeatStarCharacteristics_Lawless,
+  /**
+   * A characteristic added by mods, the raw name is kept in the difficulty's `char`
+   */
+  BeatStarCharacteristics_Custom,
+} BeatStarCharacteristics;
+
+/**
+ *
+ * The kind of failure of the last FFI call on this thread
+ *
+ */
+typedef enum BeatStarErrorCode {
+  BeatStarErrorCode_Ok,
+  /**
+   * A null or non UTF-8 argument was passed
+   */
+  BeatStarErrorCode_InvalidArgument,
+  /**
+   * The data could not be downloaded
+   */
+  BeatStarErrorCode_Network,
+  /**
+   * A local file could not be read or written
+   */
+  BeatStarErrorCode_Io,
+  /**
+   * The downloaded or local data is not a valid scrapped data zip
+   */
+  BeatStarErrorCode_CorruptData,
+  /**
+   * The requested song, difficulty or index does not exist
+   */
+  BeatStarErrorCode_NotFound,
+  /**
+   * The library panicked, the message has the panic payload
+   */
+  BeatStarErrorCode_Panic,
+  BeatStarErrorCode_Unknown,
+  /**
+   * The headers the host was compiled with don't match the library
+   */
+  BeatStarErrorCode_AbiMismatch,
+  /**
+   * The library was built without the cargo feature the function needs,
+   * or a file uses a schema the library can't read
+   */
+  BeatStarErrorCode_Unsupported,
+} BeatStarErrorCode;
+
+/**
+ *
+ * The layers stacked over the scraped database, from lowest to highest priority
+ *
+ */
+typedef enum BeatStarLayer {
+  /**
+   * Songs built from local level folders
+   */
+  BeatStarLayer_Local,
+  /**
+   * The user's corrected metadata and custom star ratings
+   */
+  BeatStarLayer_User,
+} BeatStarLayer;
+
+/**
+ *
+ * Verbosity of the library's logs, each level includes the ones before it
+ *
+ */
+typedef enum BeatStarLogLevel {
+  BeatStarLogLevel_Off,
+  BeatStarLogLevel_Error,
+  BeatStarLogLevel_Warn,
+  BeatStarLogLevel_Info,
+  BeatStarLogLevel_Debug,
+  BeatStarLogLevel_Trace,
+} BeatStarLogLevel;
+
+/**
+ *
+ * How a playlist entry matched the database
+ *
+ */
+typedef enum BeatStarPlaylistEntryStatus {
+  /**
+   * The hash is in the database
+   */
+  BeatStarPlaylistEntryStatus_Found,
+  /**
+   * The hash isn't, but the key is. The map was updated since the playlist was made.
+   */
+  BeatStarPlaylistEntryStatus_StaleHash,
+  /**
+   * Neither the hash nor the key are in the database
+   */
+  BeatStarPlaylistEntryStatus_Missing,
+} BeatStarPlaylistEntryStatus;
+
+/**
+ *
+ * Where a song's data comes from
+ *
+ */
+typedef enum BeatStarSongSource {
+  /**
+   * The scraped BeatSaver data
+   */
+  BeatStarSongSource_Scraped,
+  /**
+   * A level folder's Info.dat, for maps that aren't in the scraped data yet
+   */
+  BeatStarSongSource_Local,
+  /**
+   * Only known from the user's corrections
+   */
+  BeatStarSongSource_User,
+} BeatStarSongSource;
+
+/**
+ *
+ * The difficulty of a beatmap, ordered from easiest to hardest.
+ * Difficulties that can't be parsed fall back to `Unknown`, which sorts last.
+ *
+ */
+typedef enum BeatStarSongDiffs {
+  BeatStarSongDiffs_Easy,
+  BeatStarSongDiffs_Normal,
+  BeatStarSongDiffs_Hard,
+  BeatStarSongDiffs_Expert,
+  BeatStarSongDiffs_ExpertPlus,
+  BeatStarSongDiffs_Unknown,
+} BeatStarSongDiffs;
+
+/**
+ *
+ * Walks the characteristics of a song in declaration order, custom ones last by name
+ *
+ */
+typedef struct BeatStarCharacteristicIter BeatStarCharacteristicIter;
+
+/**
+ *
+ * The loaded database, opaque to C. Use the accessor functions to read it.
+ * Wraps a `SongDatabase` with the FFI form of its songs.
+ *
+ */
+typedef struct BeatStarDataFile BeatStarDataFile;
+
+/**
+ *
+ * Walks the difficulties of a characteristic from easiest to hardest, then by name
+ *
+ */
+typedef struct BeatStarDifficultyIter BeatStarDifficultyIter;
+
+/**
+ *
+ * The database seen through the layers (user over local over a base) over the C ABI, with the
+ * lookups and queries of `BeatStarDataFile`. Only the songs the layers change or add are built,
+ * every other one is the base's.
+ *
+ * The view borrows the base without owning it: the base must outlive the view, freeing it with
+ * `Beatstar_DatabaseFree` first leaves the view and every song it returned dangling.
+ * Must be freed with `BeatStarLayeredDatabase_Free`
+ *
+ */
+typedef struct BeatStarLayeredDatabase BeatStarLayeredDatabase;
+
+/**
+ *
+ * The local and user layers. They're kept apart from the scraped database,
+ * so a view over a refreshed database keeps every local song and correction.
+ *
+ */
+typedef struct BeatStarLayers BeatStarLayers;
+
+/**
+ *
+ * The level folders of a scan, split by whether the database has them.
+ * Must be freed with `BeatStarLevelScan_Free`
+ *
+ */
+typedef struct BeatStarLevelScan BeatStarLevelScan;
+
+/**
+ *
+ * Songs layered over the loaded database, keyed by uppercase hash.
+ * Local songs are merged in here so they can be looked up like scraped ones.
+ *
+ */
+typedef struct BeatStarOverlay BeatStarOverlay;
+
+/**
+ *
+ * A playlist resolved against a database, see `SongDatabase::resolve_playlist`.
+ * Must be freed with `BeatStarResolvedPlaylist_Free`, before the database.
+ *
+ */
+typedef struct BeatStarResolvedPlaylist BeatStarResolvedPlaylist;
+
+/**
+ *
+ * What changed between two snapshots, see `SnapshotDiff`.
+ * Must be freed with `BeatStarSnapshotDiff_Free`
+ *
+ */
+typedef struct BeatStarSnapshotDiff BeatStarSnapshotDiff;
+
+/**
+ *
+ * A song of the database, opaque to C. `BeatStarSong_GetInfo` reads its fields.
+ *
+ */
+typedef struct BeatStarSong BeatStarSong;
+
+/**
+ *
+ * A characteristic of a song with its difficulties, opaque to C.
+ * Custom characteristics keep their raw name, which is the name the characteristic counts use.
+ *
+ */
+typedef struct BeatStarSongCharacteristic BeatStarSongCharacteristic;
+
+/**
+ *
+ * A list of difficulties returned by a query.
+ * Must be freed with `BeatStarSongDifficultyList_Free`
+ *
+ */
+typedef struct BeatStarSongDifficultyList BeatStarSongDifficultyList;
+
+/**
+ *
+ * A difficulty of a song, opaque to C. `BeatStarSongDifficultyStats_GetInfo` reads its fields.
+ *
+ */
+typedef struct BeatStarSongDifficultyStats BeatStarSongDifficultyStats;
+
+/**
+ *
+ * Walks the songs of a database ordered by hash
+ *
+ */
+typedef struct BeatStarSongIter BeatStarSongIter;
+
+/**
+ *
+ * `sizeof` of every struct passed by value or read directly across the C ABI.
+ * The host fills it from its headers and the library compares it with its own.
+ *
+ */
+typedef struct BeatStarAbiSizes {
+  uintptr_t string_wrapper;
+  uintptr_t string_view;
+  uintptr_t song_info;
+  uintptr_t difficulty_info;
+  uintptr_t difficulty_ref;
+  uintptr_t difficulty_filter;
+  uintptr_t jump_info;
+  uintptr_t song_update;
+  uintptr_t difficulty_change;
+  uintptr_t vote_swing;
+  uintptr_t metadata_edit;
+  uintptr_t playlist_entry;
+  uintptr_t playlist_difficulty;
+  uintptr_t playlist_summary;
+} BeatStarAbiSizes;
+
+/**
+ *
+ * Receives every log event of the library. `target` and `message` are only valid during the call.
+ * Can be called from any thread the library logs from, including ones it didn't start on.
+ *
+ */
+typedef void (*BeatStarLogCallback)(enum BeatStarLogLevel level,
+                                    const char *target,
+                                    const char *message,
+                                    void *user_data);
+
+/**
+ *
+ * A nul terminated UTF-8 string owned by the library.
+ * C only ever borrows it, through a `const char *` or `BeatStarStringView` that is valid
+ * as long as its owner (the database for song fields) and must not be freed.
+ *
+ */
+typedef struct BeatStarCString {
+  char *string_data;
+} BeatStarCString;
+
+/**
+ *
+ * A difficulty along with the song that owns it.
+ * Both point into the database, so they live as long as it.
+ *
+ */
+typedef struct BeatStarSongDifficultyRef {
+  const struct BeatStarSong *song;
+  const struct BeatStarSongDifficultyStats *diff;
+} BeatStarSongDifficultyRef;
+
+/**
+ * Seconds since the unix epoch, always 64 bit so the ABI doesn't depend on the platform's `time_t`
+ */
+typedef int64_t BeatStarUnixTime;
+
+/**
+ *
+ * Ranges a difficulty has to be in to match a query. Ranges are inclusive,
+ * the default filter matches every difficulty. Difficulties without an NJS
+ * are matched on the game's default NJS for the difficulty.
+ *
+ */
+typedef struct BeatStarDifficultyFilter {
+  bool ranked_only;
+  float min_stars;
+  float max_stars;
+  float min_njs;
+  float max_njs;
+  float min_reaction_time_ms;
+  float max_reaction_time_ms;
+} BeatStarDifficultyFilter;
+
+/**
+ *
+ * A borrowed UTF-8 string, also nul terminated.
+ * Valid for as long as its owner, which for database fields is the database.
+ *
+ */
+typedef struct BeatStarStringView {
+  const char *data;
+  uintptr_t len;
+} BeatStarStringView;
+
+/**
+ *
+ * A song re-uploaded under a new hash, matched by its Beat Saver key.
+ * The strings are owned by the report.
+ *
+ */
+typedef struct BeatStarSongUpdate {
+  struct BeatStarStringView key;
+  struct BeatStarStringView old_hash;
+  struct BeatStarStringView new_hash;
+} BeatStarSongUpdate;
+
+/**
+ *
+ * A difficulty whose rank or star rating changed between two snapshots.
+ * The strings are owned by the report.
+ *
+ */
+typedef struct BeatStarDifficultyChange {
+  struct BeatStarStringView hash;
+  struct BeatStarStringView characteristic;
+  struct BeatStarStringView difficulty;
+  /**
+   * 0 when the difficulty wasn't there
+   */
+  float old_stars;
+  /**
+   * 0 when the difficulty is gone
+   */
+  float new_stars;
+} BeatStarDifficultyChange;
+
+/**
+ *
+ * The hash is owned by the report
+ *
+ */
+typedef struct BeatStarVoteSwing {
+  struct BeatStarStringView hash;
+  uint32_t old_upvotes;
+  uint32_t old_downvotes;
+  uint32_t new_upvotes;
+  uint32_t new_downvotes;
+  float old_rating;
+  float new_rating;
+} BeatStarVoteSwing;
+
+/**
+ *
+ * The strings are owned by the report
+ *
+ */
+typedef struct BeatStarMetadataEdit {
+  struct BeatStarStringView hash;
+  /**
+   * The field name as in the scraped data, e.g. `SongName`
+   */
+  struct BeatStarStringView field;
+  struct BeatStarStringView old_value;
+  struct BeatStarStringView new_value;
+} BeatStarMetadataEdit;
+
+/**
+ *
+ * A playlist entry matched against the database. The strings are owned by the resolved playlist.
+ *
+ */
+typedef struct BeatStarPlaylistEntry {
+  struct BeatStarStringView hash;
+  /**
+   * Empty when the playlist has no key for it
+   */
+  struct BeatStarStringView key;
+  enum BeatStarPlaylistEntryStatus status;
+  /**
+   * The database song, null when missing. Lives as long as the database.
+   */
+  const struct BeatStarSong *song;
+} BeatStarPlaylistEntry;
+
+/**
+ *
+ * A difficulty highlighted by a playlist entry. The strings are owned by the resolved playlist.
+ *
+ */
+typedef struct BeatStarPlaylistDifficulty {
+  /**
+   * The index of its entry
+   */
+  uintptr_t entry_index;
+  struct BeatStarStringView characteristic;
+  struct BeatStarStringView name;
+  /**
+   * The difficulty's stars and the rest, null when the song doesn't have it.
+   * Lives as long as the database.
+   */
+  const struct BeatStarSongDifficultyStats *stats;
+} BeatStarPlaylistDifficulty;
+
+typedef struct BeatStarPlaylistSummary {
+  uintptr_t entries;
+  uintptr_t found;
+  uintptr_t stale_hashes;
+  uintptr_t missing;
+  /**
+   * Total length of the songs that resolved
+   */
+  uint64_t duration_secs;
+} BeatStarPlaylistSummary;
+
+/**
+ *
+ * The plain fields of a song
+ *
+ */
+typedef struct BeatStarSongInfo {
+  float bpm;
+  uint32_t upvotes;
+  uint32_t downvotes;
+  uint32_t duration_secs;
+  struct BeatStarStringView key;
+  struct BeatStarStringView song_name;
+  struct BeatStarStringView song_sub_name;
+  struct BeatStarStringView song_author_name;
+  struct BeatStarStringView level_author_name;
+  struct BeatStarStringView uploaded;
+  BeatStarUnixTime uploaded_unix_time;
+  struct BeatStarStringView hash;
+  float heat;
+  float rating;
+} BeatStarSongInfo;
+
+/**
+ *
+ * How far and for how long notes travel before reaching the player
+ *
+ */
+typedef struct BeatStarJumpInfo {
+  /**
+   * Half jump duration in beats, including the NJS offset
+   */
+  float half_jump_duration;
+  /**
+   * Jump distance in meters
+   */
+  float jump_distance;
+  /**
+   * Time from a note spawning to reaching the player, in milliseconds
+   */
+  float reaction_time_ms;
+} BeatStarJumpInfo;
+
+/**
+ *
+ * The plain fields of a difficulty
+ *
+ */
+typedef struct BeatStarSongDifficultyInfo {
+  struct BeatStarStringView diff;
+  enum BeatStarSongDiffs difficulty;
+  struct BeatStarStringView characteristic_name;
+  enum BeatStarCharacteristics diff_characteristics;
+  float approximate_pp_value;
+  float stars;
+  bool ranked;
+  float njs;
+  float njs_offset;
+  uint32_t bombs;
+  uint32_t notes;
+  uint32_t obstacles;
+  struct BeatStarStringView ranked_update_time;
+  BeatStarUnixTime ranked_update_time_unix_epoch;
+  struct BeatStarJumpInfo jump_info;
+} BeatStarSongDifficultyInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ *
+ * Gets the error code of the last call on this thread, `Ok` if it succeeded
+ *
+ */
+enum BeatStarErrorCode Beatstar_LastError(void);
+
+/**
+ *
+ * Gets the error message of the last call on this thread, null if it succeeded.
+ * The string is valid until the next call on this thread.
+ *
+ */
+const char *Beatstar_LastErrorMessage(void);
+
+/**
+ *
+ * Gets the ABI version of the library, `BEATSTAR_ABI_VERSION` in the headers it was built with
+ *
+ */
+uint32_t Beatstar_AbiVersion(void);
+
+/**
+ *
+ * Gets the crate version of the library, e.g. "0.1.0". The string is static.
+ *
+ */
+const char *Beatstar_Version(void);
+
+/**
+ *
+ * Gets the struct sizes the library was built with
+ *
+ */
+struct BeatStarAbiSizes Beatstar_AbiSizes(void);
+
+/**
+ *
+ * Checks the headers the host was compiled with match the library.
+ * Pass `BEATSTAR_ABI_VERSION` and the `sizeof` of each struct from the headers.
+ * Returns false and sets `AbiMismatch` with the differences if they don't match,
+ * in which case no other function should be called.
+ *
+ */
+bool Beatstar_CheckAbi(uint32_t expected_version, const struct BeatStarAbiSizes *sizes);
+
+/**
+ *
+ * Sends the library's logs to `callback` instead of stdout (logcat on Android),
+ * from any thread. Pass null to go back to the default output. `user_data` is passed
+ * back to every call and must stay valid, calls on other threads may still be running.
+ *
+ */
+void Beatstar_SetLogCallback(BeatStarLogCallback callback, void *user_data);
+
+/**
+ *
+ * Sets the most verbose level that gets logged, `Info` by default
+ *
+ */
+void Beatstar_SetLogLevel(enum BeatStarLogLevel level);
+
+/**
+ *
+ * Get the song list and clone it
+ *
+ */
+const struct BeatStarDataFile *Beatstar_RetrieveDatabase(void);
+
+/**
+ *
+ * Download song database to a file, true if successful
+ *
+ */
+bool Beatstar_DownloadDatabaseToFile(const char *file_path);
+
+/**
+ *
+ * Load database from a local file
+ *
+ */
+const struct BeatStarDataFile *Beatstar_RetrieveDatabaseLocal(const char *file_path);
+
+/**
+ *
+ * Get the song based on hash
+ *
+ *
+ */
+const struct BeatStarSong *Beatstar_GetSong(const char *hash);
+
+/**
+ *
+ * Frees a string the library allocated for the caller. Those are returned as `char *`,
+ * while the `const char *` and string views it returns are borrowed and must not be freed.
+ * Null is ignored.
+ *
+ */
+void Beatstar_StringFree(char *str);
+
+/**
+ *
+ * Get the song based on hash
+ *
+ */
+const struct BeatStarSong *BeatStarDataFile_map_SongsGet(const struct BeatStarDataFile *self_i,
+                                                         const struct BeatStarCString *index);
+
+/**
+ *
+ * Get the hash of the song at the index when ordered by hash
+ *
+ */
+const struct BeatStarCString *BeatStarDataFile_map_SongsGetKey(const struct BeatStarDataFile *self_i,
+                                                               uintptr_t index);
+
+/**
+ *
+ * Get the song count
+ *
+ */
+uintptr_t BeatStarDataFile_map_SongsLen(const struct BeatStarDataFile *self_i);
+
+/**
+ *
+ * Get the song based on hash, same as `BeatStarDataFile_map_SongsGet` with a plain string
+ *
+ */
+const struct BeatStarSong *BeatStarDataFile_GetSong(const struct BeatStarDataFile *self_i,
+                                                    const char *hash);
+
+/**
+ *
+ * Get how many diffs were loaded for the characteristic name
+ *
+ */
+const uintptr_t *BeatStarDataFile_map_CharacteristicCountsGet(const struct BeatStarDataFile *self_i,
+                                                              const struct BeatStarCString *index);
+
+/**
+ *
+ * Get the characteristic name at the index when ordered by name
+ *
+ */
+const struct BeatStarCString *BeatStarDataFile_map_CharacteristicCountsGetKey(const struct BeatStarDataFile *self_i,
+                                                                              uintptr_t index);
+
+/**
+ *
+ * Get the number of characteristic names
+ *
+ */
+uintptr_t BeatStarDataFile_map_CharacteristicCountsLen(const struct BeatStarDataFile *self_i);
+
+/**
+ *
+ * Gets the song at the index when ordered by hash
+ *
+ */
+const struct BeatStarSong *BeatStarDataFile_SongAt(const struct BeatStarDataFile *self_i,
+                                                   uintptr_t index);
+
+/**
+ *
+ * Starts iterating the songs ordered by hash.
+ * Must be freed with `Beatstar_SongIterFree`
+ *
+ */
+struct BeatStarSongIter *Beatstar_SongIterNew(const struct BeatStarDataFile *data);
+
+/**
+ *
+ * Gets the next song, null once every song was visited
+ *
+ */
+const struct BeatStarSong *Beatstar_SongIterNext(struct BeatStarSongIter *iter);
+
+void Beatstar_SongIterFree(struct BeatStarSongIter *iter);
+
+/**
+ *
+ * Gets the dataset statistics (counts, distributions and uploads per month) as JSON.
+ * The string is owned by the database and computed on first call.
+ *
+ */
+const char *BeatStarDataFile_StatsJson(const struct BeatStarDataFile *self_i);
+
+/**
+ *
+ * Exports the database to SQLite tables at `path`, replacing the file once the export succeeded.
+ * Duplicate difficulties of a song are skipped with a warning in the log.
+ * Fails with `Unsupported` unless the library was built with the `sqlite` feature.
+ *
+ */
+bool BeatStarDataFile_ExportSqlite(const struct BeatStarDataFile *self_i, const char *path);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarSongDifficultyRef *BeatStarSongDifficultyList_Get(const struct BeatStarSongDifficultyList *self_i,
+                                                                       uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSongDifficultyList_Len(const struct BeatStarSongDifficultyList *self_i);
+
+/**
+ *
+ * Frees a list returned by a query
+ *
+ */
+void BeatStarSongDifficultyList_Free(struct BeatStarSongDifficultyList *list);
+
+/**
+ *
+ * Gets all the difficulties ranked at or after `since`, newest first
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarDataFile_RankedSince(const struct BeatStarDataFile *self_i,
+                                                                BeatStarUnixTime since);
+
+/**
+ *
+ * Gets a filter that matches every difficulty, to be narrowed down by the caller
+ *
+ */
+struct BeatStarDifficultyFilter Beatstar_DifficultyFilterDefault(void);
+
+/**
+ *
+ * Gets all the difficulties matching the filter, ordered by song hash
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarDataFile_QueryDifficulties(const struct BeatStarDataFile *self_i,
+                                                                      const struct BeatStarDifficultyFilter *filter);
+
+/**
+ *
+ * Gets the `limit` most recently ranked difficulties, newest first
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarDataFile_RecentlyRanked(const struct BeatStarDataFile *self_i,
+                                                                   uintptr_t limit);
+
+/**
+ *
+ * Computes the level hash of a folder (Info.dat followed by its difficulty files),
+ * which is the key for `Beatstar_GetSong`. Must be freed with `Beatstar_StringFree`
+ *
+ */
+char *Beatstar_HashLevelFolder(const char *path);
+
+/**
+ *
+ * Builds a song from a level folder's Info.dat and beatmaps, for maps missing from the database.
+ * Its source is `Local`. Must be freed with `Beatstar_LocalSongFree`
+ *
+ */
+struct BeatStarSong *Beatstar_LocalSongNew(const char *path);
+
+void Beatstar_LocalSongFree(struct BeatStarSong *song);
+
+/**
+ *
+ * Creates an empty overlay for local songs. Must be freed with `Beatstar_OverlayFree`
+ *
+ */
+struct BeatStarOverlay *Beatstar_OverlayNew(void);
+
+void Beatstar_OverlayFree(struct BeatStarOverlay *overlay);
+
+/**
+ *
+ * Merges a local song built from every level folder in `levels_dir` into the overlay.
+ * Returns how many were added, folders that can't be read are skipped.
+ *
+ */
+uintptr_t BeatStarOverlay_LoadLevels(struct BeatStarOverlay *self_i, const char *levels_dir);
+
+/**
+ *
+ * Gets the song from the database, or from the overlay if the database doesn't have it
+ *
+ */
+const struct BeatStarSong *BeatStarDataFile_GetSongWithOverlay(const struct BeatStarDataFile *self_i,
+                                                               const struct BeatStarOverlay *overlay,
+                                                               const char *hash);
+
+/**
+ *
+ * Corrects a song's names in the overlay, null keeps the value of the lower layers
+ *
+ */
+bool BeatStarOverlay_SetMetadata(struct BeatStarOverlay *self_i,
+                                 const char *hash,
+                                 const char *song_name,
+                                 const char *song_sub_name,
+                                 const char *song_author_name,
+                                 const char *level_author_name);
+
+/**
+ *
+ * Sets a custom star rating for a difficulty in the overlay. The names are parsed like the
+ * scraped data, so `Expert+` is `ExpertPlus`; a difficulty that doesn't parse is an invalid argument.
+ *
+ */
+bool BeatStarOverlay_SetStars(struct BeatStarOverlay *self_i,
+                              const char *hash,
+                              const char *char_name,
+                              const char *diff_name,
+                              float stars);
+
+/**
+ *
+ * Creates empty local and user layers. Must be freed with `Beatstar_LayersFree`
+ *
+ */
+struct BeatStarLayers *Beatstar_LayersNew(void);
+
+void Beatstar_LayersFree(struct BeatStarLayers *layers);
+
+/**
+ *
+ * Gets one of the layers to fill with the `BeatStarOverlay_*` functions, owned by the layers
+ *
+ */
+struct BeatStarOverlay *BeatStarLayers_Layer(struct BeatStarLayers *self_i,
+                                             enum BeatStarLayer layer);
+
+/**
+ *
+ * Looks through the layers over `base` (user over local over `base`) without copying it.
+ * The songs the layers change are built now, so make a new view after changing the layers
+ * or reloading `base`. `base` must outlive the view: free the view before the database.
+ * Must be freed with `BeatStarLayeredDatabase_Free`
+ *
+ */
+struct BeatStarLayeredDatabase *BeatStarLayers_View(const struct BeatStarLayers *self_i,
+                                                    const struct BeatStarDataFile *base);
+
+void BeatStarLayeredDatabase_Free(struct BeatStarLayeredDatabase *view);
+
+/**
+ *
+ * Gets a song through the layers by hash, owned by the view or its base
+ *
+ */
+const struct BeatStarSong *BeatStarLayeredDatabase_GetSong(const struct BeatStarLayeredDatabase *self_i,
+                                                           const char *hash);
+
+/**
+ *
+ * Gets a song through the layers by hash (in any case) or Beat Saver key.
+ * A key gives the newest upload of the merged songs.
+ *
+ */
+const struct BeatStarSong *BeatStarLayeredDatabase_GetSongById(const struct BeatStarLayeredDatabase *self_i,
+                                                               const char *id);
+
+/**
+ *
+ * Get the song count, including the songs only the layers have
+ *
+ */
+uintptr_t BeatStarLayeredDatabase_SongsLen(const struct BeatStarLayeredDatabase *self_i);
+
+/**
+ *
+ * Gets the song at the index: the base's ordered by hash, then the songs only the layers
+ * have ordered by hash. Iterate from 0 to `BeatStarLayeredDatabase_SongsLen`
+ *
+ */
+const struct BeatStarSong *BeatStarLayeredDatabase_SongAt(const struct BeatStarLayeredDatabase *self_i,
+                                                          uintptr_t index);
+
+/**
+ *
+ * Gets the statistics of the merged songs as JSON, see `BeatStarDataFile_StatsJson`.
+ * The string is owned by the view and computed on first call.
+ *
+ */
+const char *BeatStarLayeredDatabase_StatsJson(const struct BeatStarLayeredDatabase *self_i);
+
+/**
+ *
+ * Exports the merged songs to SQLite tables at `path`, see `BeatStarDataFile_ExportSqlite`
+ *
+ */
+bool BeatStarLayeredDatabase_ExportSqlite(const struct BeatStarLayeredDatabase *self_i,
+                                          const char *path);
+
+/**
+ *
+ * Gets all the difficulties matching the filter through the layers. The base's are ordered
+ * by song hash, followed by the ones of songs only the layers have
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarLayeredDatabase_QueryDifficulties(const struct BeatStarLayeredDatabase *self_i,
+                                                                             const struct BeatStarDifficultyFilter *filter);
+
+/**
+ *
+ * Gets all the difficulties ranked at or after `since` through the layers, newest first
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarLayeredDatabase_RankedSince(const struct BeatStarLayeredDatabase *self_i,
+                                                                       BeatStarUnixTime since);
+
+/**
+ *
+ * Gets the `limit` most recently ranked difficulties through the layers, newest first
+ *
+ */
+struct BeatStarSongDifficultyList *BeatStarLayeredDatabase_RecentlyRanked(const struct BeatStarLayeredDatabase *self_i,
+                                                                          uintptr_t limit);
+
+/**
+ *
+ * Opens a database zip as a handle owned by the caller. Handles don't share anything with each
+ * other or with `Beatstar_RetrieveDatabase`, so several sources can be loaded at once, e.g. to
+ * keep an older snapshot for `Beatstar_Diff`. Works with every `BeatStarDataFile_*` function.
+ * Must be freed with `Beatstar_DatabaseFree`
+ *
+ */
+struct BeatStarDataFile *Beatstar_DatabaseOpen(const char *file_path);
+
+/**
+ *
+ * Frees a handle from `Beatstar_DatabaseOpen`, along with every
+ * song and string borrowed from it. Never the one from `Beatstar_RetrieveDatabase`, null is ignored.
+ *
+ */
+void Beatstar_DatabaseFree(struct BeatStarDataFile *data);
+
+/**
+ *
+ * Gets a song by hash (in any case) or Beat Saver key
+ *
+ */
+const struct BeatStarSong *BeatStarDataFile_GetSongById(const struct BeatStarDataFile *self_i,
+                                                        const char *id);
+
+/**
+ *
+ * Hashes every level folder in `levels_dir` (e.g. CustomLevels) and looks them up in the database
+ *
+ */
+struct BeatStarLevelScan *BeatStarDataFile_ScanLevels(const struct BeatStarDataFile *self_i,
+                                                      const char *levels_dir);
+
+/**
+ *
+ * Hashes every level folder in `levels_dir` and looks them up through the layers
+ *
+ */
+struct BeatStarLevelScan *BeatStarLayeredDatabase_ScanLevels(const struct BeatStarLayeredDatabase *self_i,
+                                                             const char *levels_dir);
+
+uintptr_t BeatStarLevelScan_MatchedLen(const struct BeatStarLevelScan *self_i);
+
+/**
+ *
+ * Gets the database song of the matched folder at the index
+ *
+ */
+const struct BeatStarSong *BeatStarLevelScan_MatchedSong(const struct BeatStarLevelScan *self_i,
+                                                         uintptr_t index);
+
+/**
+ *
+ * Gets the path of the matched folder at the index, owned by the scan
+ *
+ */
+const char *BeatStarLevelScan_MatchedFolder(const struct BeatStarLevelScan *self_i,
+                                            uintptr_t index);
+
+uintptr_t BeatStarLevelScan_UnmatchedLen(const struct BeatStarLevelScan *self_i);
+
+/**
+ *
+ * Gets the path of a folder that couldn't be hashed or isn't in the database, owned by the scan
+ *
+ */
+const char *BeatStarLevelScan_UnmatchedFolder(const struct BeatStarLevelScan *self_i,
+                                              uintptr_t index);
+
+void BeatStarLevelScan_Free(struct BeatStarLevelScan *scan);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarCString *BeatStarSnapshotDiff_AddedSongsGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                 uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_AddedSongsLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarCString *BeatStarSnapshotDiff_RemovedSongsGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                   uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_RemovedSongsLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarSongUpdate *BeatStarSnapshotDiff_UpdatedSongsGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                      uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_UpdatedSongsLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarDifficultyChange *BeatStarSnapshotDiff_NewlyRankedGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                           uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_NewlyRankedLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarDifficultyChange *BeatStarSnapshotDiff_UnrankedGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                        uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_UnrankedLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarDifficultyChange *BeatStarSnapshotDiff_StarChangesGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                           uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_StarChangesLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarVoteSwing *BeatStarSnapshotDiff_VoteSwingsGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                   uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_VoteSwingsLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarMetadataEdit *BeatStarSnapshotDiff_MetadataEditsGet(const struct BeatStarSnapshotDiff *self_i,
+                                                                         uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSnapshotDiff_MetadataEditsLen(const struct BeatStarSnapshotDiff *self_i);
+
+/**
+ *
+ * Compares two snapshots, e.g. one from `Beatstar_DatabaseOpen` and the freshly
+ * downloaded one. The report doesn't point into either database. Null if it can't be built.
+ *
+ */
+struct BeatStarSnapshotDiff *Beatstar_Diff(const struct BeatStarDataFile *old,
+                                           const struct BeatStarDataFile *new_);
+
+/**
+ *
+ * Gets the whole report as JSON, owned by the report
+ *
+ */
+const char *BeatStarSnapshotDiff_Json(const struct BeatStarSnapshotDiff *self_i);
+
+void BeatStarSnapshotDiff_Free(struct BeatStarSnapshotDiff *diff);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarPlaylistEntry *BeatStarResolvedPlaylist_EntriesGet(const struct BeatStarResolvedPlaylist *self_i,
+                                                                        uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarResolvedPlaylist_EntriesLen(const struct BeatStarResolvedPlaylist *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarPlaylistDifficulty *BeatStarResolvedPlaylist_DifficultiesGet(const struct BeatStarResolvedPlaylist *self_i,
+                                                                                  uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarResolvedPlaylist_DifficultiesLen(const struct BeatStarResolvedPlaylist *self_i);
+
+/**
+ *
+ * Reads a .bplist file and looks up every entry by hash, falling back to the key
+ *
+ */
+struct BeatStarResolvedPlaylist *BeatStarDataFile_ResolvePlaylist(const struct BeatStarDataFile *self_i,
+                                                                  const char *path);
+
+/**
+ *
+ * Reads a .bplist file and looks up every entry through the layers, by hash then key.
+ * Must be freed with `BeatStarResolvedPlaylist_Free`, before the view.
+ *
+ */
+struct BeatStarResolvedPlaylist *BeatStarLayeredDatabase_ResolvePlaylist(const struct BeatStarLayeredDatabase *self_i,
+                                                                         const char *path);
+
+/**
+ *
+ * Gets the playlist title, owned by the resolved playlist
+ *
+ */
+const char *BeatStarResolvedPlaylist_Title(const struct BeatStarResolvedPlaylist *self_i);
+
+/**
+ *
+ * Gets how many entries were found, stale or missing and the total duration
+ *
+ */
+struct BeatStarPlaylistSummary BeatStarResolvedPlaylist_Summary(const struct BeatStarResolvedPlaylist *self_i);
+
+void BeatStarResolvedPlaylist_Free(struct BeatStarResolvedPlaylist *playlist);
+
+/**
+ *
+ * An algorithm for getting a song's rating.
+ *
+ * TODO: Remove
+ */
+float BeatStarSong_rating(const struct BeatStarSong *self_i);
+
+/**
+ *
+ * Gets the plain fields of the song, the strings are owned by the database
+ *
+ */
+struct BeatStarSongInfo BeatStarSong_GetInfo(const struct BeatStarSong *self_i);
+
+/**
+ *
+ * Gets whether the song was scraped or built from a local Info.dat
+ *
+ */
+enum BeatStarSongSource BeatStarSong_Source(const struct BeatStarSong *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSong_DiffGet(const struct BeatStarSong *self_i,
+                                                               uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSong_DiffLen(const struct BeatStarSong *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarSongCharacteristic *BeatStarSong_CharacteristicGet(const struct BeatStarSong *self_i,
+                                                                        uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSong_CharacteristicLen(const struct BeatStarSong *self_i);
+
+/**
+ *
+ * Gets the number of characteristics, custom ones included
+ *
+ */
+uintptr_t BeatStarSong_map_CharacteristicsLen(const struct BeatStarSong *self_i);
+
+/**
+ *
+ * Gets the kind of the characteristic at the index, `Custom` for custom ones.
+ * `BeatStarSong_CharacteristicGet` also gives their name.
+ *
+ */
+const enum BeatStarCharacteristics *BeatStarSong_map_CharacteristicsKeyGet(const struct BeatStarSong *self_i,
+                                                                           uintptr_t index);
+
+/**
+ *
+ * Gets the characteristic by its raw name, custom ones included
+ *
+ */
+const struct BeatStarSongCharacteristic *BeatStarSong_GetCharacteristic(const struct BeatStarSong *self_i,
+                                                                        const char *char_name);
+
+/**
+ *
+ * Gets the difficulty stats based on the raw characteristic name + typed difficulty,
+ * custom characteristics included
+ *
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSong_GetDifficultyByName(const struct BeatStarSong *self_i,
+                                                                           const char *char_name,
+                                                                           enum BeatStarSongDiffs diff);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSongCharacteristic_DiffGet(const struct BeatStarSongCharacteristic *self_i,
+                                                                             uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSongCharacteristic_DiffLen(const struct BeatStarSongCharacteristic *self_i);
+
+/**
+ *
+ * Gets the kind of the characteristic, `Custom` for characteristics added by mods
+ *
+ */
+enum BeatStarCharacteristics BeatStarSongCharacteristic_Kind(const struct BeatStarSongCharacteristic *self_i);
+
+/**
+ *
+ * Gets the raw name of the characteristic
+ *
+ */
+const char *BeatStarSongCharacteristic_Name(const struct BeatStarSongCharacteristic *self_i);
+
+/**
+ *
+ * Gets the difficulty stats of the characteristic based on the typed difficulty
+ *
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSongCharacteristic_GetDifficulty(const struct BeatStarSongCharacteristic *self_i,
+                                                                                   enum BeatStarSongDiffs diff);
+
+/**
+ *
+ * Gets the size of the hashmap of the characteristics
+ *
+ */
+uintptr_t BeatStarSong_map_Characteristics_DifficultyStatsLen(const struct BeatStarSong *self_i,
+                                                              enum BeatStarCharacteristics beat_char);
+
+/**
+ *
+ * Gets the difficulty stats based on the characteristic key + difficulty key
+ *
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSong_map_Characteristics_DifficultyStatsGet(const struct BeatStarSong *self_i,
+                                                                                              enum BeatStarCharacteristics beat_char,
+                                                                                              const char *beat_key2);
+
+/**
+ *
+ * Gets the key based on the index, converted to a string.
+ * Difficulties are ordered from easiest to hardest, then by name.
+ *
+ */
+const char *BeatStarSong_map_Characteristics_DifficultyStatsGetStrKey(const struct BeatStarSong *self_i,
+                                                                      enum BeatStarCharacteristics beat_char,
+                                                                      uintptr_t index);
+
+/**
+ *
+ * Starts iterating the characteristics of the song in declaration order,
+ * custom ones last by name. Must be freed with `Beatstar_CharacteristicIterFree`
+ *
+ */
+struct BeatStarCharacteristicIter *Beatstar_CharacteristicIterNew(const struct BeatStarSong *song);
+
+/**
+ *
+ * Gets the next characteristic, null once every characteristic was visited
+ *
+ */
+const struct BeatStarSongCharacteristic *Beatstar_CharacteristicIterNext(struct BeatStarCharacteristicIter *iter);
+
+void Beatstar_CharacteristicIterFree(struct BeatStarCharacteristicIter *iter);
+
+/**
+ *
+ * Starts iterating the difficulties of the characteristic from easiest to hardest,
+ * then by name. Must be freed with `Beatstar_DifficultyIterFree`
+ *
+ */
+struct BeatStarDifficultyIter *Beatstar_DifficultyIterNew(const struct BeatStarSongCharacteristic *characteristic);
+
+/**
+ *
+ * Gets the next difficulty, null once every difficulty was visited
+ *
+ */
+const struct BeatStarSongDifficultyStats *Beatstar_DifficultyIterNext(struct BeatStarDifficultyIter *iter);
+
+void Beatstar_DifficultyIterFree(struct BeatStarDifficultyIter *iter);
+
+/**
+ *
+ * Gets the difficulty stats based on the characteristic + typed difficulty
+ *
+ */
+const struct BeatStarSongDifficultyStats *BeatStarSong_GetDifficulty(const struct BeatStarSong *self_i,
+                                                                     enum BeatStarCharacteristics beat_char,
+                                                                     enum BeatStarSongDiffs diff);
+
+/**
+ *
+ * Gets the raw characteristic name of the difficulty, useful for `Custom` characteristics
+ *
+ */
+const char *BeatStarSongDifficultyStats_CharacteristicName(const struct BeatStarSongDifficultyStats *self_i);
+
+/**
+ *
+ * Gets the maximum score of the difficulty based on its note count
+ *
+ */
+uint32_t BeatStarSongDifficultyStats_MaxScore(const struct BeatStarSongDifficultyStats *self_i);
+
+/**
+ *
+ * Converts a raw score on this difficulty to an accuracy percentage (0-100)
+ *
+ */
+float BeatStarSongDifficultyStats_ScoreToAccuracy(const struct BeatStarSongDifficultyStats *self_i,
+                                                  uint32_t score);
+
+/**
+ *
+ * Converts an accuracy percentage (0-100) on this difficulty to a raw score
+ *
+ */
+uint32_t BeatStarSongDifficultyStats_AccuracyToScore(const struct BeatStarSongDifficultyStats *self_i,
+                                                     float accuracy);
+
+/**
+ *
+ * Calculates the half jump duration, jump distance and reaction time
+ * for a map with the given bpm, NJS and NJS offset
+ *
+ */
+struct BeatStarJumpInfo Beatstar_CalculateJumpInfo(float bpm, float njs, float njs_offset);
+
+/**
+ *
+ * Gets the maximum score of a map with the amount of notes
+ *
+ */
+uint32_t Beatstar_MaxScoreForNotes(uint32_t notes);
+
+/**
+ *
+ * Gets the plain fields of the difficulty, the strings are owned by the database
+ *
+ */
+struct BeatStarSongDifficultyInfo BeatStarSongDifficultyStats_GetInfo(const struct BeatStarSongDifficultyStats *self_i);
+
+/**
+ * Gets the item in the vector from index
+ */
+const struct BeatStarCString *BeatStarSongDifficultyStats_requirementsGet(const struct BeatStarSongDifficultyStats *self_i,
+                                                                          uintptr_t index);
+
+/**
+ * Gets the length of the vector
+ */
+uintptr_t BeatStarSongDifficultyStats_requirementsLen(const struct BeatStarSongDifficultyStats *self_i);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SONG_DATA_CORE_BINDINGS_H */
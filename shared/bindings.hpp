@@ -8,6 +8,20 @@
 
 namespace song_data_core {
 
+///
+/// Version of the C ABI. Bump it whenever an exported function signature,
+/// struct layout or enum order changes, so stale headers fail `Beatstar_CheckAbi`
+///
+constexpr static const uint32_t BEATSTAR_ABI_VERSION = 5;
+
+/// Highest score a single note can give before the combo multiplier
+constexpr static const uint32_t MAX_NOTE_SCORE = 115;
+
+///
+/// How much the net votes (upvotes minus downvotes) of a song must move to be reported
+///
+constexpr static const int64_t BEATSTAR_MIN_VOTE_SWING = 10;
+
 enum class BeatStarCharacteristics {
   Unknown,
   Standard,
@@ -17,62 +31,436 @@ enum class BeatStarCharacteristics {
   Degree90,
   Degree360,
   Lawless,
+  /// A characteristic added by mods, the raw name is kept in the difficulty's `char`
+  Custom,
+};
+
+///
+/// The kind of failure of the last FFI call on this thread
+///
+enum class BeatStarErrorCode {
+  Ok,
+  /// A null or non UTF-8 argument was passed
+  InvalidArgument,
+  /// The data could not be downloaded
+  Network,
+  /// A local file could not be read or written
+  Io,
+  /// The downloaded or local data is not a valid scrapped data zip
+  CorruptData,
+  /// The requested song, difficulty or index does not exist
+  NotFound,
+  /// The library panicked, the message has the panic payload
+  Panic,
+  Unknown,
+  /// The headers the host was compiled with don't match the library
+  AbiMismatch,
+  /// The library was built without the cargo feature the function needs,
+  /// or a file uses a schema the library can't read
+  Unsupported,
+};
+
+///
+/// The layers stacked over the scraped database, from lowest to highest priority
+///
+enum class BeatStarLayer {
+  /// Songs built from local level folders
+  Local,
+  /// The user's corrected metadata and custom star ratings
+  User,
+};
+
+///
+/// Verbosity of the library's logs, each level includes the ones before it
+///
+enum class BeatStarLogLevel {
+  Off,
+  Error,
+  Warn,
+  Info,
+  Debug,
+  Trace,
+};
+
+///
+/// How a playlist entry matched the database
+///
+enum class BeatStarPlaylistEntryStatus {
+  /// The hash is in the database
+  Found,
+  /// The hash isn't, but the key is. The map was updated since the playlist was made.
+  StaleHash,
+  /// Neither the hash nor the key are in the database
+  Missing,
+};
+
+///
+/// Where a song's data comes from
+///
+enum class BeatStarSongSource {
+  /// The scraped BeatSaver data
+  Scraped,
+  /// A level folder's Info.dat, for maps that aren't in the scraped data yet
+  Local,
+  /// Only known from the user's corrections
+  User,
 };
 
-template<typename K = void, typename V = void, typename Hasher = void>
-struct HashMap;
+///
+/// The difficulty of a beatmap, ordered from easiest to hardest.
+/// Difficulties that can't be parsed fall back to `Unknown`, which sorts last.
+///
+enum class SongDiffs {
+  Easy,
+  Normal,
+  Hard,
+  Expert,
+  ExpertPlus,
+  Unknown,
+};
+
+///
+/// Walks the characteristics of a song in declaration order, custom ones last by name
+///
+struct BeatStarCharacteristicIter;
+
+///
+/// The loaded database, opaque to C. Use the accessor functions to read it.
+/// Wraps a `SongDatabase` with the FFI form of its songs.
+///
+struct BeatStarDataFile;
+
+///
+/// Walks the difficulties of a characteristic from easiest to hardest, then by name
+///
+struct BeatStarDifficultyIter;
+
+///
+/// The database seen through the layers (user over local over a base) over the C ABI, with the
+/// lookups and queries of `BeatStarDataFile`. Only the songs the layers change or add are built,
+/// every other one is the base's.
+///
+/// The view borrows the base without owning it: the base must outlive the view, freeing it with
+/// `Beatstar_DatabaseFree` first leaves the view and every song it returned dangling.
+/// Must be freed with `BeatStarLayeredDatabase_Free`
+///
+struct BeatStarLayeredDatabase;
+
+///
+/// The local and user layers. They're kept apart from the scraped database,
+/// so a view over a refreshed database keeps every local song and correction.
+///
+struct BeatStarLayers;
+
+///
+/// The level folders of a scan, split by whether the database has them.
+/// Must be freed with `BeatStarLevelScan_Free`
+///
+struct BeatStarLevelScan;
+
+///
+/// Songs layered over the loaded database, keyed by uppercase hash.
+/// Local songs are merged in here so they can be looked up like scraped ones.
+///
+struct BeatStarOverlay;
+
+///
+/// A playlist resolved against a database, see `SongDatabase::resolve_playlist`.
+/// Must be freed with `BeatStarResolvedPlaylist_Free`, before the database.
+///
+struct BeatStarResolvedPlaylist;
+
+///
+/// What changed between two snapshots, see `SnapshotDiff`.
+/// Must be freed with `BeatStarSnapshotDiff_Free`
+///
+struct BeatStarSnapshotDiff;
+
+///
+/// A song of the database, opaque to C. `BeatStarSong_GetInfo` reads its fields.
+///
+struct BeatStarSong;
+
+///
+/// A characteristic of a song with its difficulties, opaque to C.
+/// Custom characteristics keep their raw name, which is the name the characteristic counts use.
+///
+struct BeatStarSongCharacteristic;
+
+///
+/// A list of difficulties returned by a query.
+/// Must be freed with `BeatStarSongDifficultyList_Free`
+///
+struct BeatStarSongDifficultyList;
 
-template<typename T = void>
-struct Vec;
+///
+/// A difficulty of a song, opaque to C. `BeatStarSongDifficultyStats_GetInfo` reads its fields.
+///
+struct BeatStarSongDifficultyStats;
+
+///
+/// Walks the songs of a database ordered by hash
+///
+struct BeatStarSongIter;
+
+///
+/// `sizeof` of every struct passed by value or read directly across the C ABI.
+/// The host fills it from its headers and the library compares it with its own.
+///
+struct BeatStarAbiSizes {
+  uintptr_t string_wrapper;
+  uintptr_t string_view;
+  uintptr_t song_info;
+  uintptr_t difficulty_info;
+  uintptr_t difficulty_ref;
+  uintptr_t difficulty_filter;
+  uintptr_t jump_info;
+  uintptr_t song_update;
+  uintptr_t difficulty_change;
+  uintptr_t vote_swing;
+  uintptr_t metadata_edit;
+  uintptr_t playlist_entry;
+  uintptr_t playlist_difficulty;
+  uintptr_t playlist_summary;
+};
 
+///
+/// Receives every log event of the library. `target` and `message` are only valid during the call.
+/// Can be called from any thread the library logs from, including ones it didn't start on.
+///
+using BeatStarLogCallback = void(*)(BeatStarLogLevel level,
+                                    const char *target,
+                                    const char *message,
+                                    void *user_data);
+
+///
+/// A nul terminated UTF-8 string owned by the library.
+/// C only ever borrows it, through a `const char *` or `BeatStarStringView` that is valid
+/// as long as its owner (the database for song fields) and must not be freed.
+///
 struct RustCStringWrapper {
   char *string_data;
 };
 
-using UnixTime = time_t;
+///
+/// A difficulty along with the song that owns it.
+/// Both point into the database, so they live as long as it.
+///
+struct BeatStarSongDifficultyRef {
+  const BeatStarSong *song;
+  const BeatStarSongDifficultyStats *diff;
+};
 
-struct BeatStarSongDifficultyStats {
-  RustCStringWrapper diff;
-  float approximate_pp_value;
-  float stars;
-  bool ranked;
-  float njs;
-  float njs_offset;
-  uint32_t bombs;
-  uint32_t notes;
-  uint32_t obstacles;
-  RustCStringWrapper char_;
-  BeatStarCharacteristics diff_characteristics;
-  RustCStringWrapper ranked_update_time;
-  UnixTime ranked_update_time_unix_epoch;
-  Vec<RustCStringWrapper> requirements;
+/// Seconds since the unix epoch, always 64 bit so the ABI doesn't depend on the platform's `time_t`
+using UnixTime = int64_t;
+
+///
+/// Ranges a difficulty has to be in to match a query. Ranges are inclusive,
+/// the default filter matches every difficulty. Difficulties without an NJS
+/// are matched on the game's default NJS for the difficulty.
+///
+struct BeatStarDifficultyFilter {
+  bool ranked_only;
+  float min_stars;
+  float max_stars;
+  float min_njs;
+  float max_njs;
+  float min_reaction_time_ms;
+  float max_reaction_time_ms;
 };
 
-struct BeatStarSong {
+///
+/// A borrowed UTF-8 string, also nul terminated.
+/// Valid for as long as its owner, which for database fields is the database.
+///
+struct BeatStarStringView {
+  const char *data;
+  uintptr_t len;
+};
+
+///
+/// A song re-uploaded under a new hash, matched by its Beat Saver key.
+/// The strings are owned by the report.
+///
+struct BeatStarSongUpdate {
+  BeatStarStringView key;
+  BeatStarStringView old_hash;
+  BeatStarStringView new_hash;
+};
+
+///
+/// A difficulty whose rank or star rating changed between two snapshots.
+/// The strings are owned by the report.
+///
+struct BeatStarDifficultyChange {
+  BeatStarStringView hash;
+  BeatStarStringView characteristic;
+  BeatStarStringView difficulty;
+  /// 0 when the difficulty wasn't there
+  float old_stars;
+  /// 0 when the difficulty is gone
+  float new_stars;
+};
+
+///
+/// The hash is owned by the report
+///
+struct BeatStarVoteSwing {
+  BeatStarStringView hash;
+  uint32_t old_upvotes;
+  uint32_t old_downvotes;
+  uint32_t new_upvotes;
+  uint32_t new_downvotes;
+  float old_rating;
+  float new_rating;
+};
+
+///
+/// The strings are owned by the report
+///
+struct BeatStarMetadataEdit {
+  BeatStarStringView hash;
+  /// The field name as in the scraped data, e.g. `SongName`
+  BeatStarStringView field;
+  BeatStarStringView old_value;
+  BeatStarStringView new_value;
+};
+
+///
+/// A playlist entry matched against the database. The strings are owned by the resolved playlist.
+///
+struct BeatStarPlaylistEntry {
+  BeatStarStringView hash;
+  /// Empty when the playlist has no key for it
+  BeatStarStringView key;
+  BeatStarPlaylistEntryStatus status;
+  /// The database song, null when missing. Lives as long as the database.
+  const BeatStarSong *song;
+};
+
+///
+/// A difficulty highlighted by a playlist entry. The strings are owned by the resolved playlist.
+///
+struct BeatStarPlaylistDifficulty {
+  /// The index of its entry
+  uintptr_t entry_index;
+  BeatStarStringView characteristic;
+  BeatStarStringView name;
+  /// The difficulty's stars and the rest, null when the song doesn't have it.
+  /// Lives as long as the database.
+  const BeatStarSongDifficultyStats *stats;
+};
+
+struct BeatStarPlaylistSummary {
+  uintptr_t entries;
+  uintptr_t found;
+  uintptr_t stale_hashes;
+  uintptr_t missing;
+  /// Total length of the songs that resolved
+  uint64_t duration_secs;
+};
+
+///
+/// The plain fields of a song
+///
+struct BeatStarSongInfo {
   float bpm;
   uint32_t upvotes;
   uint32_t downvotes;
   uint32_t duration_secs;
-  RustCStringWrapper key;
-  RustCStringWrapper song_name;
-  RustCStringWrapper song_sub_name;
-  RustCStringWrapper song_author_name;
-  RustCStringWrapper level_author_name;
-  RustCStringWrapper uploaded;
+  BeatStarStringView key;
+  BeatStarStringView song_name;
+  BeatStarStringView song_sub_name;
+  BeatStarStringView song_author_name;
+  BeatStarStringView level_author_name;
+  BeatStarStringView uploaded;
   UnixTime uploaded_unix_time;
-  RustCStringWrapper hash;
+  BeatStarStringView hash;
   float heat;
   float rating;
-  Vec<BeatStarSongDifficultyStats> diffs;
-  HashMap<BeatStarCharacteristics, HashMap<RustCStringWrapper, BeatStarSongDifficultyStats>> characteristics;
 };
 
-struct BeatStarDataFile {
-  HashMap<RustCStringWrapper, BeatStarSong> songs;
+///
+/// How far and for how long notes travel before reaching the player
+///
+struct BeatStarJumpInfo {
+  /// Half jump duration in beats, including the NJS offset
+  float half_jump_duration;
+  /// Jump distance in meters
+  float jump_distance;
+  /// Time from a note spawning to reaching the player, in milliseconds
+  float reaction_time_ms;
+};
+
+///
+/// The plain fields of a difficulty
+///
+struct BeatStarSongDifficultyInfo {
+  BeatStarStringView diff;
+  SongDiffs difficulty;
+  BeatStarStringView characteristic_name;
+  BeatStarCharacteristics diff_characteristics;
+  float approximate_pp_value;
+  float stars;
+  bool ranked;
+  float njs;
+  float njs_offset;
+  uint32_t bombs;
+  uint32_t notes;
+  uint32_t obstacles;
+  BeatStarStringView ranked_update_time;
+  UnixTime ranked_update_time_unix_epoch;
+  BeatStarJumpInfo jump_info;
 };
 
 extern "C" {
 
+///
+/// Gets the error code of the last call on this thread, `Ok` if it succeeded
+///
+BeatStarErrorCode Beatstar_LastError();
+
+///
+/// Gets the error message of the last call on this thread, null if it succeeded.
+/// The string is valid until the next call on this thread.
+///
+const char *Beatstar_LastErrorMessage();
+
+///
+/// Gets the ABI version of the library, `BEATSTAR_ABI_VERSION` in the headers it was built with
+///
+uint32_t Beatstar_AbiVersion();
+
+///
+/// Gets the crate version of the library, e.g. "0.1.0". The string is static.
+///
+const char *Beatstar_Version();
+
+///
+/// Gets the struct sizes the library was built with
+///
+BeatStarAbiSizes Beatstar_AbiSizes();
+
+///
+/// Checks the headers the host was compiled with match the library.
+/// Pass `BEATSTAR_ABI_VERSION` and the `sizeof` of each struct from the headers.
+/// Returns false and sets `AbiMismatch` with the differences if they don't match,
+/// in which case no other function should be called.
+///
+bool Beatstar_CheckAbi(uint32_t expected_version, const BeatStarAbiSizes *sizes);
+
+///
+/// Sends the library's logs to `callback` instead of stdout (logcat on Android),
+/// from any thread. Pass null to go back to the default output. `user_data` is passed
+/// back to every call and must stay valid, calls on other threads may still be running.
+///
+void Beatstar_SetLogCallback(BeatStarLogCallback callback, void *user_data);
+
+///
+/// Sets the most verbose level that gets logged, `Info` by default
+///
+void Beatstar_SetLogLevel(BeatStarLogLevel level);
+
 ///
 /// Get the song list and clone it
 ///
@@ -95,30 +483,424 @@ const BeatStarDataFile *Beatstar_RetrieveDatabaseLocal(const char *file_path);
 const BeatStarSong *Beatstar_GetSong(const char *hash);
 
 ///
-/// Get the value in the hashmap from the key
+/// Frees a string the library allocated for the caller. Those are returned as `char *`,
+/// while the `const char *` and string views it returns are borrowed and must not be freed.
+/// Null is ignored.
+///
+void Beatstar_StringFree(char *str);
+
+///
+/// Get the song based on hash
 ///
 const BeatStarSong *BeatStarDataFile_map_SongsGet(const BeatStarDataFile *self_i,
                                                   const RustCStringWrapper *index);
 
 ///
-/// Get the key in the hashmap from the index in it's set.
+/// Get the hash of the song at the index when ordered by hash
 ///
 const RustCStringWrapper *BeatStarDataFile_map_SongsGetKey(const BeatStarDataFile *self_i,
                                                            uintptr_t index);
 
 ///
-/// Get the length of the hashmap
+/// Get the song count
 ///
 uintptr_t BeatStarDataFile_map_SongsLen(const BeatStarDataFile *self_i);
 
+///
+/// Get the song based on hash, same as `BeatStarDataFile_map_SongsGet` with a plain string
+///
+const BeatStarSong *BeatStarDataFile_GetSong(const BeatStarDataFile *self_i, const char *hash);
+
+///
+/// Get how many diffs were loaded for the characteristic name
+///
+const uintptr_t *BeatStarDataFile_map_CharacteristicCountsGet(const BeatStarDataFile *self_i,
+                                                              const RustCStringWrapper *index);
+
+///
+/// Get the characteristic name at the index when ordered by name
+///
+const RustCStringWrapper *BeatStarDataFile_map_CharacteristicCountsGetKey(const BeatStarDataFile *self_i,
+                                                                          uintptr_t index);
+
+///
+/// Get the number of characteristic names
+///
+uintptr_t BeatStarDataFile_map_CharacteristicCountsLen(const BeatStarDataFile *self_i);
+
+///
+/// Gets the song at the index when ordered by hash
+///
+const BeatStarSong *BeatStarDataFile_SongAt(const BeatStarDataFile *self_i, uintptr_t index);
+
+///
+/// Starts iterating the songs ordered by hash.
+/// Must be freed with `Beatstar_SongIterFree`
+///
+BeatStarSongIter *Beatstar_SongIterNew(const BeatStarDataFile *data);
+
+///
+/// Gets the next song, null once every song was visited
+///
+const BeatStarSong *Beatstar_SongIterNext(BeatStarSongIter *iter);
+
+void Beatstar_SongIterFree(BeatStarSongIter *iter);
+
+///
+/// Gets the dataset statistics (counts, distributions and uploads per month) as JSON.
+/// The string is owned by the database and computed on first call.
+///
+const char *BeatStarDataFile_StatsJson(const BeatStarDataFile *self_i);
+
+///
+/// Exports the database to SQLite tables at `path`, replacing the file once the export succeeded.
+/// Duplicate difficulties of a song are skipped with a warning in the log.
+/// Fails with `Unsupported` unless the library was built with the `sqlite` feature.
+///
+bool BeatStarDataFile_ExportSqlite(const BeatStarDataFile *self_i, const char *path);
+
+/// Gets the item in the vector from index
+const BeatStarSongDifficultyRef *BeatStarSongDifficultyList_Get(const BeatStarSongDifficultyList *self_i,
+                                                                uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSongDifficultyList_Len(const BeatStarSongDifficultyList *self_i);
+
+///
+/// Frees a list returned by a query
+///
+void BeatStarSongDifficultyList_Free(BeatStarSongDifficultyList *list);
+
+///
+/// Gets all the difficulties ranked at or after `since`, newest first
+///
+BeatStarSongDifficultyList *BeatStarDataFile_RankedSince(const BeatStarDataFile *self_i,
+                                                         UnixTime since);
+
+///
+/// Gets a filter that matches every difficulty, to be narrowed down by the caller
+///
+BeatStarDifficultyFilter Beatstar_DifficultyFilterDefault();
+
+///
+/// Gets all the difficulties matching the filter, ordered by song hash
+///
+BeatStarSongDifficultyList *BeatStarDataFile_QueryDifficulties(const BeatStarDataFile *self_i,
+                                                               const BeatStarDifficultyFilter *filter);
+
+///
+/// Gets the `limit` most recently ranked difficulties, newest first
+///
+BeatStarSongDifficultyList *BeatStarDataFile_RecentlyRanked(const BeatStarDataFile *self_i,
+                                                            uintptr_t limit);
+
+///
+/// Computes the level hash of a folder (Info.dat followed by its difficulty files),
+/// which is the key for `Beatstar_GetSong`. Must be freed with `Beatstar_StringFree`
+///
+char *Beatstar_HashLevelFolder(const char *path);
+
+///
+/// Builds a song from a level folder's Info.dat and beatmaps, for maps missing from the database.
+/// Its source is `Local`. Must be freed with `Beatstar_LocalSongFree`
+///
+BeatStarSong *Beatstar_LocalSongNew(const char *path);
+
+void Beatstar_LocalSongFree(BeatStarSong *song);
+
+///
+/// Creates an empty overlay for local songs. Must be freed with `Beatstar_OverlayFree`
+///
+BeatStarOverlay *Beatstar_OverlayNew();
+
+void Beatstar_OverlayFree(BeatStarOverlay *overlay);
+
+///
+/// Merges a local song built from every level folder in `levels_dir` into the overlay.
+/// Returns how many were added, folders that can't be read are skipped.
+///
+uintptr_t BeatStarOverlay_LoadLevels(BeatStarOverlay *self_i, const char *levels_dir);
+
+///
+/// Gets the song from the database, or from the overlay if the database doesn't have it
+///
+const BeatStarSong *BeatStarDataFile_GetSongWithOverlay(const BeatStarDataFile *self_i,
+                                                        const BeatStarOverlay *overlay,
+                                                        const char *hash);
+
+///
+/// Corrects a song's names in the overlay, null keeps the value of the lower layers
+///
+bool BeatStarOverlay_SetMetadata(BeatStarOverlay *self_i,
+                                 const char *hash,
+                                 const char *song_name,
+                                 const char *song_sub_name,
+                                 const char *song_author_name,
+                                 const char *level_author_name);
+
+///
+/// Sets a custom star rating for a difficulty in the overlay. The names are parsed like the
+/// scraped data, so `Expert+` is `ExpertPlus`; a difficulty that doesn't parse is an invalid argument.
+///
+bool BeatStarOverlay_SetStars(BeatStarOverlay *self_i,
+                              const char *hash,
+                              const char *char_name,
+                              const char *diff_name,
+                              float stars);
+
+///
+/// Creates empty local and user layers. Must be freed with `Beatstar_LayersFree`
+///
+BeatStarLayers *Beatstar_LayersNew();
+
+void Beatstar_LayersFree(BeatStarLayers *layers);
+
+///
+/// Gets one of the layers to fill with the `BeatStarOverlay_*` functions, owned by the layers
+///
+BeatStarOverlay *BeatStarLayers_Layer(BeatStarLayers *self_i, BeatStarLayer layer);
+
+///
+/// Looks through the layers over `base` (user over local over `base`) without copying it.
+/// The songs the layers change are built now, so make a new view after changing the layers
+/// or reloading `base`. `base` must outlive the view: free the view before the database.
+/// Must be freed with `BeatStarLayeredDatabase_Free`
+///
+BeatStarLayeredDatabase *BeatStarLayers_View(const BeatStarLayers *self_i,
+                                             const BeatStarDataFile *base);
+
+void BeatStarLayeredDatabase_Free(BeatStarLayeredDatabase *view);
+
+///
+/// Gets a song through the layers by hash, owned by the view or its base
+///
+const BeatStarSong *BeatStarLayeredDatabase_GetSong(const BeatStarLayeredDatabase *self_i,
+                                                    const char *hash);
+
+///
+/// Gets a song through the layers by hash (in any case) or Beat Saver key.
+/// A key gives the newest upload of the merged songs.
+///
+const BeatStarSong *BeatStarLayeredDatabase_GetSongById(const BeatStarLayeredDatabase *self_i,
+                                                        const char *id);
+
+///
+/// Get the song count, including the songs only the layers have
+///
+uintptr_t BeatStarLayeredDatabase_SongsLen(const BeatStarLayeredDatabase *self_i);
+
+///
+/// Gets the song at the index: the base's ordered by hash, then the songs only the layers
+/// have ordered by hash. Iterate from 0 to `BeatStarLayeredDatabase_SongsLen`
+///
+const BeatStarSong *BeatStarLayeredDatabase_SongAt(const BeatStarLayeredDatabase *self_i,
+                                                   uintptr_t index);
+
+///
+/// Gets the statistics of the merged songs as JSON, see `BeatStarDataFile_StatsJson`.
+/// The string is owned by the view and computed on first call.
+///
+const char *BeatStarLayeredDatabase_StatsJson(const BeatStarLayeredDatabase *self_i);
+
+///
+/// Exports the merged songs to SQLite tables at `path`, see `BeatStarDataFile_ExportSqlite`
+///
+bool BeatStarLayeredDatabase_ExportSqlite(const BeatStarLayeredDatabase *self_i, const char *path);
+
+///
+/// Gets all the difficulties matching the filter through the layers. The base's are ordered
+/// by song hash, followed by the ones of songs only the layers have
+///
+BeatStarSongDifficultyList *BeatStarLayeredDatabase_QueryDifficulties(const BeatStarLayeredDatabase *self_i,
+                                                                      const BeatStarDifficultyFilter *filter);
+
+///
+/// Gets all the difficulties ranked at or after `since` through the layers, newest first
+///
+BeatStarSongDifficultyList *BeatStarLayeredDatabase_RankedSince(const BeatStarLayeredDatabase *self_i,
+                                                                UnixTime since);
+
+///
+/// Gets the `limit` most recently ranked difficulties through the layers, newest first
+///
+BeatStarSongDifficultyList *BeatStarLayeredDatabase_RecentlyRanked(const BeatStarLayeredDatabase *self_i,
+                                                                   uintptr_t limit);
+
+///
+/// Opens a database zip as a handle owned by the caller. Handles don't share anything with each
+/// other or with `Beatstar_RetrieveDatabase`, so several sources can be loaded at once, e.g. to
+/// keep an older snapshot for `Beatstar_Diff`. Works with every `BeatStarDataFile_*` function.
+/// Must be freed with `Beatstar_DatabaseFree`
+///
+BeatStarDataFile *Beatstar_DatabaseOpen(const char *file_path);
+
+///
+/// Frees a handle from `Beatstar_DatabaseOpen`, along with every
+/// song and string borrowed from it. Never the one from `Beatstar_RetrieveDatabase`, null is ignored.
+///
+void Beatstar_DatabaseFree(BeatStarDataFile *data);
+
+///
+/// Gets a song by hash (in any case) or Beat Saver key
+///
+const BeatStarSong *BeatStarDataFile_GetSongById(const BeatStarDataFile *self_i, const char *id);
+
+///
+/// Hashes every level folder in `levels_dir` (e.g. CustomLevels) and looks them up in the database
+///
+BeatStarLevelScan *BeatStarDataFile_ScanLevels(const BeatStarDataFile *self_i,
+                                               const char *levels_dir);
+
+///
+/// Hashes every level folder in `levels_dir` and looks them up through the layers
+///
+BeatStarLevelScan *BeatStarLayeredDatabase_ScanLevels(const BeatStarLayeredDatabase *self_i,
+                                                      const char *levels_dir);
+
+uintptr_t BeatStarLevelScan_MatchedLen(const BeatStarLevelScan *self_i);
+
+///
+/// Gets the database song of the matched folder at the index
+///
+const BeatStarSong *BeatStarLevelScan_MatchedSong(const BeatStarLevelScan *self_i, uintptr_t index);
+
+///
+/// Gets the path of the matched folder at the index, owned by the scan
+///
+const char *BeatStarLevelScan_MatchedFolder(const BeatStarLevelScan *self_i, uintptr_t index);
+
+uintptr_t BeatStarLevelScan_UnmatchedLen(const BeatStarLevelScan *self_i);
+
+///
+/// Gets the path of a folder that couldn't be hashed or isn't in the database, owned by the scan
+///
+const char *BeatStarLevelScan_UnmatchedFolder(const BeatStarLevelScan *self_i, uintptr_t index);
+
+void BeatStarLevelScan_Free(BeatStarLevelScan *scan);
+
+/// Gets the item in the vector from index
+const RustCStringWrapper *BeatStarSnapshotDiff_AddedSongsGet(const BeatStarSnapshotDiff *self_i,
+                                                             uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_AddedSongsLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const RustCStringWrapper *BeatStarSnapshotDiff_RemovedSongsGet(const BeatStarSnapshotDiff *self_i,
+                                                               uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_RemovedSongsLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarSongUpdate *BeatStarSnapshotDiff_UpdatedSongsGet(const BeatStarSnapshotDiff *self_i,
+                                                               uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_UpdatedSongsLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarDifficultyChange *BeatStarSnapshotDiff_NewlyRankedGet(const BeatStarSnapshotDiff *self_i,
+                                                                    uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_NewlyRankedLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarDifficultyChange *BeatStarSnapshotDiff_UnrankedGet(const BeatStarSnapshotDiff *self_i,
+                                                                 uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_UnrankedLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarDifficultyChange *BeatStarSnapshotDiff_StarChangesGet(const BeatStarSnapshotDiff *self_i,
+                                                                    uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_StarChangesLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarVoteSwing *BeatStarSnapshotDiff_VoteSwingsGet(const BeatStarSnapshotDiff *self_i,
+                                                            uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_VoteSwingsLen(const BeatStarSnapshotDiff *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarMetadataEdit *BeatStarSnapshotDiff_MetadataEditsGet(const BeatStarSnapshotDiff *self_i,
+                                                                  uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSnapshotDiff_MetadataEditsLen(const BeatStarSnapshotDiff *self_i);
+
+///
+/// Compares two snapshots, e.g. one from `Beatstar_DatabaseOpen` and the freshly
+/// downloaded one. The report doesn't point into either database. Null if it can't be built.
+///
+BeatStarSnapshotDiff *Beatstar_Diff(const BeatStarDataFile *old, const BeatStarDataFile *new_);
+
+///
+/// Gets the whole report as JSON, owned by the report
+///
+const char *BeatStarSnapshotDiff_Json(const BeatStarSnapshotDiff *self_i);
+
+void BeatStarSnapshotDiff_Free(BeatStarSnapshotDiff *diff);
+
+/// Gets the item in the vector from index
+const BeatStarPlaylistEntry *BeatStarResolvedPlaylist_EntriesGet(const BeatStarResolvedPlaylist *self_i,
+                                                                 uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarResolvedPlaylist_EntriesLen(const BeatStarResolvedPlaylist *self_i);
+
+/// Gets the item in the vector from index
+const BeatStarPlaylistDifficulty *BeatStarResolvedPlaylist_DifficultiesGet(const BeatStarResolvedPlaylist *self_i,
+                                                                           uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarResolvedPlaylist_DifficultiesLen(const BeatStarResolvedPlaylist *self_i);
+
+///
+/// Reads a .bplist file and looks up every entry by hash, falling back to the key
+///
+BeatStarResolvedPlaylist *BeatStarDataFile_ResolvePlaylist(const BeatStarDataFile *self_i,
+                                                           const char *path);
+
+///
+/// Reads a .bplist file and looks up every entry through the layers, by hash then key.
+/// Must be freed with `BeatStarResolvedPlaylist_Free`, before the view.
+///
+BeatStarResolvedPlaylist *BeatStarLayeredDatabase_ResolvePlaylist(const BeatStarLayeredDatabase *self_i,
+                                                                  const char *path);
+
+///
+/// Gets the playlist title, owned by the resolved playlist
+///
+const char *BeatStarResolvedPlaylist_Title(const BeatStarResolvedPlaylist *self_i);
+
+///
+/// Gets how many entries were found, stale or missing and the total duration
+///
+BeatStarPlaylistSummary BeatStarResolvedPlaylist_Summary(const BeatStarResolvedPlaylist *self_i);
+
+void BeatStarResolvedPlaylist_Free(BeatStarResolvedPlaylist *playlist);
+
 ///
 /// An algorithm for getting a song's rating.
 ///
 /// TODO: Remove
 float BeatStarSong_rating(const BeatStarSong *self_i);
 
-/// Gets the item in the vector from index
-const BeatStarSongDifficultyStats *BeatStarSong_DiffPtr(const BeatStarSong *self_i);
+///
+/// Gets the plain fields of the song, the strings are owned by the database
+///
+BeatStarSongInfo BeatStarSong_GetInfo(const BeatStarSong *self_i);
+
+///
+/// Gets whether the song was scraped or built from a local Info.dat
+///
+BeatStarSongSource BeatStarSong_Source(const BeatStarSong *self_i);
 
 /// Gets the item in the vector from index
 const BeatStarSongDifficultyStats *BeatStarSong_DiffGet(const BeatStarSong *self_i,
@@ -127,22 +909,61 @@ const BeatStarSongDifficultyStats *BeatStarSong_DiffGet(const BeatStarSong *self
 /// Gets the length of the vector
 uintptr_t BeatStarSong_DiffLen(const BeatStarSong *self_i);
 
+/// Gets the item in the vector from index
+const BeatStarSongCharacteristic *BeatStarSong_CharacteristicGet(const BeatStarSong *self_i,
+                                                                 uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSong_CharacteristicLen(const BeatStarSong *self_i);
+
 ///
-/// Get the value in the hashmap from the key
+/// Gets the number of characteristics, custom ones included
 ///
-const HashMap<RustCStringWrapper, BeatStarSongDifficultyStats> *useless_BeatStarSong_map_CharacteristicsGet(const BeatStarSong *self_i,
-                                                                                                            const BeatStarCharacteristics *index);
+uintptr_t BeatStarSong_map_CharacteristicsLen(const BeatStarSong *self_i);
 
 ///
-/// Get the key in the hashmap from the index in it's set.
+/// Gets the kind of the characteristic at the index, `Custom` for custom ones.
+/// `BeatStarSong_CharacteristicGet` also gives their name.
 ///
 const BeatStarCharacteristics *BeatStarSong_map_CharacteristicsKeyGet(const BeatStarSong *self_i,
                                                                       uintptr_t index);
 
 ///
-/// Get the length of the hashmap
+/// Gets the characteristic by its raw name, custom ones included
 ///
-uintptr_t BeatStarSong_map_CharacteristicsLen(const BeatStarSong *self_i);
+const BeatStarSongCharacteristic *BeatStarSong_GetCharacteristic(const BeatStarSong *self_i,
+                                                                 const char *char_name);
+
+///
+/// Gets the difficulty stats based on the raw characteristic name + typed difficulty,
+/// custom characteristics included
+///
+const BeatStarSongDifficultyStats *BeatStarSong_GetDifficultyByName(const BeatStarSong *self_i,
+                                                                    const char *char_name,
+                                                                    SongDiffs diff);
+
+/// Gets the item in the vector from index
+const BeatStarSongDifficultyStats *BeatStarSongCharacteristic_DiffGet(const BeatStarSongCharacteristic *self_i,
+                                                                      uintptr_t index);
+
+/// Gets the length of the vector
+uintptr_t BeatStarSongCharacteristic_DiffLen(const BeatStarSongCharacteristic *self_i);
+
+///
+/// Gets the kind of the characteristic, `Custom` for characteristics added by mods
+///
+BeatStarCharacteristics BeatStarSongCharacteristic_Kind(const BeatStarSongCharacteristic *self_i);
+
+///
+/// Gets the raw name of the characteristic
+///
+const char *BeatStarSongCharacteristic_Name(const BeatStarSongCharacteristic *self_i);
+
+///
+/// Gets the difficulty stats of the characteristic based on the typed difficulty
+///
+const BeatStarSongDifficultyStats *BeatStarSongCharacteristic_GetDifficulty(const BeatStarSongCharacteristic *self_i,
+                                                                            SongDiffs diff);
 
 ///
 /// Gets the size of the hashmap of the characteristics
@@ -159,13 +980,82 @@ const BeatStarSongDifficultyStats *BeatStarSong_map_Characteristics_DifficultySt
 
 ///
 /// Gets the key based on the index, converted to a string.
+/// Difficulties are ordered from easiest to hardest, then by name.
 ///
 const char *BeatStarSong_map_Characteristics_DifficultyStatsGetStrKey(const BeatStarSong *self_i,
                                                                       BeatStarCharacteristics beat_char,
                                                                       uintptr_t index);
 
-/// Gets the item in the vector from index
-const RustCStringWrapper *BeatStarSongDifficultyStats_ptr(const BeatStarSongDifficultyStats *self_i);
+///
+/// Starts iterating the characteristics of the song in declaration order,
+/// custom ones last by name. Must be freed with `Beatstar_CharacteristicIterFree`
+///
+BeatStarCharacteristicIter *Beatstar_CharacteristicIterNew(const BeatStarSong *song);
+
+///
+/// Gets the next characteristic, null once every characteristic was visited
+///
+const BeatStarSongCharacteristic *Beatstar_CharacteristicIterNext(BeatStarCharacteristicIter *iter);
+
+void Beatstar_CharacteristicIterFree(BeatStarCharacteristicIter *iter);
+
+///
+/// Starts iterating the difficulties of the characteristic from easiest to hardest,
+/// then by name. Must be freed with `Beatstar_DifficultyIterFree`
+///
+BeatStarDifficultyIter *Beatstar_DifficultyIterNew(const BeatStarSongCharacteristic *characteristic);
+
+///
+/// Gets the next difficulty, null once every difficulty was visited
+///
+const BeatStarSongDifficultyStats *Beatstar_DifficultyIterNext(BeatStarDifficultyIter *iter);
+
+void Beatstar_DifficultyIterFree(BeatStarDifficultyIter *iter);
+
+///
+/// Gets the difficulty stats based on the characteristic + typed difficulty
+///
+const BeatStarSongDifficultyStats *BeatStarSong_GetDifficulty(const BeatStarSong *self_i,
+                                                              BeatStarCharacteristics beat_char,
+                                                              SongDiffs diff);
+
+///
+/// Gets the raw characteristic name of the difficulty, useful for `Custom` characteristics
+///
+const char *BeatStarSongDifficultyStats_CharacteristicName(const BeatStarSongDifficultyStats *self_i);
+
+///
+/// Gets the maximum score of the difficulty based on its note count
+///
+uint32_t BeatStarSongDifficultyStats_MaxScore(const BeatStarSongDifficultyStats *self_i);
+
+///
+/// Converts a raw score on this difficulty to an accuracy percentage (0-100)
+///
+float BeatStarSongDifficultyStats_ScoreToAccuracy(const BeatStarSongDifficultyStats *self_i,
+                                                  uint32_t score);
+
+///
+/// Converts an accuracy percentage (0-100) on this difficulty to a raw score
+///
+uint32_t BeatStarSongDifficultyStats_AccuracyToScore(const BeatStarSongDifficultyStats *self_i,
+                                                     float accuracy);
+
+///
+/// Calculates the half jump duration, jump distance and reaction time
+/// for a map with the given bpm, NJS and NJS offset
+///
+BeatStarJumpInfo Beatstar_CalculateJumpInfo(float bpm, float njs, float njs_offset);
+
+///
+/// Gets the maximum score of a map with the amount of notes
+///
+uint32_t Beatstar_MaxScoreForNotes(uint32_t notes);
+
+///
+/// Gets the plain fields of the difficulty, the strings are owned by the database
+///
+BeatStarSongDifficultyInfo BeatStarSongDifficultyStats_GetInfo(const BeatStarSongDifficultyStats *self_i);
 
 /// Gets the item in the vector from index
 const RustCStringWrapper *BeatStarSongDifficultyStats_requirementsGet(const BeatStarSongDifficultyStats *self_i,
@@ -1,46 +1,574 @@
 #pragma once
 
+// Header only C++ wrapper on top of the C ABI in bindings.hpp.
+// Everything here borrows from the database, which lives for the rest of the program,
+// so songs, difficulties and string views can be copied and stored freely.
+// The exception is `OwnedDatabase`, whose songs are only valid while it lives.
+
+#include <cstdint>
 #include <future>
-#include <thread>
+#include <iterator>
+#include <memory>
+#include <optional>
+#include <stdexcept>
+#include <string>
+#include <string_view>
+#include <utility>
 
 #include "bindings.hpp"
 
 namespace song_data_core
 {
+    namespace detail
+    {
+        inline std::string_view view(BeatStarStringView str)
+        {
+            if (!str.data)
+                return {};
+            return {str.data, static_cast<std::size_t>(str.len)};
+        }
+
+        inline std::string_view view(const char *str)
+        {
+            if (!str)
+                return {};
+            return str;
+        }
+
+        inline std::string_view view(const RustCStringWrapper *str)
+        {
+            if (!str)
+                return {};
+            return view(str->string_data);
+        }
+
+        ///
+        /// Range over a Rust vector or map exposed as `Len` + `Get(index)` functions
+        ///
+        template <typename Owner, typename Raw, typename Value>
+        class IndexedRange
+        {
+        public:
+            using LenFn = uintptr_t (*)(const Owner *);
+            using GetFn = const Raw *(*)(const Owner *, uintptr_t);
+            using ConvertFn = Value (*)(const Raw *);
+
+            struct Source
+            {
+                const Owner *owner;
+                LenFn len;
+                GetFn get;
+                ConvertFn convert;
+
+                std::size_t size() const { return owner ? len(owner) : 0; }
+                Value at(uintptr_t index) const { return convert(get(owner, index)); }
+            };
+
+            class iterator
+            {
+            public:
+                using iterator_category = std::input_iterator_tag;
+                using value_type = Value;
+                using difference_type = std::ptrdiff_t;
+                using pointer = void;
+                using reference = Value;
+
+                iterator() = default;
+                iterator(Source source, uintptr_t index) : source(source), index(index) {}
+
+                Value operator*() const { return source.at(index); }
+
+                iterator &operator++()
+                {
+                    ++index;
+                    return *this;
+                }
+
+                iterator operator++(int)
+                {
+                    auto copy = *this;
+                    ++index;
+                    return copy;
+                }
+
+                bool operator==(const iterator &other) const { return index == other.index; }
+                bool operator!=(const iterator &other) const { return index != other.index; }
+
+            private:
+                Source source{};
+                uintptr_t index = 0;
+            };
+
+            IndexedRange(const Owner *owner, LenFn len, GetFn get, ConvertFn convert)
+                : source{owner, len, get, convert} {}
+
+            iterator begin() const { return {source, 0}; }
+            iterator end() const { return {source, size()}; }
+
+            std::size_t size() const { return source.size(); }
+            bool empty() const { return size() == 0; }
+
+            Value operator[](std::size_t index) const { return source.at(index); }
+
+        private:
+            Source source;
+        };
+
+        ///
+        /// Range over one of the Rust iterator handles, each `begin()` starts a new handle.
+        /// `Traits` provides `Handle`, `Value`, `create()`, `next(handle)` and `free(handle)`.
+        ///
+        template <typename Traits>
+        class HandleRange
+        {
+        public:
+            using Handle = typename Traits::Handle;
+            using Value = typename Traits::Value;
+
+            struct sentinel
+            {
+            };
+
+            class iterator
+            {
+            public:
+                using iterator_category = std::input_iterator_tag;
+                using value_type = Value;
+                using difference_type = std::ptrdiff_t;
+                using pointer = const Value *;
+                using reference = const Value &;
+
+                explicit iterator(Handle *handle) : handle(handle, &Traits::free)
+                {
+                    advance();
+                }
+
+                const Value &operator*() const { return *current; }
+                const Value *operator->() const { return &*current; }
+
+                iterator &operator++()
+                {
+                    advance();
+                    return *this;
+                }
+
+                bool operator==(sentinel) const { return !current; }
+                bool operator!=(sentinel) const { return current.has_value(); }
+
+            private:
+                void advance()
+                {
+                    current = handle ? Traits::next(handle.get()) : std::nullopt;
+                }
+
+                std::shared_ptr<Handle> handle;
+                std::optional<Value> current;
+            };
+
+            explicit HandleRange(Traits traits) : traits(std::move(traits)) {}
+
+            iterator begin() const { return iterator(traits.create()); }
+            sentinel end() const { return {}; }
+
+        private:
+            Traits traits;
+        };
+    }
+
+    ///
+    /// The last error of the C ABI on this thread, thrown by the wrapper when a call fails
+    ///
+    class Error : public std::runtime_error
+    {
+    public:
+        Error(BeatStarErrorCode code, const std::string &message) : std::runtime_error(message), code(code) {}
+
+        static Error last()
+        {
+            auto message = Beatstar_LastErrorMessage();
+            return Error(Beatstar_LastError(), message ? message : "Unknown error");
+        }
+
+        BeatStarErrorCode code;
+    };
+
+    ///
+    /// Checks the library matches the headers this was compiled with, throws `Error` if it doesn't.
+    /// Called once before the database is loaded, so a stale library fails loudly instead of corrupting memory.
+    ///
+    inline void checkAbi()
+    {
+        // Empty if compatible, otherwise the mismatches reported by the library
+        static const std::string mismatch = []() -> std::string
+        {
+            BeatStarAbiSizes sizes{};
+            sizes.string_wrapper = sizeof(RustCStringWrapper);
+            sizes.string_view = sizeof(BeatStarStringView);
+            sizes.song_info = sizeof(BeatStarSongInfo);
+            sizes.difficulty_info = sizeof(BeatStarSongDifficultyInfo);
+            sizes.difficulty_ref = sizeof(BeatStarSongDifficultyRef);
+            sizes.difficulty_filter = sizeof(BeatStarDifficultyFilter);
+            sizes.jump_info = sizeof(BeatStarJumpInfo);
+            sizes.song_update = sizeof(BeatStarSongUpdate);
+            sizes.difficulty_change = sizeof(BeatStarDifficultyChange);
+            sizes.vote_swing = sizeof(BeatStarVoteSwing);
+            sizes.metadata_edit = sizeof(BeatStarMetadataEdit);
+            sizes.playlist_entry = sizeof(BeatStarPlaylistEntry);
+            sizes.playlist_difficulty = sizeof(BeatStarPlaylistDifficulty);
+            sizes.playlist_summary = sizeof(BeatStarPlaylistSummary);
+            if (Beatstar_CheckAbi(BEATSTAR_ABI_VERSION, &sizes))
+                return {};
+
+            auto message = Beatstar_LastErrorMessage();
+            return message ? message : "song_data_core library doesn't match its headers";
+        }();
+
+        if (!mismatch.empty())
+            throw Error(BeatStarErrorCode::AbiMismatch, mismatch);
+    }
+
+    class Difficulty
+    {
+    public:
+        explicit Difficulty(const BeatStarSongDifficultyStats *diff) : diff(diff) {}
+
+        const BeatStarSongDifficultyStats *raw() const { return diff; }
+
+        BeatStarSongDifficultyInfo info() const { return BeatStarSongDifficultyStats_GetInfo(diff); }
+
+        std::string_view name() const { return detail::view(info().diff); }
+        SongDiffs difficulty() const { return info().difficulty; }
+        BeatStarCharacteristics characteristic() const { return info().diff_characteristics; }
+        std::string_view characteristicName() const { return detail::view(info().characteristic_name); }
+
+        float stars() const { return info().stars; }
+        float pp() const { return info().approximate_pp_value; }
+        bool ranked() const { return info().ranked; }
+        float njs() const { return info().njs; }
+        float njsOffset() const { return info().njs_offset; }
+        uint32_t notes() const { return info().notes; }
+        uint32_t bombs() const { return info().bombs; }
+        uint32_t obstacles() const { return info().obstacles; }
+        UnixTime rankedUpdateTime() const { return info().ranked_update_time_unix_epoch; }
+        BeatStarJumpInfo jumpInfo() const { return info().jump_info; }
+
+        uint32_t maxScore() const { return BeatStarSongDifficultyStats_MaxScore(diff); }
+        float scoreToAccuracy(uint32_t score) const { return BeatStarSongDifficultyStats_ScoreToAccuracy(diff, score); }
+        uint32_t accuracyToScore(float accuracy) const { return BeatStarSongDifficultyStats_AccuracyToScore(diff, accuracy); }
+
+        detail::IndexedRange<BeatStarSongDifficultyStats, RustCStringWrapper, std::string_view> requirements() const
+        {
+            return {diff, &BeatStarSongDifficultyStats_requirementsLen, &BeatStarSongDifficultyStats_requirementsGet,
+                    [](const RustCStringWrapper *str) { return detail::view(str); }};
+        }
+
+    private:
+        const BeatStarSongDifficultyStats *diff;
+    };
+
+    namespace detail
+    {
+        struct DifficultyIterTraits
+        {
+            using Handle = BeatStarDifficultyIter;
+            using Value = Difficulty;
+
+            const BeatStarSongCharacteristic *characteristic;
+
+            Handle *create() const { return Beatstar_DifficultyIterNew(characteristic); }
+
+            static std::optional<Value> next(Handle *handle)
+            {
+                auto diff = Beatstar_DifficultyIterNext(handle);
+                if (!diff)
+                    return std::nullopt;
+                return Difficulty(diff);
+            }
+
+            static void free(Handle *handle) { Beatstar_DifficultyIterFree(handle); }
+        };
+    }
+
+    ///
+    /// A characteristic of a song, custom ones keep their raw name
+    ///
+    class Characteristic
+    {
+    public:
+        explicit Characteristic(const BeatStarSongCharacteristic *characteristic) : characteristic(characteristic) {}
+
+        const BeatStarSongCharacteristic *raw() const { return characteristic; }
+
+        BeatStarCharacteristics kind() const { return BeatStarSongCharacteristic_Kind(characteristic); }
+        std::string_view name() const { return detail::view(BeatStarSongCharacteristic_Name(characteristic)); }
+
+        ///
+        /// The difficulties from easiest to hardest, then by name
+        ///
+        detail::HandleRange<detail::DifficultyIterTraits> difficulties() const
+        {
+            return detail::HandleRange<detail::DifficultyIterTraits>({characteristic});
+        }
+
+        std::optional<Difficulty> difficulty(SongDiffs diff) const
+        {
+            auto stats = BeatStarSongCharacteristic_GetDifficulty(characteristic, diff);
+            if (!stats)
+                return std::nullopt;
+            return Difficulty(stats);
+        }
+
+    private:
+        const BeatStarSongCharacteristic *characteristic;
+    };
 
-    std::promise<const BeatStarDataFile *> loadDatabaseAsync()
+    namespace detail
     {
-        std::promise<const BeatStarDataFile *> promise;
+        struct CharacteristicIterTraits
+        {
+            using Handle = BeatStarCharacteristicIter;
+            using Value = Characteristic;
 
-        std::thread t([&]
-                      { promise.set_value(Beatstar_RetrieveDatabase()); });
-        t.detach();
+            const BeatStarSong *song;
 
-        return promise;
+            Handle *create() const { return Beatstar_CharacteristicIterNew(song); }
+
+            static std::optional<Value> next(Handle *handle)
+            {
+                auto characteristic = Beatstar_CharacteristicIterNext(handle);
+                if (!characteristic)
+                    return std::nullopt;
+                return Characteristic(characteristic);
+            }
+
+            static void free(Handle *handle) { Beatstar_CharacteristicIterFree(handle); }
+        };
     }
 
-    template <typename K = void, typename V = void, typename Hasher = void>
-    struct HashmapWrapper
+    class Song
     {
-        const HashMap<K, V, Hasher> *hashmap;
+    public:
+        explicit Song(const BeatStarSong *song) : song(song) {}
+
+        const BeatStarSong *raw() const { return song; }
+
+        BeatStarSongInfo info() const { return BeatStarSong_GetInfo(song); }
+
+        std::string_view hash() const { return detail::view(info().hash); }
+        std::string_view key() const { return detail::view(info().key); }
+        std::string_view songName() const { return detail::view(info().song_name); }
+        std::string_view songSubName() const { return detail::view(info().song_sub_name); }
+        std::string_view songAuthorName() const { return detail::view(info().song_author_name); }
+        std::string_view levelAuthorName() const { return detail::view(info().level_author_name); }
+        float bpm() const { return info().bpm; }
+        uint32_t durationSecs() const { return info().duration_secs; }
+        UnixTime uploadedTime() const { return info().uploaded_unix_time; }
+
+        ///
+        /// The characteristics of the song in declaration order, custom ones last by name
+        ///
+        detail::HandleRange<detail::CharacteristicIterTraits> characteristics() const
+        {
+            return detail::HandleRange<detail::CharacteristicIterTraits>({song});
+        }
+
+        ///
+        /// Looks the characteristic up by its raw name, custom ones included
+        ///
+        std::optional<Characteristic> characteristic(const std::string &name) const
+        {
+            auto characteristic = BeatStarSong_GetCharacteristic(song, name.c_str());
+            if (!characteristic)
+                return std::nullopt;
+            return Characteristic(characteristic);
+        }
 
-        const std::function<size_t()> getLengthFunc;
-        const std::function<K(size_t)> getKeyFunc;
-        const std::function<V(K)> getValueFunc;
+        ///
+        /// Every difficulty of the song, in the scrapped data's order
+        ///
+        detail::IndexedRange<BeatStarSong, BeatStarSongDifficultyStats, Difficulty> allDifficulties() const
+        {
+            return {song, &BeatStarSong_DiffLen, &BeatStarSong_DiffGet,
+                    [](const BeatStarSongDifficultyStats *diff) { return Difficulty(diff); }};
+        }
+
+        std::optional<Difficulty> difficulty(BeatStarCharacteristics characteristic, SongDiffs diff) const
+        {
+            auto stats = BeatStarSong_GetDifficulty(song, characteristic, diff);
+            if (!stats)
+                return std::nullopt;
+            return Difficulty(stats);
+        }
+
+        /// Looks the characteristic up by its raw name, custom ones included
+        std::optional<Difficulty> difficulty(const std::string &characteristic, SongDiffs diff) const
+        {
+            auto stats = BeatStarSong_GetDifficultyByName(song, characteristic.c_str(), diff);
+            if (!stats)
+                return std::nullopt;
+            return Difficulty(stats);
+        }
+
+    private:
+        const BeatStarSong *song;
     };
 
-#define CONVERT_RUST_TO_CPP_MAP(hashmap, funcPrefix, type) HashmapWrapper( \
-    hashmap, [] { return funcPrefix##Len(hashmap); }, [](size_t index) { return funcPrefix##Get(hashmap, index); }, [](type key) { return funcPrefix##KeyGet(hashmap, key); })
+    ///
+    /// Owns a list returned by a query, iterating it gives the song and difficulty of each entry
+    ///
+    class DifficultyList
+    {
+    public:
+        using Entry = std::pair<Song, Difficulty>;
+
+        explicit DifficultyList(BeatStarSongDifficultyList *list) : list(list, &BeatStarSongDifficultyList_Free) {}
+
+        detail::IndexedRange<BeatStarSongDifficultyList, BeatStarSongDifficultyRef, Entry> entries() const
+        {
+            return {list.get(), &BeatStarSongDifficultyList_Len, &BeatStarSongDifficultyList_Get,
+                    [](const BeatStarSongDifficultyRef *entry) { return Entry(Song(entry->song), Difficulty(entry->diff)); }};
+        }
 
-    template <typename K = void>
-    struct VectorWrapper
+        auto begin() const { return entries().begin(); }
+        auto end() const { return entries().end(); }
+        std::size_t size() const { return entries().size(); }
+        bool empty() const { return entries().empty(); }
+
+    private:
+        std::unique_ptr<BeatStarSongDifficultyList, void (*)(BeatStarSongDifficultyList *)> list;
+    };
+
+    class Database
+    {
+    public:
+        explicit Database(const BeatStarDataFile *data) : data(data) {}
+
+        ///
+        /// Downloads the database, or returns the already loaded one. Throws `Error` on failure.
+        ///
+        static Database download()
+        {
+            checkAbi();
+            auto data = Beatstar_RetrieveDatabase();
+            if (!data)
+                throw Error::last();
+            return Database(data);
+        }
+
+        ///
+        /// Loads the database from a local zip, or returns the already loaded one. Throws `Error` on failure.
+        ///
+        static Database load(const std::string &path)
+        {
+            checkAbi();
+            auto data = Beatstar_RetrieveDatabaseLocal(path.c_str());
+            if (!data)
+                throw Error::last();
+            return Database(data);
+        }
+
+        const BeatStarDataFile *raw() const { return data; }
+
+        std::size_t size() const { return BeatStarDataFile_map_SongsLen(data); }
+
+        ///
+        /// The songs ordered by hash
+        ///
+        detail::IndexedRange<BeatStarDataFile, BeatStarSong, Song> songs() const
+        {
+            return {data, &BeatStarDataFile_map_SongsLen, &BeatStarDataFile_SongAt,
+                    [](const BeatStarSong *song) { return Song(song); }};
+        }
+
+        std::optional<Song> song(const std::string &hash) const
+        {
+            auto song = BeatStarDataFile_GetSong(data, hash.c_str());
+            if (!song)
+                return std::nullopt;
+            return Song(song);
+        }
+
+        DifficultyList query(const BeatStarDifficultyFilter &filter) const
+        {
+            return checked(BeatStarDataFile_QueryDifficulties(data, &filter));
+        }
+
+        DifficultyList rankedSince(UnixTime since) const
+        {
+            return checked(BeatStarDataFile_RankedSince(data, since));
+        }
+
+        DifficultyList recentlyRanked(std::size_t limit) const
+        {
+            return checked(BeatStarDataFile_RecentlyRanked(data, limit));
+        }
+
+        std::string_view statsJson() const { return detail::view(BeatStarDataFile_StatsJson(data)); }
+
+    private:
+        static DifficultyList checked(BeatStarSongDifficultyList *list)
+        {
+            if (!list)
+                throw Error::last();
+            return DifficultyList(list);
+        }
+
+        const BeatStarDataFile *data;
+    };
+
+    ///
+    /// A database handle owning its songs, independent of `Database::load` and other handles
+    ///
+    class OwnedDatabase
     {
-        Vec<K> *vector;
+    public:
+        ///
+        /// Opens a database zip. Throws `Error` on failure.
+        ///
+        static OwnedDatabase open(const std::string &path)
+        {
+            checkAbi();
+            auto handle = Beatstar_DatabaseOpen(path.c_str());
+            if (!handle)
+                throw Error::last();
+            return OwnedDatabase(handle);
+        }
+
+        ///
+        /// The queries of `Database`, valid while this handle lives
+        ///
+        Database view() const { return Database(handle.get()); }
+
+        ///
+        /// Gets a song by hash or Beat Saver key
+        ///
+        std::optional<Song> song(const std::string &id) const
+        {
+            auto song = BeatStarDataFile_GetSongById(handle.get(), id.c_str());
+            if (!song)
+                return std::nullopt;
+            return Song(song);
+        }
 
-        std::function<size_t()> getLengthFunc;
-        std::function<K(size_t)> getValueFunc;
+    private:
+        explicit OwnedDatabase(BeatStarDataFile *handle) : handle(handle, &Beatstar_DatabaseFree) {}
+
+        std::unique_ptr<BeatStarDataFile, void (*)(BeatStarDataFile *)> handle;
     };
 
-#define CONVERT_RUST_TO_CPP_VEC(vec, funcPrefix) VectorWrapper( \
-    vec, [] { return funcPrefix##Len(hashmap); }, [](size_t index) { return funcPrefix##Get(hashmap, index); })
-}
\ No newline at end of file
+    ///
+    /// Downloads the database on another thread
+    ///
+    inline std::future<Database> loadDatabaseAsync()
+    {
+        return std::async(std::launch::async, [] { return Database::download(); });
+    }
+
+    ///
+    /// Loads the database from a local zip on another thread
+    ///
+    inline std::future<Database> loadDatabaseAsync(std::string path)
+    {
+        return std::async(std::launch::async, [path = std::move(path)] { return Database::load(path); });
+    }
+}
@@ -1,14 +0,0 @@
-#pragma once
-
-namespace song_data_core  {
-template <typename K, typename V, typename Hasher>
-struct HashMap
-{
-};
-
-template <typename T>
-struct Vec {
-};
-}
-
-#include "bindings.hpp"
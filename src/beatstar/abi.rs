@@ -0,0 +1,115 @@
+use std::mem::size_of;
+
+use crate::beatstar::bplist::BeatStarPlaylistSummary;
+use crate::beatstar::error::{BeatStarError, BeatStarErrorCode};
+use crate::beatstar::ffi::{
+    BeatStarDifficultyChange, BeatStarMetadataEdit, BeatStarPlaylistDifficulty,
+    BeatStarPlaylistEntry, BeatStarSongDifficultyInfo, BeatStarSongDifficultyRef,
+    BeatStarSongInfo, BeatStarSongUpdate, BeatStarStringView, BeatStarVoteSwing,
+    RustCStringWrapper,
+};
+use crate::beatstar::jump::BeatStarJumpInfo;
+use crate::beatstar::query::BeatStarDifficultyFilter;
+
+///
+/// Version of the C ABI. Bump it whenever an exported function signature,
+/// struct layout or enum order changes, so stale headers fail `Beatstar_CheckAbi`
+///
+pub const BEATSTAR_ABI_VERSION: u32 = 5;
+
+/// The crate semver, nul terminated for C
+pub(crate) const BEATSTAR_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+///
+/// `sizeof` of every struct passed by value or read directly across the C ABI.
+/// The host fills it from its headers and the library compares it with its own.
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BeatStarAbiSizes {
+    pub string_wrapper: usize,
+    pub string_view: usize,
+    pub song_info: usize,
+    pub difficulty_info: usize,
+    pub difficulty_ref: usize,
+    pub difficulty_filter: usize,
+    pub jump_info: usize,
+    pub song_update: usize,
+    pub difficulty_change: usize,
+    pub vote_swing: usize,
+    pub metadata_edit: usize,
+    pub playlist_entry: usize,
+    pub playlist_difficulty: usize,
+    pub playlist_summary: usize,
+}
+
+impl BeatStarAbiSizes {
+    ///
+    /// The sizes this library was built with
+    ///
+    pub fn current() -> BeatStarAbiSizes {
+        BeatStarAbiSizes {
+            string_wrapper: size_of::<RustCStringWrapper>(),
+            string_view: size_of::<BeatStarStringView>(),
+            song_info: size_of::<BeatStarSongInfo>(),
+            difficulty_info: size_of::<BeatStarSongDifficultyInfo>(),
+            difficulty_ref: size_of::<BeatStarSongDifficultyRef>(),
+            difficulty_filter: size_of::<BeatStarDifficultyFilter>(),
+            jump_info: size_of::<BeatStarJumpInfo>(),
+            song_update: size_of::<BeatStarSongUpdate>(),
+            difficulty_change: size_of::<BeatStarDifficultyChange>(),
+            vote_swing: size_of::<BeatStarVoteSwing>(),
+            metadata_edit: size_of::<BeatStarMetadataEdit>(),
+            playlist_entry: size_of::<BeatStarPlaylistEntry>(),
+            playlist_difficulty: size_of::<BeatStarPlaylistDifficulty>(),
+            playlist_summary: size_of::<BeatStarPlaylistSummary>(),
+        }
+    }
+}
+
+///
+/// Checks the version and struct sizes the host was compiled with against this library
+///
+pub fn check_abi(expected_version: u32, sizes: &BeatStarAbiSizes) -> Result<(), BeatStarError> {
+    if expected_version != BEATSTAR_ABI_VERSION {
+        return Err(BeatStarError::new(
+            BeatStarErrorCode::AbiMismatch,
+            format!(
+                "Headers are for ABI version {expected_version} but the library is version {BEATSTAR_ABI_VERSION}"
+            ),
+        ));
+    }
+
+    let current = BeatStarAbiSizes::current();
+    let fields = [
+        ("string_wrapper", sizes.string_wrapper, current.string_wrapper),
+        ("string_view", sizes.string_view, current.string_view),
+        ("song_info", sizes.song_info, current.song_info),
+        ("difficulty_info", sizes.difficulty_info, current.difficulty_info),
+        ("difficulty_ref", sizes.difficulty_ref, current.difficulty_ref),
+        ("difficulty_filter", sizes.difficulty_filter, current.difficulty_filter),
+        ("jump_info", sizes.jump_info, current.jump_info),
+        ("song_update", sizes.song_update, current.song_update),
+        ("difficulty_change", sizes.difficulty_change, current.difficulty_change),
+        ("vote_swing", sizes.vote_swing, current.vote_swing),
+        ("metadata_edit", sizes.metadata_edit, current.metadata_edit),
+        ("playlist_entry", sizes.playlist_entry, current.playlist_entry),
+        ("playlist_difficulty", sizes.playlist_difficulty, current.playlist_difficulty),
+        ("playlist_summary", sizes.playlist_summary, current.playlist_summary),
+    ];
+
+    let mismatches: Vec<String> = fields
+        .iter()
+        .filter(|(_, expected, actual)| expected != actual)
+        .map(|(name, expected, actual)| format!("{name} is {expected} bytes, expected {actual}"))
+        .collect();
+
+    if !mismatches.is_empty() {
+        return Err(BeatStarError::new(
+            BeatStarErrorCode::AbiMismatch,
+            format!("Struct sizes don't match the library: {0}", mismatches.join(", ")),
+        ));
+    }
+
+    Ok(())
+}
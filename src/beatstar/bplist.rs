@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::beatstar::model::{Difficulty, Song};
+use crate::beatstar::query::SongDifficultyPair;
+use crate::beatstar::song_database::{SongDatabase, SongLookup};
+
+///
+/// A Beat Saber playlist (.bplist), the format PlaylistManager and BeatSaver use
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BPList {
+    pub playlist_title: String,
+    #[serde(default)]
+    pub playlist_author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_description: Option<String>,
+    /// Base64 cover image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub songs: Vec<BPListSong>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BPListSong {
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub song_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_author_name: Option<String>,
+    /// The difficulties to highlight, empty for the whole song
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub difficulties: Vec<BPListDifficulty>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BPListDifficulty {
+    pub characteristic: String,
+    pub name: String,
+}
+
+impl From<&Song> for BPListSong {
+    fn from(song: &Song) -> Self {
+        let text = |value: &String| if value.is_empty() { None } else { Some(value.clone()) };
+
+        BPListSong {
+            hash: song.hash.clone(),
+            key: text(&song.key),
+            song_name: text(&song.song_name),
+            level_author_name: text(&song.level_author_name),
+            difficulties: vec![],
+        }
+    }
+}
+
+impl BPList {
+    pub fn new(title: &str) -> BPList {
+        BPList {
+            playlist_title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// A playlist of whole songs, in the given order
+    ///
+    pub fn from_songs<'a>(
+        title: &str,
+        songs: impl IntoIterator<Item = &'a Song>,
+    ) -> BPList {
+        BPList {
+            songs: songs.into_iter().map(BPListSong::from).collect(),
+            ..BPList::new(title)
+        }
+    }
+
+    ///
+    /// A playlist of the songs of the difficulties, each listing its difficulties.
+    /// Songs are in the order they first appear.
+    ///
+    pub fn from_difficulties(title: &str, pairs: &[SongDifficultyPair]) -> BPList {
+        let mut playlist = BPList::new(title);
+        let mut indices: HashMap<&str, usize> = HashMap::new();
+
+        for (song, diff) in pairs {
+            let index = *indices.entry(song.hash.as_str()).or_insert_with(|| {
+                playlist.songs.push(BPListSong::from(*song));
+                playlist.songs.len() - 1
+            });
+
+            playlist.songs[index].difficulties.push(BPListDifficulty {
+                characteristic: diff.characteristic.name().to_string(),
+                name: diff.name.clone(),
+            });
+        }
+
+        playlist
+    }
+
+    ///
+    /// Reads a .bplist file
+    ///
+    pub fn read(path: &Path) -> anyhow::Result<BPList> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {0}", path.display()))?;
+
+        serde_json::from_str(&text)
+            .with_context(|| format!("{0} is not a playlist", path.display()))
+    }
+}
+
+///
+/// How a playlist entry matched the database
+///
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BeatStarPlaylistEntryStatus {
+    /// The hash is in the database
+    Found,
+    /// The hash isn't, but the key is. The map was updated since the playlist was made.
+    StaleHash,
+    /// Neither the hash nor the key are in the database
+    Missing,
+}
+
+///
+/// A difficulty the playlist highlights, `stats` is `None` when the song doesn't have it
+///
+#[derive(Debug, Clone)]
+pub struct ResolvedDifficulty<'a> {
+    pub characteristic: String,
+    pub name: String,
+    pub stats: Option<&'a Difficulty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedEntry<'a> {
+    pub hash: String,
+    pub key: Option<String>,
+    pub status: BeatStarPlaylistEntryStatus,
+    /// The database song, the current version for stale hashes
+    pub song: Option<&'a Song>,
+    pub difficulties: Vec<ResolvedDifficulty<'a>>,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BeatStarPlaylistSummary {
+    pub entries: usize,
+    pub found: usize,
+    pub stale_hashes: usize,
+    pub missing: usize,
+    /// Total length of the songs that resolved
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPlaylist<'a> {
+    pub entries: Vec<ResolvedEntry<'a>>,
+    pub summary: BeatStarPlaylistSummary,
+}
+
+///
+/// Finds a highlighted difficulty. Playlists write names in camelCase (`expertPlus`),
+/// so names are compared ignoring case.
+///
+fn find_difficulty<'a>(
+    song: &'a Song,
+    difficulty: &BPListDifficulty,
+) -> Option<&'a Difficulty> {
+    song.difficulties.iter().find(|diff| {
+        diff.characteristic
+            .name()
+            .eq_ignore_ascii_case(&difficulty.characteristic)
+            && diff.name.eq_ignore_ascii_case(&difficulty.name)
+    })
+}
+
+impl SongDatabase {
+    ///
+    /// Looks up every entry of the playlist by hash, falling back to the key, which gives
+    /// the newest upload of that key. Both are indexed lookups, so large playlists stay cheap.
+    ///
+    pub fn resolve_playlist(&self, playlist: &BPList) -> ResolvedPlaylist<'_> {
+        resolve_playlist_in(self, playlist)
+    }
+}
+
+///
+/// `SongDatabase::resolve_playlist` over any database, layered or not
+///
+pub(crate) fn resolve_playlist_in<'a>(
+    data: &'a impl SongLookup,
+    playlist: &BPList,
+) -> ResolvedPlaylist<'a> {
+    let mut summary = BeatStarPlaylistSummary {
+        entries: playlist.songs.len(),
+        ..Default::default()
+    };
+
+    let entries = playlist
+        .songs
+        .iter()
+        .map(|entry| {
+            let by_hash = data.song_by_hash(&entry.hash);
+            let key = entry.key.as_deref().filter(|key| !key.is_empty());
+
+            let (status, song) = match (by_hash, key) {
+                (Some(song), _) => (BeatStarPlaylistEntryStatus::Found, Some(song)),
+                (None, Some(key)) => match data.song_by_key(key) {
+                    Some(song) => (BeatStarPlaylistEntryStatus::StaleHash, Some(song)),
+                    None => (BeatStarPlaylistEntryStatus::Missing, None),
+                },
+                (None, None) => (BeatStarPlaylistEntryStatus::Missing, None),
+            };
+
+            match status {
+                BeatStarPlaylistEntryStatus::Found => summary.found += 1,
+                BeatStarPlaylistEntryStatus::StaleHash => summary.stale_hashes += 1,
+                BeatStarPlaylistEntryStatus::Missing => summary.missing += 1,
+            }
+            if let Some(song) = song {
+                summary.duration_secs += song.duration_secs as u64;
+            }
+
+            ResolvedEntry {
+                hash: entry.hash.clone(),
+                key: entry.key.clone(),
+                status,
+                song,
+                difficulties: entry
+                    .difficulties
+                    .iter()
+                    .map(|difficulty| ResolvedDifficulty {
+                        characteristic: difficulty.characteristic.clone(),
+                        name: difficulty.name.clone(),
+                        stats: song.and_then(|song| find_difficulty(song, difficulty)),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    ResolvedPlaylist { entries, summary }
+}
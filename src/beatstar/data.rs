@@ -2,20 +2,53 @@ use serde::{Deserialize, Serialize};
 use std::result;
 use std::str::FromStr;
 
-pub type UnixTime = libc::time_t;
+/// Seconds since the unix epoch, always 64 bit so the ABI doesn't depend on the platform's `time_t`
+pub type UnixTime = i64;
 
+///
+/// The difficulty of a beatmap, ordered from easiest to hardest.
+/// Difficulties that can't be parsed fall back to `Unknown`, which sorts last.
+///
 #[repr(C)]
+#[derive(
+    Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord,
+)]
 pub enum SongDiffs {
     Easy,
     Normal,
     Hard,
     Expert,
     ExpertPlus,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for SongDiffs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl FromStr for SongDiffs {
+    type Err = ();
+
+    fn from_str(input: &str) -> result::Result<SongDiffs, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "easy" => Ok(SongDiffs::Easy),
+            "normal" => Ok(SongDiffs::Normal),
+            "hard" => Ok(SongDiffs::Hard),
+            "expert" => Ok(SongDiffs::Expert),
+            "expertplus" => Ok(SongDiffs::ExpertPlus),
+            "expert+" => Ok(SongDiffs::ExpertPlus),
+            "unknown" => Ok(SongDiffs::Unknown),
+            _ => Err(()),
+        }
+    }
 }
 
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
 #[serde(rename_all = "PascalCase")]
 pub enum BeatStarCharacteristics {
     Unknown, // TODO: Unkown? intentional mispelling? We're fixing it here until it breaks
@@ -26,6 +59,8 @@ pub enum BeatStarCharacteristics {
     Degree90,
     Degree360,
     Lawless,
+    /// A characteristic added by mods, the raw name is kept in the difficulty's `char`
+    Custom,
 }
 
 impl Default for BeatStarCharacteristics {
@@ -62,3 +97,20 @@ impl FromStr for BeatStarCharacteristics {
         }
     }
 }
+
+///
+/// Where a song's data comes from
+///
+#[repr(C)]
+#[derive(
+    Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord,
+)]
+pub enum BeatStarSongSource {
+    /// The scraped BeatSaver data
+    #[default]
+    Scraped,
+    /// A level folder's Info.dat, for maps that aren't in the scraped data yet
+    Local,
+    /// Only known from the user's corrections
+    User,
+}
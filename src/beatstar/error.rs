@@ -0,0 +1,234 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use tracing::{event, Level};
+
+use crate::beatstar::logging::log_scope;
+
+///
+/// The kind of failure of the last FFI call on this thread
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BeatStarErrorCode {
+    Ok,
+    /// A null or non UTF-8 argument was passed
+    InvalidArgument,
+    /// The data could not be downloaded
+    Network,
+    /// A local file could not be read or written
+    Io,
+    /// The downloaded or local data is not a valid scrapped data zip
+    CorruptData,
+    /// The requested song, difficulty or index does not exist
+    NotFound,
+    /// The library panicked, the message has the panic payload
+    Panic,
+    Unknown,
+    /// The headers the host was compiled with don't match the library
+    AbiMismatch,
+    /// The library was built without the cargo feature the function needs,
+    /// or a file uses a schema the library can't read
+    Unsupported,
+}
+
+///
+/// An error with a known error code, for failures that don't come from a library error
+///
+#[derive(Debug)]
+pub struct BeatStarError {
+    pub code: BeatStarErrorCode,
+    pub message: String,
+}
+
+impl BeatStarError {
+    pub fn new<T: Into<String>>(code: BeatStarErrorCode, message: T) -> BeatStarError {
+        BeatStarError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for BeatStarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.message)
+    }
+}
+
+impl std::error::Error for BeatStarError {}
+
+struct LastError {
+    code: BeatStarErrorCode,
+    message: Option<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<LastError> = const {
+        RefCell::new(LastError {
+            code: BeatStarErrorCode::Ok,
+            message: None,
+        })
+    };
+}
+
+///
+/// Gets the error code of an error by looking through its causes
+///
+pub fn error_code(err: &anyhow::Error) -> BeatStarErrorCode {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<BeatStarError>() {
+            return e.code;
+        }
+        if cause.is::<ureq::Error>() {
+            return BeatStarErrorCode::Network;
+        }
+        if cause.is::<zip::result::ZipError>()
+            || cause.is::<serde_json::Error>()
+            || cause.is::<chrono::ParseError>()
+        {
+            return BeatStarErrorCode::CorruptData;
+        }
+        if cause.is::<std::io::Error>() {
+            return BeatStarErrorCode::Io;
+        }
+        #[cfg(feature = "sqlite")]
+        if cause.is::<rusqlite::Error>() {
+            return BeatStarErrorCode::Io;
+        }
+    }
+
+    BeatStarErrorCode::Unknown
+}
+
+pub(crate) fn set_last_error<T: Into<Vec<u8>>>(code: BeatStarErrorCode, message: T) {
+    // interior nul bytes would make the message unrepresentable, drop them
+    let mut message: Vec<u8> = message.into();
+    message.retain(|b| *b != 0);
+
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = LastError {
+            code,
+            message: CString::new(message).ok(),
+        }
+    });
+}
+
+pub(crate) fn clear_last_error() {
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = LastError {
+            code: BeatStarErrorCode::Ok,
+            message: None,
+        }
+    });
+}
+
+///
+/// Logs the error and stores it as the last error of this thread
+///
+pub(crate) fn report_error(context: &str, err: &anyhow::Error) {
+    event!(Level::ERROR, "{0} {1}", context, format!("{err:?}"));
+    set_last_error(error_code(err), format!("{context}: {err:#}"));
+}
+
+pub(crate) fn last_error() -> BeatStarErrorCode {
+    LAST_ERROR.with(|last| last.borrow().code)
+}
+
+///
+/// The message stays valid until the next FFI call on this thread
+///
+pub(crate) fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| match &last.borrow().message {
+        None => ptr::null(),
+        Some(message) => message.as_ptr(),
+    })
+}
+
+///
+/// The value an FFI function returns when it panicked
+///
+pub(crate) trait FfiDefault {
+    fn ffi_default() -> Self;
+}
+
+impl<T> FfiDefault for *const T {
+    fn ffi_default() -> Self {
+        ptr::null()
+    }
+}
+
+impl<T> FfiDefault for *mut T {
+    fn ffi_default() -> Self {
+        ptr::null_mut()
+    }
+}
+
+impl FfiDefault for BeatStarErrorCode {
+    fn ffi_default() -> Self {
+        BeatStarErrorCode::Panic
+    }
+}
+
+macro_rules! ffi_default_impl {
+    ($($t:ty),*) => {
+        $(
+            impl FfiDefault for $t {
+                fn ffi_default() -> Self {
+                    Default::default()
+                }
+            }
+        )*
+    };
+}
+
+ffi_default_impl!(
+    (),
+    bool,
+    u32,
+    usize,
+    f32,
+    crate::beatstar::data::BeatStarCharacteristics,
+    crate::beatstar::data::BeatStarSongSource,
+    crate::beatstar::abi::BeatStarAbiSizes,
+    crate::beatstar::bplist::BeatStarPlaylistSummary,
+    crate::beatstar::jump::BeatStarJumpInfo,
+    crate::beatstar::query::BeatStarDifficultyFilter,
+    crate::beatstar::ffi::BeatStarSongInfo,
+    crate::beatstar::ffi::BeatStarSongDifficultyInfo
+);
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+
+    "Unknown panic".to_string()
+}
+
+///
+/// Runs the body of an FFI function, catching any panic so it doesn't unwind into the host.
+/// A panic returns the function's error value and sets the last error to `Panic`.
+/// Events logged during the call go to the library's subscriber.
+///
+pub(crate) fn ffi_guard<R: FfiDefault, F: FnOnce() -> R>(f: F) -> R {
+    let _log = log_scope();
+
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = panic_message(&payload);
+            event!(Level::ERROR, "Panicked inside FFI call: {0}", message);
+            set_last_error(BeatStarErrorCode::Panic, format!("Panicked: {message}"));
+            R::ffi_default()
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Write;
+
+use crate::beatstar::ffi::BeatStarSong;
+use crate::beatstar::model::{format_time, Song};
+use crate::beatstar::query::SongDifficultyPair;
+
+///
+/// The CSV header, one row per difficulty
+///
+const CSV_COLUMNS: &[&str] = &[
+    "hash",
+    "key",
+    "song_name",
+    "song_sub_name",
+    "song_author_name",
+    "level_author_name",
+    "bpm",
+    "duration_secs",
+    "upvotes",
+    "downvotes",
+    "uploaded",
+    "heat",
+    "rating",
+    "characteristic",
+    "difficulty",
+    "stars",
+    "ranked",
+    "pp",
+    "njs",
+    "njs_offset",
+    "notes",
+    "bombs",
+    "obstacles",
+    "jump_distance",
+    "reaction_time_ms",
+    "ranked_update_time",
+    "requirements",
+];
+
+///
+/// Writes the songs as a JSON array in the scrapped data format, plus the derived fields
+/// (heat, rating, pp, jump info and unix times). It can be loaded back like the scrapped data.
+///
+pub fn beatstar_write_json<W: Write>(songs: &[&Song], writer: W) -> anyhow::Result<()> {
+    // the FFI form serializes in the scrapped format
+    let songs: Vec<BeatStarSong> = songs.iter().map(|song| BeatStarSong::from(*song)).collect();
+    serde_json::to_writer_pretty(writer, &songs)?;
+    Ok(())
+}
+
+///
+/// Quotes a CSV field if it has a separator, quote or line break (RFC 4180)
+///
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{0}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+///
+/// Writes one CSV row per difficulty with its song's fields. Requirements are joined with `;`.
+///
+pub fn beatstar_write_csv<W: Write>(
+    pairs: &[SongDifficultyPair],
+    mut writer: W,
+) -> anyhow::Result<()> {
+    writeln!(writer, "{0}", CSV_COLUMNS.join(","))?;
+
+    for (song, diff) in pairs {
+        let row = [
+            song.hash.clone(),
+            song.key.clone(),
+            song.song_name.clone(),
+            song.song_sub_name.clone(),
+            song.song_author_name.clone(),
+            song.level_author_name.clone(),
+            song.bpm.to_string(),
+            song.duration_secs.to_string(),
+            song.upvotes.to_string(),
+            song.downvotes.to_string(),
+            format_time(song.uploaded),
+            song.heat.to_string(),
+            song.rating.to_string(),
+            diff.characteristic.name().to_string(),
+            diff.name.clone(),
+            diff.stars.to_string(),
+            diff.ranked.to_string(),
+            diff.approximate_pp.to_string(),
+            diff.njs.to_string(),
+            diff.njs_offset.to_string(),
+            diff.notes.to_string(),
+            diff.bombs.to_string(),
+            diff.obstacles.to_string(),
+            diff.jump_info.jump_distance.to_string(),
+            diff.jump_info.reaction_time_ms.to_string(),
+            format_time(diff.ranked_update_time),
+            diff.requirements.join(";"),
+        ];
+
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(writer, "{0}", fields.join(","))?;
+    }
+
+    Ok(())
+}
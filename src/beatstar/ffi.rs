@@ -2,82 +2,257 @@ use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 use std::os::raw::c_char;
 
-use serde::Deserialize;
-use tracing::{event, span, Level};
+use serde::Serialize;
+use tracing::{span, Level};
 
 //noinspection RsExternalLinter
 #[macro_use]
 use crate::vec_extern;
 
-//noinspection RsExternalLinter
-#[macro_use]
-use crate::map_extern;
-use crate::beatstar::data::{BeatStarCharacteristics, UnixTime};
-use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use crate::beatstar::abi::{check_abi, BeatStarAbiSizes, BEATSTAR_ABI_VERSION, BEATSTAR_VERSION};
+use crate::beatstar::bplist::{
+    BPList, BeatStarPlaylistEntryStatus, BeatStarPlaylistSummary, ResolvedPlaylist,
+};
+use crate::beatstar::data::{BeatStarCharacteristics, BeatStarSongSource, SongDiffs, UnixTime};
+use crate::beatstar::error::{
+    clear_last_error, ffi_guard, last_error, last_error_message, report_error, set_last_error,
+    BeatStarErrorCode,
+};
+use crate::beatstar::iter::{BeatStarCharacteristicIter, BeatStarDifficultyIter, BeatStarSongIter};
+use crate::beatstar::jump::{beatstar_jump_info, BeatStarJumpInfo};
+use crate::beatstar::layers::{BeatStarLayer, BeatStarLayers, LayeredDatabase};
+use crate::beatstar::model::{format_time, parse_difficulty, Characteristic, Difficulty, Song};
+use crate::beatstar::level_hash::LevelScan;
+use crate::beatstar::logging::{set_log_callback, set_log_level, BeatStarLogCallback, BeatStarLogLevel};
+use crate::beatstar::overlay::BeatStarOverlay;
+use crate::beatstar::query::{BeatStarDifficultyFilter, SongDifficultyPair};
+use crate::beatstar::score::{
+    accuracy_to_score_for_notes, beatstar_max_score_for_notes, score_to_accuracy_for_notes,
+};
+use crate::beatstar::snapshot_diff::{
+    beatstar_diff, DifficultyChange, MetadataEdit, SnapshotDiff, SongUpdate, VoteSwing,
+};
+use crate::beatstar::song_database::SongDatabase;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_void, CStr, CString};
+use std::path::Path;
 use std::ptr;
 
 ///
-/// Get the song list and clone it
+/// Reads a C string argument, setting the last error if it's null or not UTF-8
 ///
-#[no_mangle]
-pub extern "C" fn Beatstar_RetrieveDatabase() -> *const BeatStarDataFile {
-    use crate::beatstar::database::beatstar_retrieve_database;
-    use crate::beatstar::database::initialize_log;
-
-    initialize_log();
-    let span = span!(Level::ERROR, "Beatstar_RetrieveDatabaseExtern");
-    let _guard = span.enter();
-
-    match beatstar_retrieve_database() {
-        Ok(e) => e,
-        Err(e) => {
-            event!(
-                Level::ERROR,
-                "Unable to fetch from database {0}",
-                format!("{e:?}")
+unsafe fn ffi_str<'a>(c_str: *const c_char, name: &str) -> Option<&'a str> {
+    if c_str.is_null() {
+        set_last_error(BeatStarErrorCode::InvalidArgument, format!("{name} is null"));
+        return None;
+    }
+
+    match CStr::from_ptr(c_str).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_last_error(
+                BeatStarErrorCode::InvalidArgument,
+                format!("{name} is not valid UTF-8"),
             );
+            None
+        }
+    }
+}
+
+///
+/// Reads an optional C string argument, null is `Some(None)`.
+/// `None` when the string isn't UTF-8, with the last error set.
+///
+unsafe fn ffi_nullable_str<'a>(c_str: *const c_char, name: &str) -> Option<Option<&'a str>> {
+    if c_str.is_null() {
+        return Some(None);
+    }
+
+    ffi_str(c_str, name).map(Some)
+}
+
+///
+/// Converts a lookup to a pointer, setting the last error to `NotFound` if it's missing
+///
+fn ffi_ptr<T>(value: Option<&T>, what: &str) -> *const T {
+    match value {
+        Some(e) => {
+            clear_last_error();
+            e
+        }
+        None => {
+            set_last_error(BeatStarErrorCode::NotFound, format!("{what} not found"));
             ptr::null()
         }
     }
 }
 
+///
+/// Gets the error code of the last call on this thread, `Ok` if it succeeded
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_LastError() -> BeatStarErrorCode {
+    ffi_guard(|| {
+        last_error()
+    })
+}
+
+///
+/// Gets the error message of the last call on this thread, null if it succeeded.
+/// The string is valid until the next call on this thread.
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_LastErrorMessage() -> *const c_char {
+    ffi_guard(|| {
+        last_error_message()
+    })
+}
+
+///
+/// Gets the ABI version of the library, `BEATSTAR_ABI_VERSION` in the headers it was built with
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_AbiVersion() -> u32 {
+    ffi_guard(|| {
+        clear_last_error();
+        BEATSTAR_ABI_VERSION
+    })
+}
+
+///
+/// Gets the crate version of the library, e.g. "0.1.0". The string is static.
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_Version() -> *const c_char {
+    ffi_guard(|| {
+        clear_last_error();
+        BEATSTAR_VERSION.as_ptr() as *const c_char
+    })
+}
+
+///
+/// Gets the struct sizes the library was built with
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_AbiSizes() -> BeatStarAbiSizes {
+    ffi_guard(|| {
+        clear_last_error();
+        BeatStarAbiSizes::current()
+    })
+}
+
+///
+/// Checks the headers the host was compiled with match the library.
+/// Pass `BEATSTAR_ABI_VERSION` and the `sizeof` of each struct from the headers.
+/// Returns false and sets `AbiMismatch` with the differences if they don't match,
+/// in which case no other function should be called.
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_CheckAbi(
+    expected_version: u32,
+    sizes: *const BeatStarAbiSizes,
+) -> bool {
+    ffi_guard(|| {
+        let sizes = match sizes.as_ref() {
+            Some(sizes) => sizes,
+            None => {
+                set_last_error(BeatStarErrorCode::InvalidArgument, "sizes is null");
+                return false;
+            }
+        };
+
+        match check_abi(expected_version, sizes) {
+            Ok(()) => {
+                clear_last_error();
+                true
+            }
+            Err(e) => {
+                report_error("ABI check failed", &e.into());
+                false
+            }
+        }
+    })
+}
+
+///
+/// Sends the library's logs to `callback` instead of stdout (logcat on Android),
+/// from any thread. Pass null to go back to the default output. `user_data` is passed
+/// back to every call and must stay valid, calls on other threads may still be running.
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_SetLogCallback(callback: BeatStarLogCallback, user_data: *mut c_void) {
+    ffi_guard(|| {
+        set_log_callback(callback, user_data);
+        clear_last_error();
+    })
+}
+
+///
+/// Sets the most verbose level that gets logged, `Info` by default
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_SetLogLevel(level: BeatStarLogLevel) {
+    ffi_guard(|| {
+        set_log_level(level);
+        clear_last_error();
+    })
+}
+
+///
+/// Get the song list and clone it
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_RetrieveDatabase() -> *const BeatStarDataFile {
+    ffi_guard(|| {
+        use crate::beatstar::database::beatstar_retrieve_data_file;
+
+        let span = span!(Level::ERROR, "Beatstar_RetrieveDatabaseExtern");
+        let _guard = span.enter();
+
+        match beatstar_retrieve_data_file() {
+            Ok(e) => {
+                clear_last_error();
+                e
+            }
+            Err(e) => {
+                report_error("Unable to fetch from database", &e);
+                ptr::null()
+            }
+        }
+    })
+}
+
 
 ///
 /// Download song database to a file, true if successful
 ///
 #[no_mangle]
 pub unsafe extern "C" fn Beatstar_DownloadDatabaseToFile(file_path: *const c_char) -> bool {
-    use crate::beatstar::database::beatstar_download_database_to_file;
-    use crate::beatstar::database::initialize_log;
-
-    initialize_log();
-    let span = span!(Level::ERROR, "Beatstar_DownloadDatabaseToFile");
-    let _guard = span.enter();
-    
-    
-    if file_path.is_null() {
-        return false;
-    }
-
-    let raw = CStr::from_ptr(file_path);
-
-    let file_path_str = match raw.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-    
-    match beatstar_download_database_to_file(file_path_str) {
-        Ok(_e) => true,
-        Err(e) => {
-            event!(
-                Level::ERROR,
-                "Unable to fetch from database {0}",
-                format!("{e:?}")
-            );
-            false
+    ffi_guard(|| {
+        use crate::beatstar::database::beatstar_download_database_to_file;
+
+        let span = span!(Level::ERROR, "Beatstar_DownloadDatabaseToFile");
+        let _guard = span.enter();
+
+        let file_path_str = match ffi_str(file_path, "file_path") {
+            Some(s) => s,
+            None => return false,
+        };
+
+        match beatstar_download_database_to_file(file_path_str) {
+            Ok(_e) => {
+                clear_last_error();
+                true
+            }
+            Err(e) => {
+                report_error("Unable to fetch from database", &e);
+                false
+            }
         }
-    }
+    })
 }
 
 ///
@@ -85,37 +260,28 @@ pub unsafe extern "C" fn Beatstar_DownloadDatabaseToFile(file_path: *const c_cha
 ///
 #[no_mangle]
 pub unsafe extern "C" fn Beatstar_RetrieveDatabaseLocal(file_path: *const c_char) -> *const BeatStarDataFile {
-    use crate::beatstar::database::beatstar_retrieve_database_from_file;
-    use crate::beatstar::database::initialize_log;
-
-    initialize_log();
-    let span = span!(Level::ERROR, "Beatstar_RetrieveDatabaseLocal");
-    let _guard = span.enter();
+    ffi_guard(|| {
+        use crate::beatstar::database::beatstar_retrieve_data_file_from_file;
 
-    
-    if file_path.is_null() {
-        return ptr::null();
-    }
-
-    let raw = CStr::from_ptr(file_path);
-
-    let file_path_str = match raw.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
+        let span = span!(Level::ERROR, "Beatstar_RetrieveDatabaseLocal");
+        let _guard = span.enter();
 
+        let file_path_str = match ffi_str(file_path, "file_path") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
 
-    match beatstar_retrieve_database_from_file(file_path_str) {
-        Ok(e) => e,
-        Err(e) => {
-            event!(
-                Level::ERROR,
-                "Unable to fetch from database {0}",
-                format!("{e:?}")
-            );
-            ptr::null()
+        match beatstar_retrieve_data_file_from_file(file_path_str) {
+            Ok(e) => {
+                clear_last_error();
+                e
+            }
+            Err(e) => {
+                report_error("Unable to fetch from database", &e);
+                ptr::null()
+            }
         }
-    }
+    })
 }
 
 ///
@@ -124,37 +290,31 @@ pub unsafe extern "C" fn Beatstar_RetrieveDatabaseLocal(file_path: *const c_char
 ///
 #[no_mangle]
 pub unsafe extern "C" fn Beatstar_GetSong(hash: *const c_char) -> *const BeatStarSong {
-    use crate::beatstar::database::beatstar_get_song;
-    let span = span!(Level::ERROR, "Beatstar_GetSongExtern");
-    let _guard = span.enter();
-
-    if hash.is_null() {
-        return ptr::null_mut();
-    }
-
-    let raw = CStr::from_ptr(hash);
-
-    let hash_str = match raw.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
+    ffi_guard(|| {
+        use crate::beatstar::database::beatstar_retrieve_data_file;
+        let span = span!(Level::ERROR, "Beatstar_GetSongExtern");
+        let _guard = span.enter();
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
 
-    match beatstar_get_song(hash_str) {
-        Ok(song_opt) => match song_opt {
-            None => ptr::null(),
-            Some(song) => song,
-        },
-        Err(e) => {
-            event!(
-                Level::ERROR,
-                "Unable to fetch from database {0}",
-                format!("{e:?}")
-            );
-            ptr::null()
+        match beatstar_retrieve_data_file() {
+            Ok(data) => ffi_ptr(data.get_song(hash_str), "Song"),
+            Err(e) => {
+                report_error("Unable to fetch from database", &e);
+                ptr::null()
+            }
         }
-    }
+    })
 }
 
+///
+/// A nul terminated UTF-8 string owned by the library.
+/// C only ever borrows it, through a `const char *` or `BeatStarStringView` that is valid
+/// as long as its owner (the database for song fields) and must not be freed.
+///
 #[derive(Eq, Debug)]
 #[repr(C)]
 pub struct RustCStringWrapper {
@@ -163,7 +323,7 @@ pub struct RustCStringWrapper {
 
 impl Clone for RustCStringWrapper {
     fn clone(&self) -> Self {
-        RustCStringWrapper::from_copy(self.string_data)
+        RustCStringWrapper::new(self.as_bytes())
     }
 }
 
@@ -190,18 +350,10 @@ unsafe impl Sync for RustCStringWrapper {}
 
 impl ToString for RustCStringWrapper {
     fn to_string(&self) -> String {
-        if self.string_data.is_null() {
-            return String::new();
+        match std::str::from_utf8(self.as_bytes()) {
+            Ok(s) => s.to_string(),
+            Err(_) => String::new(),
         }
-
-        let raw = unsafe { CStr::from_ptr(self.string_data) };
-
-        let hash_str = match raw.to_str() {
-            Ok(s) => s,
-            Err(_) => return String::new(),
-        };
-
-        hash_str.to_string()
     }
 }
 
@@ -210,6 +362,7 @@ impl Drop for RustCStringWrapper {
         if self.string_data.is_null() {
             return;
         }
+        // Only ever created from CString::into_raw in `new`
         unsafe {
             drop(CString::from_raw(self.string_data));
         }
@@ -217,70 +370,1932 @@ impl Drop for RustCStringWrapper {
 }
 
 impl RustCStringWrapper {
+    ///
+    /// Copies the string, dropping interior nul bytes which C can't represent
+    ///
     pub fn new<T: Into<Vec<u8>>>(str_data: T) -> RustCStringWrapper {
-        let c_string = CString::new(str_data).expect("RustCStringWrapper::new failed");
-        let ptr = c_string.into_raw();
-        RustCStringWrapper { string_data: ptr }
-    }
+        let mut bytes: Vec<u8> = str_data.into();
+        bytes.retain(|b| *b != 0);
 
-    fn from_copy(c_str: *const c_char) -> Self {
-        unsafe {
-            let old_string = CStr::from_ptr(c_str);
-            let new_string = CString::new(old_string.to_bytes())
-                .expect("Unable to create a new C String from a C string");
-            RustCStringWrapper {
-                string_data: new_string.into_raw(),
-            }
+        let c_string = CString::new(bytes).expect("nul bytes were removed");
+        RustCStringWrapper {
+            string_data: c_string.into_raw(),
         }
     }
-}
 
-impl From<*mut c_char> for RustCStringWrapper {
-    fn from(c_str: *mut c_char) -> Self {
-        unsafe {
-            RustCStringWrapper {
-                string_data: CString::from_raw(c_str).into_raw(),
-            }
+    ///
+    /// Hands the string over to C, which must free it with `Beatstar_StringFree`
+    ///
+    pub fn into_raw(self) -> *mut c_char {
+        std::mem::ManuallyDrop::new(self).string_data
+    }
+
+    ///
+    /// The bytes without the nul terminator
+    ///
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.string_data.is_null() {
+            return &[];
         }
+
+        unsafe { CStr::from_ptr(self.string_data) }.to_bytes()
     }
 }
 
-impl<'de> Deserialize<'de> for RustCStringWrapper {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+impl Serialize for RustCStringWrapper {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        D: serde::Deserializer<'de>,
+        S: serde::Serializer,
     {
-        let value: serde_json::Value = serde::Deserialize::deserialize(deserializer)?;
+        serializer.serialize_str(&String::from_utf8_lossy(self.as_bytes()))
+    }
+}
+
+///
+/// A borrowed UTF-8 string, also nul terminated.
+/// Valid for as long as its owner, which for database fields is the database.
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BeatStarStringView {
+    pub data: *const c_char,
+    pub len: usize,
+}
+
+impl Default for BeatStarStringView {
+    fn default() -> Self {
+        BeatStarStringView {
+            data: ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+impl From<&RustCStringWrapper> for BeatStarStringView {
+    fn from(str: &RustCStringWrapper) -> Self {
+        if str.string_data.is_null() {
+            return BeatStarStringView::default();
+        }
+
+        BeatStarStringView {
+            data: str.string_data,
+            len: str.as_bytes().len(),
+        }
+    }
+}
+
+///
+/// Frees a string the library allocated for the caller. Those are returned as `char *`,
+/// while the `const char *` and string views it returns are borrowed and must not be freed.
+/// Null is ignored.
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_StringFree(str: *mut c_char) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !str.is_null() {
+            drop(CString::from_raw(str));
+        }
+    })
+}
+
+impl Default for RustCStringWrapper {
+    fn default() -> Self {
+        RustCStringWrapper::new("")
+    }
+}
+
+///
+/// The loaded database, opaque to C. Use the accessor functions to read it.
+/// Wraps a `SongDatabase` with the FFI form of its songs.
+///
+pub struct BeatStarDataFile {
+    database: SongDatabase,
+
+    /// The FFI form of `database.songs()`, in the same order. Each is built the first time it's
+    /// read, so a host that only looks up a few songs doesn't keep a second copy of the database.
+    songs: Vec<OnceCell<Box<BeatStarSong>>>,
+
+    /// How many diffs were loaded for each characteristic name, including custom ones, by name
+    pub(crate) characteristic_counts: Vec<(RustCStringWrapper, usize)>,
+
+    /// Lazily computed statistics of this snapshot
+    pub(crate) stats_json: OnceCell<RustCStringWrapper>,
+}
+
+impl From<SongDatabase> for BeatStarDataFile {
+    fn from(database: SongDatabase) -> Self {
+        let songs = database.songs().iter().map(|_| OnceCell::new()).collect();
+        let characteristic_counts = database
+            .characteristic_counts()
+            .into_iter()
+            .map(|(name, count)| (RustCStringWrapper::new(name), count))
+            .collect();
+
+        BeatStarDataFile {
+            database,
+            songs,
+            characteristic_counts,
+            stats_json: OnceCell::new(),
+        }
+    }
+}
+
+impl BeatStarDataFile {
+    ///
+    /// The database this wraps, for the Rust API
+    ///
+    pub fn database(&self) -> &SongDatabase {
+        &self.database
+    }
+
+    ///
+    /// The songs ordered by hash, building the ones that weren't read yet
+    ///
+    pub fn songs(&self) -> impl Iterator<Item = &BeatStarSong> {
+        (0..self.len()).filter_map(|index| self.song_at(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+
+    ///
+    /// Gets the song at the index when ordered by hash
+    ///
+    pub fn song_at(&self, index: usize) -> Option<&BeatStarSong> {
+        let song = self.database.song_at(index)?;
+
+        Some(self.songs[index].get_or_init(|| Box::new(BeatStarSong::from(song))))
+    }
+
+    ///
+    /// How many diffs were loaded for each characteristic name, including custom ones, by name
+    ///
+    pub fn characteristic_counts(&self) -> &[(RustCStringWrapper, usize)] {
+        &self.characteristic_counts
+    }
+
+    ///
+    /// How many diffs were loaded for the characteristic name
+    ///
+    pub fn characteristic_count(&self, char_name: &str) -> Option<&usize> {
+        self.characteristic_counts
+            .iter()
+            .find(|(name, _)| name.as_bytes() == char_name.as_bytes())
+            .map(|(_, count)| count)
+    }
+
+    ///
+    /// Gets a song by hash, in any case
+    ///
+    pub fn get_song(&self, hash: &str) -> Option<&BeatStarSong> {
+        self.song_at(self.database.position(hash)?)
+    }
+
+    ///
+    /// Gets a song by hash, falling back to its key
+    ///
+    pub fn get_song_by_id(&self, id: &str) -> Option<&BeatStarSong> {
+        self.ffi_song(self.database.get_song_by_id(id)?)
+    }
+
+}
+
+///
+/// Maps the songs of a database handle to their FFI form, so query lists, scans and playlists
+/// point into the handle they were made from, layered or not
+///
+pub(crate) trait FfiSongs {
+    ///
+    /// The FFI form of a song of this handle
+    ///
+    fn ffi_song(&self, song: &Song) -> Option<&BeatStarSong>;
+
+    ///
+    /// The FFI form of a difficulty of a song of this handle
+    ///
+    fn ffi_diff(
+        &self,
+        song: &Song,
+        diff: &Difficulty,
+    ) -> Option<(&BeatStarSong, &BeatStarSongDifficultyStats)> {
+        let ffi_song = self.ffi_song(song)?;
+        let index = song.difficulties.iter().position(|d| ptr::eq(d, diff))?;
+
+        Some((ffi_song, ffi_song.diffs.get(index)?))
+    }
+}
+
+impl FfiSongs for BeatStarDataFile {
+    fn ffi_song(&self, song: &Song) -> Option<&BeatStarSong> {
+        self.get_song(&song.hash)
+    }
+}
+
+unsafe impl Send for BeatStarDataFile {}
+unsafe impl Sync for BeatStarDataFile {}
+
+///
+/// Get the song based on hash
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_SongsGet(
+    self_i: Option<&BeatStarDataFile>,
+    index: Option<&RustCStringWrapper>,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(index);
+
+        ffi_ptr(self_i.get_song(&index.to_string()), "Key")
+    })
+}
+
+///
+/// Get the hash of the song at the index when ordered by hash
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_SongsGetKey(
+    self_i: Option<&BeatStarDataFile>,
+    index: usize,
+) -> *const RustCStringWrapper {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.song_at(index).map(|song| &song.hash), "Index")
+    })
+}
+
+///
+/// Get the song count
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_SongsLen(self_i: Option<&BeatStarDataFile>) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.len()
+    })
+}
+
+///
+/// Get the song based on hash, same as `BeatStarDataFile_map_SongsGet` with a plain string
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_GetSong(
+    self_i: Option<&BeatStarDataFile>,
+    hash: *const c_char,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_song(hash_str), "Song")
+    })
+}
+
+///
+/// Get how many diffs were loaded for the characteristic name
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_CharacteristicCountsGet(
+    self_i: Option<&BeatStarDataFile>,
+    index: Option<&RustCStringWrapper>,
+) -> *const usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(index);
+
+        ffi_ptr(self_i.characteristic_count(&index.to_string()), "Key")
+    })
+}
+
+///
+/// Get the characteristic name at the index when ordered by name
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_CharacteristicCountsGetKey(
+    self_i: Option<&BeatStarDataFile>,
+    index: usize,
+) -> *const RustCStringWrapper {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(
+            self_i.characteristic_counts.get(index).map(|(name, _)| name),
+            "Index",
+        )
+    })
+}
+
+///
+/// Get the number of characteristic names
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_map_CharacteristicCountsLen(
+    self_i: Option<&BeatStarDataFile>,
+) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.characteristic_counts.len()
+    })
+}
+
+///
+/// Gets the song at the index when ordered by hash
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_SongAt(
+    self_i: Option<&BeatStarDataFile>,
+    index: usize,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.song_at(index), "Song index")
+    })
+}
+
+///
+/// Starts iterating the songs ordered by hash.
+/// Must be freed with `Beatstar_SongIterFree`
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_SongIterNew(data: Option<&BeatStarDataFile>) -> *mut BeatStarSongIter {
+    ffi_guard(|| {
+        ffi_arg!(data);
+
+        clear_last_error();
+        Box::into_raw(Box::new(BeatStarSongIter { data, index: 0 }))
+    })
+}
+
+///
+/// Gets the next song, null once every song was visited
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_SongIterNext(iter: Option<&mut BeatStarSongIter>) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(iter);
+
+        clear_last_error();
+        let data = unsafe { &*iter.data };
+
+        match data.song_at(iter.index) {
+            None => ptr::null(),
+            Some(song) => {
+                iter.index += 1;
+                song
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_SongIterFree(iter: *mut BeatStarSongIter) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !iter.is_null() {
+            drop(Box::from_raw(iter));
+        }
+    })
+}
+
+///
+/// Gets the dataset statistics (counts, distributions and uploads per month) as JSON.
+/// The string is owned by the database and computed on first call.
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_StatsJson(self_i: Option<&BeatStarDataFile>) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.stats_json().string_data
+    })
+}
+
+///
+/// Exports the database to SQLite tables at `path`, replacing the file once the export succeeded.
+/// Duplicate difficulties of a song are skipped with a warning in the log.
+/// Fails with `Unsupported` unless the library was built with the `sqlite` feature.
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_ExportSqlite(
+    self_i: Option<&BeatStarDataFile>,
+    path: *const c_char,
+) -> bool {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarDataFile_ExportSqlite");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return false,
+        };
+
+        match self_i.database().export_sqlite(Path::new(path_str)) {
+            Ok(_) => {
+                clear_last_error();
+                true
+            }
+            Err(e) => {
+                report_error("Unable to export to SQLite", &e);
+                false
+            }
+        }
+    })
+}
+
+///
+/// A difficulty along with the song that owns it.
+/// Both point into the database, so they live as long as it.
+///
+#[repr(C)]
+pub struct BeatStarSongDifficultyRef {
+    pub song: *const BeatStarSong,
+    pub diff: *const BeatStarSongDifficultyStats,
+}
+
+///
+/// A list of difficulties returned by a query.
+/// Must be freed with `BeatStarSongDifficultyList_Free`
+///
+pub struct BeatStarSongDifficultyList {
+    pub entries: Vec<BeatStarSongDifficultyRef>,
+}
+
+impl BeatStarSongDifficultyList {
+    ///
+    /// Points the pairs of `data`'s songs at their FFI form
+    ///
+    fn new(data: &impl FfiSongs, pairs: Vec<SongDifficultyPair>) -> Self {
+        BeatStarSongDifficultyList {
+            entries: pairs
+                .into_iter()
+                .filter_map(|(song, diff)| data.ffi_diff(song, diff))
+                .map(|(song, diff)| BeatStarSongDifficultyRef { song, diff })
+                .collect(),
+        }
+    }
+}
+
+vec_extern!(
+    BeatStarSongDifficultyList,
+    entries,
+    BeatStarSongDifficultyRef,
+    BeatStarSongDifficultyList_Get,
+    BeatStarSongDifficultyList_Len
+);
+
+///
+/// Frees a list returned by a query
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarSongDifficultyList_Free(list: *mut BeatStarSongDifficultyList) {
+    ffi_guard(|| {
+        clear_last_error();
+        if list.is_null() {
+            return;
+        }
+
+        drop(Box::from_raw(list));
+    })
+}
+
+///
+/// Gets all the difficulties ranked at or after `since`, newest first
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_RankedSince(
+    self_i: Option<&BeatStarDataFile>,
+    since: UnixTime,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        let pairs = self_i.database().ranked_since(since);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Gets a filter that matches every difficulty, to be narrowed down by the caller
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_DifficultyFilterDefault() -> BeatStarDifficultyFilter {
+    ffi_guard(|| {
+        clear_last_error();
+        BeatStarDifficultyFilter::default()
+    })
+}
+
+///
+/// Gets all the difficulties matching the filter, ordered by song hash
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_QueryDifficulties(
+    self_i: Option<&BeatStarDataFile>,
+    filter: Option<&BeatStarDifficultyFilter>,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(filter);
+
+        clear_last_error();
+        let pairs = self_i.database().query_difficulties(filter);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Gets the `limit` most recently ranked difficulties, newest first
+///
+#[no_mangle]
+pub extern "C" fn BeatStarDataFile_RecentlyRanked(
+    self_i: Option<&BeatStarDataFile>,
+    limit: usize,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        let pairs = self_i.database().recently_ranked(limit);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Computes the level hash of a folder (Info.dat followed by its difficulty files),
+/// which is the key for `Beatstar_GetSong`. Must be freed with `Beatstar_StringFree`
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_HashLevelFolder(path: *const c_char) -> *mut c_char {
+    ffi_guard(|| {
+        use crate::beatstar::level_hash::beatstar_hash_level_folder;
+
+        let span = span!(Level::ERROR, "Beatstar_HashLevelFolder");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match beatstar_hash_level_folder(Path::new(path_str)) {
+            Ok(hash) => {
+                clear_last_error();
+                RustCStringWrapper::new(hash).into_raw()
+            }
+            Err(e) => {
+                report_error("Unable to hash level", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Builds a song from a level folder's Info.dat and beatmaps, for maps missing from the database.
+/// Its source is `Local`. Must be freed with `Beatstar_LocalSongFree`
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_LocalSongNew(path: *const c_char) -> *mut BeatStarSong {
+    ffi_guard(|| {
+        use crate::beatstar::info_dat::beatstar_local_song;
+
+        let span = span!(Level::ERROR, "Beatstar_LocalSongNew");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match beatstar_local_song(Path::new(path_str)) {
+            Ok(song) => {
+                clear_last_error();
+                Box::into_raw(Box::new(song))
+            }
+            Err(e) => {
+                report_error("Unable to read level", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_LocalSongFree(song: *mut BeatStarSong) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !song.is_null() {
+            drop(Box::from_raw(song));
+        }
+    })
+}
+
+///
+/// Creates an empty overlay for local songs. Must be freed with `Beatstar_OverlayFree`
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_OverlayNew() -> *mut BeatStarOverlay {
+    ffi_guard(|| {
+        clear_last_error();
+        Box::into_raw(Box::<BeatStarOverlay>::default())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_OverlayFree(overlay: *mut BeatStarOverlay) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !overlay.is_null() {
+            drop(Box::from_raw(overlay));
+        }
+    })
+}
+
+///
+/// Merges a local song built from every level folder in `levels_dir` into the overlay.
+/// Returns how many were added, folders that can't be read are skipped.
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarOverlay_LoadLevels(
+    self_i: Option<&mut BeatStarOverlay>,
+    levels_dir: *const c_char,
+) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarOverlay_LoadLevels");
+        let _guard = span.enter();
+
+        let levels_dir_str = match ffi_str(levels_dir, "levels_dir") {
+            Some(s) => s,
+            None => return 0,
+        };
+
+        match self_i.load_levels(Path::new(levels_dir_str)) {
+            Ok(added) => {
+                clear_last_error();
+                added
+            }
+            Err(e) => {
+                report_error("Unable to load levels", &e);
+                0
+            }
+        }
+    })
+}
+
+///
+/// Gets the song from the database, or from the overlay if the database doesn't have it
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_GetSongWithOverlay(
+    self_i: Option<&BeatStarDataFile>,
+    overlay: Option<&BeatStarOverlay>,
+    hash: *const c_char,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(overlay);
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_song_with_overlay(overlay, hash_str), "Song")
+    })
+}
+
+///
+/// Corrects a song's names in the overlay, null keeps the value of the lower layers
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarOverlay_SetMetadata(
+    self_i: Option<&mut BeatStarOverlay>,
+    hash: *const c_char,
+    song_name: *const c_char,
+    song_sub_name: *const c_char,
+    song_author_name: *const c_char,
+    level_author_name: *const c_char,
+) -> bool {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let song_name = match ffi_nullable_str(song_name, "song_name") {
+            Some(s) => s,
+            None => return false,
+        };
+        let song_sub_name = match ffi_nullable_str(song_sub_name, "song_sub_name") {
+            Some(s) => s,
+            None => return false,
+        };
+        let song_author_name = match ffi_nullable_str(song_author_name, "song_author_name") {
+            Some(s) => s,
+            None => return false,
+        };
+        let level_author_name = match ffi_nullable_str(level_author_name, "level_author_name") {
+            Some(s) => s,
+            None => return false,
+        };
+
+        clear_last_error();
+        self_i.set_metadata(
+            hash_str,
+            song_name,
+            song_sub_name,
+            song_author_name,
+            level_author_name,
+        );
+        true
+    })
+}
+
+///
+/// Sets a custom star rating for a difficulty in the overlay. The names are parsed like the
+/// scraped data, so `Expert+` is `ExpertPlus`; a difficulty that doesn't parse is an invalid argument.
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarOverlay_SetStars(
+    self_i: Option<&mut BeatStarOverlay>,
+    hash: *const c_char,
+    char_name: *const c_char,
+    diff_name: *const c_char,
+    stars: f32,
+) -> bool {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return false,
+        };
+        let char_name_str = match ffi_str(char_name, "char_name") {
+            Some(s) => s,
+            None => return false,
+        };
+        let diff_name_str = match ffi_str(diff_name, "diff_name") {
+            Some(s) => s,
+            None => return false,
+        };
+        let difficulty = match parse_difficulty(diff_name_str) {
+            SongDiffs::Unknown => {
+                set_last_error(
+                    BeatStarErrorCode::InvalidArgument,
+                    format!("{diff_name_str} is not a difficulty"),
+                );
+                return false;
+            }
+            difficulty => difficulty,
+        };
+
+        clear_last_error();
+        self_i.set_stars(hash_str, Characteristic::from(char_name_str), difficulty, stars);
+        true
+    })
+}
+
+///
+/// Creates empty local and user layers. Must be freed with `Beatstar_LayersFree`
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_LayersNew() -> *mut BeatStarLayers {
+    ffi_guard(|| {
+        clear_last_error();
+        Box::into_raw(Box::<BeatStarLayers>::default())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_LayersFree(layers: *mut BeatStarLayers) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !layers.is_null() {
+            drop(Box::from_raw(layers));
+        }
+    })
+}
+
+///
+/// Gets one of the layers to fill with the `BeatStarOverlay_*` functions, owned by the layers
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayers_Layer(
+    self_i: Option<&mut BeatStarLayers>,
+    layer: BeatStarLayer,
+) -> *mut BeatStarOverlay {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.layer_mut(layer) as *mut BeatStarOverlay
+    })
+}
+
+///
+/// The database seen through the layers (user over local over a base) over the C ABI, with the
+/// lookups and queries of `BeatStarDataFile`. Only the songs the layers change or add are built,
+/// every other one is the base's.
+///
+/// The view borrows the base without owning it: the base must outlive the view, freeing it with
+/// `Beatstar_DatabaseFree` first leaves the view and every song it returned dangling.
+/// Must be freed with `BeatStarLayeredDatabase_Free`
+///
+pub struct BeatStarLayeredDatabase {
+    /// Borrows the database of `base`, see the lifetime rule above
+    view: LayeredDatabase<'static>,
+    base: *const BeatStarDataFile,
+
+    /// The FFI form of the songs the layers change or add, by index in the view
+    changed: HashMap<usize, BeatStarSong>,
+
+    /// Lazily serialized statistics of the merged songs
+    pub(crate) stats_json: OnceCell<RustCStringWrapper>,
+}
+
+impl BeatStarLayeredDatabase {
+    ///
+    /// # Safety
+    /// `base` must outlive the view, it isn't tracked by the borrow checker
+    ///
+    unsafe fn new(layers: &BeatStarLayers, base: &BeatStarDataFile) -> Self {
+        let database = &*(base.database() as *const SongDatabase);
+        let view = layers.view(database);
+        let changed = view
+            .changed_hashes()
+            .iter()
+            .filter_map(|hash| {
+                let index = view.position(hash)?;
+                Some((index, BeatStarSong::from(view.song_at(index)?)))
+            })
+            .collect();
+
+        BeatStarLayeredDatabase {
+            view,
+            base,
+            changed,
+            stats_json: OnceCell::new(),
+        }
+    }
+
+    fn base(&self) -> &BeatStarDataFile {
+        // The caller keeps the base alive while the view is
+        unsafe { &*self.base }
+    }
+
+    ///
+    /// The merged songs in their Rust model
+    ///
+    pub fn view(&self) -> &LayeredDatabase<'_> {
+        &self.view
+    }
+
+    pub fn len(&self) -> usize {
+        self.view.len()
+    }
+
+    ///
+    /// Gets the song at the index: the base's ordered by hash, then the songs only the layers
+    /// have ordered by hash
+    ///
+    pub fn song_at(&self, index: usize) -> Option<&BeatStarSong> {
+        match self.changed.get(&index) {
+            Some(song) => Some(song),
+            None => self.base().song_at(index),
+        }
+    }
+
+    ///
+    /// Gets a song by hash, in any case
+    ///
+    pub fn get_song(&self, hash: &str) -> Option<&BeatStarSong> {
+        self.song_at(self.view.position(hash)?)
+    }
+
+    ///
+    /// Gets a song by hash, falling back to its key
+    ///
+    pub fn get_song_by_id(&self, id: &str) -> Option<&BeatStarSong> {
+        self.ffi_song(self.view.get_song_by_id(id)?)
+    }
+}
+
+impl FfiSongs for BeatStarLayeredDatabase {
+    fn ffi_song(&self, song: &Song) -> Option<&BeatStarSong> {
+        self.get_song(&song.hash)
+    }
+}
+
+unsafe impl Send for BeatStarLayeredDatabase {}
+unsafe impl Sync for BeatStarLayeredDatabase {}
+
+///
+/// Looks through the layers over `base` (user over local over `base`) without copying it.
+/// The songs the layers change are built now, so make a new view after changing the layers
+/// or reloading `base`. `base` must outlive the view: free the view before the database.
+/// Must be freed with `BeatStarLayeredDatabase_Free`
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayers_View(
+    self_i: Option<&BeatStarLayers>,
+    base: Option<&BeatStarDataFile>,
+) -> *mut BeatStarLayeredDatabase {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(base);
+
+        let span = span!(Level::ERROR, "BeatStarLayers_View");
+        let _guard = span.enter();
+
+        clear_last_error();
+        // The caller keeps `base` alive while the view is, as documented above
+        Box::into_raw(Box::new(unsafe { BeatStarLayeredDatabase::new(self_i, base) }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_Free(view: *mut BeatStarLayeredDatabase) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !view.is_null() {
+            drop(Box::from_raw(view));
+        }
+    })
+}
+
+///
+/// Gets a song through the layers by hash, owned by the view or its base
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_GetSong(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    hash: *const c_char,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let hash_str = match ffi_str(hash, "hash") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_song(hash_str), "Song")
+    })
+}
+
+///
+/// Gets a song through the layers by hash (in any case) or Beat Saver key.
+/// A key gives the newest upload of the merged songs.
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_GetSongById(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    id: *const c_char,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let id_str = match ffi_str(id, "id") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_song_by_id(id_str), "Song")
+    })
+}
+
+///
+/// Get the song count, including the songs only the layers have
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_SongsLen(self_i: Option<&BeatStarLayeredDatabase>) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.len()
+    })
+}
+
+///
+/// Gets the song at the index: the base's ordered by hash, then the songs only the layers
+/// have ordered by hash. Iterate from 0 to `BeatStarLayeredDatabase_SongsLen`
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_SongAt(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    index: usize,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.song_at(index), "Song index")
+    })
+}
+
+///
+/// Gets the statistics of the merged songs as JSON, see `BeatStarDataFile_StatsJson`.
+/// The string is owned by the view and computed on first call.
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_StatsJson(
+    self_i: Option<&BeatStarLayeredDatabase>,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.stats_json().string_data
+    })
+}
+
+///
+/// Exports the merged songs to SQLite tables at `path`, see `BeatStarDataFile_ExportSqlite`
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_ExportSqlite(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    path: *const c_char,
+) -> bool {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarLayeredDatabase_ExportSqlite");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return false,
+        };
+
+        match self_i.view().export_sqlite(Path::new(path_str)) {
+            Ok(_) => {
+                clear_last_error();
+                true
+            }
+            Err(e) => {
+                report_error("Unable to export to SQLite", &e);
+                false
+            }
+        }
+    })
+}
+
+///
+/// Gets all the difficulties matching the filter through the layers. The base's are ordered
+/// by song hash, followed by the ones of songs only the layers have
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_QueryDifficulties(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    filter: Option<&BeatStarDifficultyFilter>,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+        ffi_arg!(filter);
+
+        clear_last_error();
+        let pairs = self_i.view().query_difficulties(filter);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Gets all the difficulties ranked at or after `since` through the layers, newest first
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_RankedSince(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    since: UnixTime,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        let pairs = self_i.view().ranked_since(since);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Gets the `limit` most recently ranked difficulties through the layers, newest first
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLayeredDatabase_RecentlyRanked(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    limit: usize,
+) -> *mut BeatStarSongDifficultyList {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        let pairs = self_i.view().recently_ranked(limit);
+        Box::into_raw(Box::new(BeatStarSongDifficultyList::new(self_i, pairs)))
+    })
+}
+
+///
+/// Opens a database zip as a handle owned by the caller. Handles don't share anything with each
+/// other or with `Beatstar_RetrieveDatabase`, so several sources can be loaded at once, e.g. to
+/// keep an older snapshot for `Beatstar_Diff`. Works with every `BeatStarDataFile_*` function.
+/// Must be freed with `Beatstar_DatabaseFree`
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_DatabaseOpen(file_path: *const c_char) -> *mut BeatStarDataFile {
+    ffi_guard(|| {
+        use crate::beatstar::database::beatstar_load_database_file;
+
+        let span = span!(Level::ERROR, "Beatstar_DatabaseOpen");
+        let _guard = span.enter();
+
+        let file_path_str = match ffi_str(file_path, "file_path") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match beatstar_load_database_file(file_path_str) {
+            Ok(database) => {
+                clear_last_error();
+                Box::into_raw(Box::new(BeatStarDataFile::from(database)))
+            }
+            Err(e) => {
+                report_error("Unable to open database", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Frees a handle from `Beatstar_DatabaseOpen`, along with every
+/// song and string borrowed from it. Never the one from `Beatstar_RetrieveDatabase`, null is ignored.
+///
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_DatabaseFree(data: *mut BeatStarDataFile) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !data.is_null() {
+            drop(Box::from_raw(data));
+        }
+    })
+}
+
+///
+/// Gets a song by hash (in any case) or Beat Saver key
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_GetSongById(
+    self_i: Option<&BeatStarDataFile>,
+    id: *const c_char,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let id_str = match ffi_str(id, "id") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_song_by_id(id_str), "Song")
+    })
+}
+
+///
+/// The level folders of a scan, split by whether the database has them.
+/// Must be freed with `BeatStarLevelScan_Free`
+///
+pub struct BeatStarLevelScan {
+    pub matched: Vec<(RustCStringWrapper, *const BeatStarSong)>,
+    pub unmatched: Vec<RustCStringWrapper>,
+}
+
+impl BeatStarLevelScan {
+    ///
+    /// Points the songs of a scan of `data` at their FFI form
+    ///
+    fn new(data: &impl FfiSongs, scan: LevelScan) -> Self {
+        let folder = |path: &Path| RustCStringWrapper::new(path.to_string_lossy().into_owned());
+
+        BeatStarLevelScan {
+            matched: scan
+                .matched
+                .iter()
+                .filter_map(|(path, song)| Some((folder(path), data.ffi_song(song)?)))
+                .map(|(path, song)| (path, song as *const BeatStarSong))
+                .collect(),
+            unmatched: scan.unmatched.iter().map(|path| folder(path)).collect(),
+        }
+    }
+}
+
+///
+/// Hashes every level folder in `levels_dir` (e.g. CustomLevels) and looks them up in the database
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_ScanLevels(
+    self_i: Option<&BeatStarDataFile>,
+    levels_dir: *const c_char,
+) -> *mut BeatStarLevelScan {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarDataFile_ScanLevels");
+        let _guard = span.enter();
+
+        let levels_dir_str = match ffi_str(levels_dir, "levels_dir") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match self_i.database().scan_levels(Path::new(levels_dir_str)) {
+            Ok(scan) => {
+                clear_last_error();
+                Box::into_raw(Box::new(BeatStarLevelScan::new(self_i, scan)))
+            }
+            Err(e) => {
+                report_error("Unable to scan levels", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Hashes every level folder in `levels_dir` and looks them up through the layers
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_ScanLevels(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    levels_dir: *const c_char,
+) -> *mut BeatStarLevelScan {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarLayeredDatabase_ScanLevels");
+        let _guard = span.enter();
+
+        let levels_dir_str = match ffi_str(levels_dir, "levels_dir") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match self_i.view().scan_levels(Path::new(levels_dir_str)) {
+            Ok(scan) => {
+                clear_last_error();
+                Box::into_raw(Box::new(BeatStarLevelScan::new(self_i, scan)))
+            }
+            Err(e) => {
+                report_error("Unable to scan levels", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn BeatStarLevelScan_MatchedLen(self_i: Option<&BeatStarLevelScan>) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.matched.len()
+    })
+}
+
+///
+/// Gets the database song of the matched folder at the index
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLevelScan_MatchedSong(
+    self_i: Option<&BeatStarLevelScan>,
+    index: usize,
+) -> *const BeatStarSong {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        match self_i.matched.get(index) {
+            Some((_, song)) => {
+                clear_last_error();
+                *song
+            }
+            None => ffi_ptr(None, "Index"),
+        }
+    })
+}
+
+///
+/// Gets the path of the matched folder at the index, owned by the scan
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLevelScan_MatchedFolder(
+    self_i: Option<&BeatStarLevelScan>,
+    index: usize,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        match self_i.matched.get(index) {
+            Some((folder, _)) => {
+                clear_last_error();
+                folder.string_data
+            }
+            None => ffi_ptr(None, "Index"),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn BeatStarLevelScan_UnmatchedLen(self_i: Option<&BeatStarLevelScan>) -> usize {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.unmatched.len()
+    })
+}
+
+///
+/// Gets the path of a folder that couldn't be hashed or isn't in the database, owned by the scan
+///
+#[no_mangle]
+pub extern "C" fn BeatStarLevelScan_UnmatchedFolder(
+    self_i: Option<&BeatStarLevelScan>,
+    index: usize,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        match self_i.unmatched.get(index) {
+            Some(folder) => {
+                clear_last_error();
+                folder.string_data
+            }
+            None => ffi_ptr(None, "Index"),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLevelScan_Free(scan: *mut BeatStarLevelScan) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !scan.is_null() {
+            drop(Box::from_raw(scan));
+        }
+    })
+}
+
+///
+/// Owns the strings the views of a diff report or a resolved playlist point at.
+/// Each string has its own allocation, so the views stay valid as more strings are added.
+///
+#[derive(Default)]
+struct StringStore {
+    strings: Vec<RustCStringWrapper>,
+}
+
+impl StringStore {
+    ///
+    /// Copies the string into the store and borrows it
+    ///
+    fn view(&mut self, str: &str) -> BeatStarStringView {
+        let string = RustCStringWrapper::new(str);
+        let view = (&string).into();
+        self.strings.push(string);
+        view
+    }
+}
+
+///
+/// A song re-uploaded under a new hash, matched by its Beat Saver key.
+/// The strings are owned by the report.
+///
+#[repr(C)]
+pub struct BeatStarSongUpdate {
+    pub key: BeatStarStringView,
+    pub old_hash: BeatStarStringView,
+    pub new_hash: BeatStarStringView,
+}
+
+impl BeatStarSongUpdate {
+    fn new(update: &SongUpdate, strings: &mut StringStore) -> Self {
+        BeatStarSongUpdate {
+            key: strings.view(&update.key),
+            old_hash: strings.view(&update.old_hash),
+            new_hash: strings.view(&update.new_hash),
+        }
+    }
+}
+
+///
+/// A difficulty whose rank or star rating changed between two snapshots.
+/// The strings are owned by the report.
+///
+#[repr(C)]
+pub struct BeatStarDifficultyChange {
+    pub hash: BeatStarStringView,
+    pub characteristic: BeatStarStringView,
+    pub difficulty: BeatStarStringView,
+    /// 0 when the difficulty wasn't there
+    pub old_stars: f32,
+    /// 0 when the difficulty is gone
+    pub new_stars: f32,
+}
+
+impl BeatStarDifficultyChange {
+    fn new(change: &DifficultyChange, strings: &mut StringStore) -> Self {
+        BeatStarDifficultyChange {
+            hash: strings.view(&change.hash),
+            characteristic: strings.view(&change.characteristic),
+            difficulty: strings.view(&change.difficulty),
+            old_stars: change.old_stars,
+            new_stars: change.new_stars,
+        }
+    }
+}
+
+///
+/// The hash is owned by the report
+///
+#[repr(C)]
+pub struct BeatStarVoteSwing {
+    pub hash: BeatStarStringView,
+    pub old_upvotes: u32,
+    pub old_downvotes: u32,
+    pub new_upvotes: u32,
+    pub new_downvotes: u32,
+    pub old_rating: f32,
+    pub new_rating: f32,
+}
+
+impl BeatStarVoteSwing {
+    fn new(swing: &VoteSwing, strings: &mut StringStore) -> Self {
+        BeatStarVoteSwing {
+            hash: strings.view(&swing.hash),
+            old_upvotes: swing.old_upvotes,
+            old_downvotes: swing.old_downvotes,
+            new_upvotes: swing.new_upvotes,
+            new_downvotes: swing.new_downvotes,
+            old_rating: swing.old_rating,
+            new_rating: swing.new_rating,
+        }
+    }
+}
+
+///
+/// The strings are owned by the report
+///
+#[repr(C)]
+pub struct BeatStarMetadataEdit {
+    pub hash: BeatStarStringView,
+    /// The field name as in the scraped data, e.g. `SongName`
+    pub field: BeatStarStringView,
+    pub old_value: BeatStarStringView,
+    pub new_value: BeatStarStringView,
+}
+
+impl BeatStarMetadataEdit {
+    fn new(edit: &MetadataEdit, strings: &mut StringStore) -> Self {
+        BeatStarMetadataEdit {
+            hash: strings.view(&edit.hash),
+            field: strings.view(&edit.field),
+            old_value: strings.view(&edit.old_value),
+            new_value: strings.view(&edit.new_value),
+        }
+    }
+}
+
+///
+/// What changed between two snapshots, see `SnapshotDiff`.
+/// Must be freed with `BeatStarSnapshotDiff_Free`
+///
+pub struct BeatStarSnapshotDiff {
+    pub added_songs: Vec<RustCStringWrapper>,
+    pub removed_songs: Vec<RustCStringWrapper>,
+    pub updated_songs: Vec<BeatStarSongUpdate>,
+    pub newly_ranked: Vec<BeatStarDifficultyChange>,
+    pub unranked: Vec<BeatStarDifficultyChange>,
+    pub star_changes: Vec<BeatStarDifficultyChange>,
+    pub vote_swings: Vec<BeatStarVoteSwing>,
+    pub metadata_edits: Vec<BeatStarMetadataEdit>,
+    pub json: RustCStringWrapper,
+
+    /// Owns the strings the views of the lists above borrow, never read
+    _strings: StringStore,
+}
+
+impl BeatStarSnapshotDiff {
+    ///
+    /// Copies the report for C, failing if it can't be serialized to JSON
+    ///
+    pub fn new(diff: &SnapshotDiff) -> anyhow::Result<Self> {
+        let json = serde_json::to_string(diff).context("Unable to serialize the report")?;
+
+        let hashes = |hashes: &[String]| {
+            hashes
+                .iter()
+                .map(|hash| RustCStringWrapper::new(hash.as_str()))
+                .collect()
+        };
+        let mut strings = StringStore::default();
+        let changes = |changes: &[DifficultyChange], strings: &mut StringStore| {
+            changes
+                .iter()
+                .map(|change| BeatStarDifficultyChange::new(change, strings))
+                .collect()
+        };
+
+        Ok(BeatStarSnapshotDiff {
+            added_songs: hashes(&diff.added_songs),
+            removed_songs: hashes(&diff.removed_songs),
+            updated_songs: diff
+                .updated_songs
+                .iter()
+                .map(|update| BeatStarSongUpdate::new(update, &mut strings))
+                .collect(),
+            newly_ranked: changes(&diff.newly_ranked, &mut strings),
+            unranked: changes(&diff.unranked, &mut strings),
+            star_changes: changes(&diff.star_changes, &mut strings),
+            vote_swings: diff
+                .vote_swings
+                .iter()
+                .map(|swing| BeatStarVoteSwing::new(swing, &mut strings))
+                .collect(),
+            metadata_edits: diff
+                .metadata_edits
+                .iter()
+                .map(|edit| BeatStarMetadataEdit::new(edit, &mut strings))
+                .collect(),
+            json: RustCStringWrapper::new(json),
+            _strings: strings,
+        })
+    }
+}
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    added_songs,
+    RustCStringWrapper,
+    BeatStarSnapshotDiff_AddedSongsGet,
+    BeatStarSnapshotDiff_AddedSongsLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    removed_songs,
+    RustCStringWrapper,
+    BeatStarSnapshotDiff_RemovedSongsGet,
+    BeatStarSnapshotDiff_RemovedSongsLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    updated_songs,
+    BeatStarSongUpdate,
+    BeatStarSnapshotDiff_UpdatedSongsGet,
+    BeatStarSnapshotDiff_UpdatedSongsLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    newly_ranked,
+    BeatStarDifficultyChange,
+    BeatStarSnapshotDiff_NewlyRankedGet,
+    BeatStarSnapshotDiff_NewlyRankedLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    unranked,
+    BeatStarDifficultyChange,
+    BeatStarSnapshotDiff_UnrankedGet,
+    BeatStarSnapshotDiff_UnrankedLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    star_changes,
+    BeatStarDifficultyChange,
+    BeatStarSnapshotDiff_StarChangesGet,
+    BeatStarSnapshotDiff_StarChangesLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    vote_swings,
+    BeatStarVoteSwing,
+    BeatStarSnapshotDiff_VoteSwingsGet,
+    BeatStarSnapshotDiff_VoteSwingsLen
+);
+
+vec_extern!(
+    BeatStarSnapshotDiff,
+    metadata_edits,
+    BeatStarMetadataEdit,
+    BeatStarSnapshotDiff_MetadataEditsGet,
+    BeatStarSnapshotDiff_MetadataEditsLen
+);
+
+///
+/// Compares two snapshots, e.g. one from `Beatstar_DatabaseOpen` and the freshly
+/// downloaded one. The report doesn't point into either database. Null if it can't be built.
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_Diff(
+    old: Option<&BeatStarDataFile>,
+    new: Option<&BeatStarDataFile>,
+) -> *mut BeatStarSnapshotDiff {
+    ffi_guard(|| {
+        ffi_arg!(old);
+        ffi_arg!(new);
+
+        let span = span!(Level::ERROR, "Beatstar_Diff");
+        let _guard = span.enter();
+
+        let diff = beatstar_diff(old.database(), new.database());
+        match BeatStarSnapshotDiff::new(&diff) {
+            Ok(report) => {
+                clear_last_error();
+                Box::into_raw(Box::new(report))
+            }
+            Err(e) => {
+                report_error("Unable to build the report", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Gets the whole report as JSON, owned by the report
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSnapshotDiff_Json(self_i: Option<&BeatStarSnapshotDiff>) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.json.string_data
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarSnapshotDiff_Free(diff: *mut BeatStarSnapshotDiff) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !diff.is_null() {
+            drop(Box::from_raw(diff));
+        }
+    })
+}
+
+///
+/// A playlist entry matched against the database. The strings are owned by the resolved playlist.
+///
+#[repr(C)]
+pub struct BeatStarPlaylistEntry {
+    pub hash: BeatStarStringView,
+    /// Empty when the playlist has no key for it
+    pub key: BeatStarStringView,
+    pub status: BeatStarPlaylistEntryStatus,
+    /// The database song, null when missing. Lives as long as the database.
+    pub song: *const BeatStarSong,
+}
+
+///
+/// A difficulty highlighted by a playlist entry. The strings are owned by the resolved playlist.
+///
+#[repr(C)]
+pub struct BeatStarPlaylistDifficulty {
+    /// The index of its entry
+    pub entry_index: usize,
+    pub characteristic: BeatStarStringView,
+    pub name: BeatStarStringView,
+    /// The difficulty's stars and the rest, null when the song doesn't have it.
+    /// Lives as long as the database.
+    pub stats: *const BeatStarSongDifficultyStats,
+}
+
+///
+/// A playlist resolved against a database, see `SongDatabase::resolve_playlist`.
+/// Must be freed with `BeatStarResolvedPlaylist_Free`, before the database.
+///
+pub struct BeatStarResolvedPlaylist {
+    pub title: RustCStringWrapper,
+    pub entries: Vec<BeatStarPlaylistEntry>,
+    pub difficulties: Vec<BeatStarPlaylistDifficulty>,
+    pub summary: BeatStarPlaylistSummary,
+
+    /// Owns the strings the views of the lists above borrow, never read
+    _strings: StringStore,
+}
+
+impl BeatStarResolvedPlaylist {
+    ///
+    /// Points the songs of a playlist resolved against `data` at their FFI form
+    ///
+    fn new(data: &impl FfiSongs, title: &str, resolved: ResolvedPlaylist) -> Self {
+        let mut strings = StringStore::default();
+        let mut difficulties = vec![];
+        let entries = resolved
+            .entries
+            .into_iter()
+            .enumerate()
+            .map(|(entry_index, entry)| {
+                difficulties.extend(entry.difficulties.iter().map(|difficulty| {
+                    BeatStarPlaylistDifficulty {
+                        entry_index,
+                        characteristic: strings.view(&difficulty.characteristic),
+                        name: strings.view(&difficulty.name),
+                        stats: entry
+                            .song
+                            .zip(difficulty.stats)
+                            .and_then(|(song, stats)| data.ffi_diff(song, stats))
+                            .map_or(ptr::null(), |(_, stats)| stats as *const _),
+                    }
+                }));
+
+                BeatStarPlaylistEntry {
+                    hash: strings.view(&entry.hash),
+                    key: strings.view(entry.key.as_deref().unwrap_or_default()),
+                    status: entry.status,
+                    song: entry
+                        .song
+                        .and_then(|song| data.ffi_song(song))
+                        .map_or(ptr::null(), |song| song as *const _),
+                }
+            })
+            .collect();
+
+        BeatStarResolvedPlaylist {
+            title: RustCStringWrapper::new(title),
+            entries,
+            difficulties,
+            summary: resolved.summary,
+            _strings: strings,
+        }
+    }
+}
+
+vec_extern!(
+    BeatStarResolvedPlaylist,
+    entries,
+    BeatStarPlaylistEntry,
+    BeatStarResolvedPlaylist_EntriesGet,
+    BeatStarResolvedPlaylist_EntriesLen
+);
+
+vec_extern!(
+    BeatStarResolvedPlaylist,
+    difficulties,
+    BeatStarPlaylistDifficulty,
+    BeatStarResolvedPlaylist_DifficultiesGet,
+    BeatStarResolvedPlaylist_DifficultiesLen
+);
+
+///
+/// Reads a .bplist file and looks up every entry by hash, falling back to the key
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarDataFile_ResolvePlaylist(
+    self_i: Option<&BeatStarDataFile>,
+    path: *const c_char,
+) -> *mut BeatStarResolvedPlaylist {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarDataFile_ResolvePlaylist");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match BPList::read(Path::new(path_str)) {
+            Ok(playlist) => {
+                clear_last_error();
+                let resolved = self_i.database().resolve_playlist(&playlist);
+                Box::into_raw(Box::new(BeatStarResolvedPlaylist::new(
+                    self_i,
+                    &playlist.playlist_title,
+                    resolved,
+                )))
+            }
+            Err(e) => {
+                report_error("Unable to read playlist", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Reads a .bplist file and looks up every entry through the layers, by hash then key.
+/// Must be freed with `BeatStarResolvedPlaylist_Free`, before the view.
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarLayeredDatabase_ResolvePlaylist(
+    self_i: Option<&BeatStarLayeredDatabase>,
+    path: *const c_char,
+) -> *mut BeatStarResolvedPlaylist {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let span = span!(Level::ERROR, "BeatStarLayeredDatabase_ResolvePlaylist");
+        let _guard = span.enter();
+
+        let path_str = match ffi_str(path, "path") {
+            Some(s) => s,
+            None => return ptr::null_mut(),
+        };
+
+        match BPList::read(Path::new(path_str)) {
+            Ok(playlist) => {
+                clear_last_error();
+                let resolved = self_i.view().resolve_playlist(&playlist);
+                Box::into_raw(Box::new(BeatStarResolvedPlaylist::new(
+                    self_i,
+                    &playlist.playlist_title,
+                    resolved,
+                )))
+            }
+            Err(e) => {
+                report_error("Unable to read playlist", &e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+///
+/// Gets the playlist title, owned by the resolved playlist
+///
+#[no_mangle]
+pub extern "C" fn BeatStarResolvedPlaylist_Title(
+    self_i: Option<&BeatStarResolvedPlaylist>,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
 
-        Ok(RustCStringWrapper::new(
-            value
-                .as_str()
-                .ok_or_else(|| serde::de::Error::custom("Not a string"))?
-                .as_bytes(),
-        ))
-    }
+        clear_last_error();
+        self_i.title.string_data
+    })
 }
 
-#[repr(C)]
-pub struct BeatStarDataFile {
-    pub songs: HashMap<RustCStringWrapper, BeatStarSong>,
+///
+/// Gets how many entries were found, stale or missing and the total duration
+///
+#[no_mangle]
+pub extern "C" fn BeatStarResolvedPlaylist_Summary(
+    self_i: Option<&BeatStarResolvedPlaylist>,
+) -> BeatStarPlaylistSummary {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.summary
+    })
 }
 
-unsafe impl Send for BeatStarDataFile {}
-unsafe impl Sync for BeatStarDataFile {}
-
-map_extern!(
-    BeatStarDataFile,
-    songs,
-    RustCStringWrapper,
-    BeatStarSong,
-    BeatStarDataFile_map_SongsGet,
-    BeatStarDataFile_map_SongsLen,
-    BeatStarDataFile_map_SongsGetKey
-);
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarResolvedPlaylist_Free(playlist: *mut BeatStarResolvedPlaylist) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !playlist.is_null() {
+            drop(Box::from_raw(playlist));
+        }
+    })
+}
 
-#[repr(C)]
-#[derive(Deserialize, Clone, Debug)]
+///
+/// A song of the database, opaque to C. `BeatStarSong_GetInfo` reads its fields.
+///
+#[derive(Serialize, Clone, Debug, Default)]
 #[serde(rename_all = "PascalCase", rename = "with typo")]
 pub struct BeatStarSong {
     #[serde(rename = "Bpm")]
@@ -313,28 +2328,71 @@ pub struct BeatStarSong {
     #[serde(rename = "Uploaded")]
     pub uploaded: RustCStringWrapper,
 
-    #[serde(skip_deserializing)]
     pub uploaded_unix_time: UnixTime,
 
     #[serde(rename = "Hash")]
     pub hash: RustCStringWrapper,
 
-    #[serde(skip_deserializing)]
     pub heat: f32,
 
-    #[serde(skip_deserializing)]
     pub rating: f32,
 
+    /// Scraped, or built from a local Info.dat when the song is missing from the scraped data
+    pub source: BeatStarSongSource,
+
     // last to make field offsets proper
     #[serde(rename = "Diffs")]
     pub diffs: Vec<BeatStarSongDifficultyStats>,
 
-    #[serde(skip_deserializing)]
-    pub characteristics:
-    HashMap<BeatStarCharacteristics, HashMap<RustCStringWrapper, BeatStarSongDifficultyStats>>,
+    /// The difficulties grouped by characteristic, custom ones included,
+    /// in declaration order with the custom ones last by name.
+    /// Each characteristic has its difficulties from easiest to hardest.
+    #[serde(skip)]
+    pub characteristics: Vec<BeatStarSongCharacteristic>,
 }
 
 impl BeatStarSong {
+    ///
+    /// Gets the characteristic by its raw name, the same name the characteristic counts use
+    ///
+    pub fn get_characteristic(&self, char_name: &str) -> Option<&BeatStarSongCharacteristic> {
+        self.characteristics
+            .iter()
+            .find(|c| c.name.to_string() == char_name)
+    }
+
+    ///
+    /// Gets the characteristic by its kind, the first custom one for `Custom`
+    ///
+    pub fn get_characteristic_kind(
+        &self,
+        beat_char: BeatStarCharacteristics,
+    ) -> Option<&BeatStarSongCharacteristic> {
+        self.characteristics.iter().find(|c| c.kind == beat_char)
+    }
+
+    ///
+    /// Gets the difficulty stats for the characteristic and difficulty
+    ///
+    pub fn get_difficulty(
+        &self,
+        beat_char: BeatStarCharacteristics,
+        diff: SongDiffs,
+    ) -> Option<&BeatStarSongDifficultyStats> {
+        self.get_characteristic_kind(beat_char)?.get_difficulty(diff)
+    }
+
+    ///
+    /// Gets the difficulty stats for a characteristic by its raw name, custom ones included
+    ///
+    pub fn get_difficulty_by_name(
+        &self,
+        char_name: &str,
+        diff: SongDiffs,
+    ) -> Option<&BeatStarSongDifficultyStats> {
+        self.get_characteristic(char_name)?.get_difficulty(diff)
+    }
+
     // fn convert(og: &BeatStarSongJson) -> BeatStarSong {
     //     let mut diff_convert: Vec<BeatStarSongDifficultyStats> = vec![];
     //     diff_convert.reserve(og.diffs.len());
@@ -384,96 +2442,515 @@ impl BeatStarSong {
     // }
 }
 
+fn time_string(time: Option<DateTime<Utc>>) -> RustCStringWrapper {
+    RustCStringWrapper::new(format_time(time))
+}
+
+impl From<&Song> for BeatStarSong {
+    fn from(song: &Song) -> Self {
+        let mut ffi_song = BeatStarSong {
+            bpm: song.bpm,
+            upvotes: song.upvotes,
+            downvotes: song.downvotes,
+            duration_secs: song.duration_secs,
+            key: RustCStringWrapper::new(song.key.as_str()),
+            song_name: RustCStringWrapper::new(song.song_name.as_str()),
+            song_sub_name: RustCStringWrapper::new(song.song_sub_name.as_str()),
+            song_author_name: RustCStringWrapper::new(song.song_author_name.as_str()),
+            level_author_name: RustCStringWrapper::new(song.level_author_name.as_str()),
+            uploaded: time_string(song.uploaded),
+            uploaded_unix_time: song.uploaded.map_or(0, |t| t.timestamp()),
+            hash: RustCStringWrapper::new(song.hash.as_str()),
+            heat: song.heat,
+            rating: song.rating,
+            source: song.source,
+            diffs: song
+                .difficulties
+                .iter()
+                .map(BeatStarSongDifficultyStats::from)
+                .collect(),
+            ..Default::default()
+        };
+
+        // the model already derived everything, only group the difficulties
+        let mut characteristics: BTreeMap<(BeatStarCharacteristics, String), Vec<_>> =
+            BTreeMap::new();
+        for diff in &ffi_song.diffs {
+            characteristics
+                .entry((diff.diff_characteristics, diff.char.to_string()))
+                .or_default()
+                .push(diff.clone());
+        }
+
+        ffi_song.characteristics = characteristics
+            .into_iter()
+            .map(|((kind, name), mut diffs)| {
+                // unparsable names are all Unknown, keep them apart by their raw name
+                diffs.sort_by(|a, b| {
+                    a.difficulty
+                        .cmp(&b.difficulty)
+                        .then_with(|| a.diff.as_bytes().cmp(b.diff.as_bytes()))
+                });
+
+                BeatStarSongCharacteristic {
+                    kind,
+                    name: RustCStringWrapper::new(name),
+                    diffs,
+                }
+            })
+            .collect();
+
+        ffi_song
+    }
+}
+
 // https://github.com/bsmg/beatsaver-reloaded/blob/420be0c964f3b4ee9c876f8b7fdb25495526138d/server/src/mongo/models/Beatmap.ts#L172-L177
 ///
 /// An algorithm for getting a song's rating.
 ///
 /// TODO: Remove
 #[no_mangle]
-pub extern "C" fn BeatStarSong_rating(self_i: &BeatStarSong) -> f32 {
-    let tot: f32 = (self_i.upvotes + self_i.downvotes) as f32;
-    let tmp: f32 = (self_i.upvotes) as f32 / tot;
+pub extern "C" fn BeatStarSong_rating(self_i: Option<&BeatStarSong>) -> f32 {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        let tot: f32 = (self_i.upvotes + self_i.downvotes) as f32;
+        let tmp: f32 = (self_i.upvotes) as f32 / tot;
+
+        tmp - (tmp - 0.5) * 2_f32.powf(-(tot + 1f32).log10())
+    })
+}
+
+///
+/// The plain fields of a song
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BeatStarSongInfo {
+    pub bpm: f32,
+    pub upvotes: u32,
+    pub downvotes: u32,
+    pub duration_secs: u32,
+    pub key: BeatStarStringView,
+    pub song_name: BeatStarStringView,
+    pub song_sub_name: BeatStarStringView,
+    pub song_author_name: BeatStarStringView,
+    pub level_author_name: BeatStarStringView,
+    pub uploaded: BeatStarStringView,
+    pub uploaded_unix_time: UnixTime,
+    pub hash: BeatStarStringView,
+    pub heat: f32,
+    pub rating: f32,
+}
+
+impl From<&BeatStarSong> for BeatStarSongInfo {
+    fn from(song: &BeatStarSong) -> Self {
+        BeatStarSongInfo {
+            bpm: song.bpm,
+            upvotes: song.upvotes,
+            downvotes: song.downvotes,
+            duration_secs: song.duration_secs,
+            key: (&song.key).into(),
+            song_name: (&song.song_name).into(),
+            song_sub_name: (&song.song_sub_name).into(),
+            song_author_name: (&song.song_author_name).into(),
+            level_author_name: (&song.level_author_name).into(),
+            uploaded: (&song.uploaded).into(),
+            uploaded_unix_time: song.uploaded_unix_time,
+            hash: (&song.hash).into(),
+            heat: song.heat,
+            rating: song.rating,
+        }
+    }
+}
+
+///
+/// Gets the plain fields of the song, the strings are owned by the database
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSong_GetInfo(self_i: Option<&BeatStarSong>) -> BeatStarSongInfo {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.into()
+    })
+}
+
+///
+/// Gets whether the song was scraped or built from a local Info.dat
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSong_Source(self_i: Option<&BeatStarSong>) -> BeatStarSongSource {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
 
-    tmp - (tmp - 0.5) * 2_f32.powf(-(tot + 1f32).log10())
+        clear_last_error();
+        self_i.source
+    })
 }
 
 vec_extern!(
     BeatStarSong,
     diffs,
     BeatStarSongDifficultyStats,
-    BeatStarSong_DiffPtr,
     BeatStarSong_DiffGet,
     BeatStarSong_DiffLen
 );
-map_extern!(BeatStarSong, characteristics, BeatStarCharacteristics, HashMap<RustCStringWrapper, BeatStarSongDifficultyStats>,
-    useless_BeatStarSong_map_CharacteristicsGet,
-    BeatStarSong_map_CharacteristicsLen,
-    BeatStarSong_map_CharacteristicsKeyGet);
+vec_extern!(
+    BeatStarSong,
+    characteristics,
+    BeatStarSongCharacteristic,
+    BeatStarSong_CharacteristicGet,
+    BeatStarSong_CharacteristicLen
+);
+
+///
+/// Gets the number of characteristics, custom ones included
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSong_map_CharacteristicsLen(self_i: Option<&BeatStarSong>) -> usize {
+    BeatStarSong_CharacteristicLen(self_i)
+}
+
+///
+/// Gets the kind of the characteristic at the index, `Custom` for custom ones.
+/// `BeatStarSong_CharacteristicGet` also gives their name.
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSong_map_CharacteristicsKeyGet(
+    self_i: Option<&BeatStarSong>,
+    index: usize,
+) -> *const BeatStarCharacteristics {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.characteristics.get(index).map(|c| &c.kind), "Index")
+    })
+}
+
+///
+/// Gets the characteristic by its raw name, custom ones included
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarSong_GetCharacteristic(
+    self_i: Option<&BeatStarSong>,
+    char_name: *const c_char,
+) -> *const BeatStarSongCharacteristic {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let char_name_str = match ffi_str(char_name, "char_name") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(self_i.get_characteristic(char_name_str), "Characteristic")
+    })
+}
+
+///
+/// Gets the difficulty stats based on the raw characteristic name + typed difficulty,
+/// custom characteristics included
+///
+#[no_mangle]
+pub unsafe extern "C" fn BeatStarSong_GetDifficultyByName(
+    self_i: Option<&BeatStarSong>,
+    char_name: *const c_char,
+    diff: SongDiffs,
+) -> *const BeatStarSongDifficultyStats {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let char_name_str = match ffi_str(char_name, "char_name") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(
+            self_i.get_difficulty_by_name(char_name_str, diff),
+            "Difficulty",
+        )
+    })
+}
+
+///
+/// A characteristic of a song with its difficulties, opaque to C.
+/// Custom characteristics keep their raw name, which is the name the characteristic counts use.
+///
+#[derive(Clone, Debug)]
+pub struct BeatStarSongCharacteristic {
+    pub kind: BeatStarCharacteristics,
+    pub name: RustCStringWrapper,
+    pub diffs: Vec<BeatStarSongDifficultyStats>,
+}
+
+impl BeatStarSongCharacteristic {
+    ///
+    /// Gets the difficulty stats by its raw name
+    ///
+    pub fn get(&self, diff_name: &str) -> Option<&BeatStarSongDifficultyStats> {
+        self.diffs.iter().find(|d| d.diff.to_string() == diff_name)
+    }
+
+    ///
+    /// Gets the difficulty stats by its typed difficulty
+    ///
+    pub fn get_difficulty(&self, diff: SongDiffs) -> Option<&BeatStarSongDifficultyStats> {
+        self.diffs.iter().find(|d| d.difficulty == diff)
+    }
+}
+
+vec_extern!(
+    BeatStarSongCharacteristic,
+    diffs,
+    BeatStarSongDifficultyStats,
+    BeatStarSongCharacteristic_DiffGet,
+    BeatStarSongCharacteristic_DiffLen
+);
+
+///
+/// Gets the kind of the characteristic, `Custom` for characteristics added by mods
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongCharacteristic_Kind(
+    self_i: Option<&BeatStarSongCharacteristic>,
+) -> BeatStarCharacteristics {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.kind
+    })
+}
+
+///
+/// Gets the raw name of the characteristic
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongCharacteristic_Name(
+    self_i: Option<&BeatStarSongCharacteristic>,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.name.string_data
+    })
+}
+
+///
+/// Gets the difficulty stats of the characteristic based on the typed difficulty
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongCharacteristic_GetDifficulty(
+    self_i: Option<&BeatStarSongCharacteristic>,
+    diff: SongDiffs,
+) -> *const BeatStarSongDifficultyStats {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.get_difficulty(diff), "Difficulty")
+    })
+}
 
 ///
 /// Gets the size of the hashmap of the characteristics
 ///
 #[no_mangle]
 pub extern "C" fn BeatStarSong_map_Characteristics_DifficultyStatsLen(
-    self_i: &BeatStarSong,
+    self_i: Option<&BeatStarSong>,
     beat_char: BeatStarCharacteristics,
 ) -> usize {
-    return match self_i.characteristics.get(&beat_char) {
-        None => 0,
-        Some(e) => e.len(),
-    };
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        return match self_i.get_characteristic_kind(beat_char) {
+            None => 0,
+            Some(e) => e.diffs.len(),
+        };
+    })
 }
 
 ///
 /// Gets the difficulty stats based on the characteristic key + difficulty key
 ///
 #[no_mangle]
-pub extern "C" fn BeatStarSong_map_Characteristics_DifficultyStatsGet(
-    self_i: &BeatStarSong,
+pub unsafe extern "C" fn BeatStarSong_map_Characteristics_DifficultyStatsGet(
+    self_i: Option<&BeatStarSong>,
     beat_char: BeatStarCharacteristics,
     beat_key2: *const c_char,
 ) -> *const BeatStarSongDifficultyStats {
-    match self_i.characteristics.get(&beat_char) {
-        Some(map) => match map.get(&RustCStringWrapper::from_copy(beat_key2)) {
-            None => ptr::null(),
-            Some(e) => e,
-        },
-        None => ptr::null(),
-    }
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let beat_key2_str = match ffi_str(beat_key2, "beat_key2") {
+            Some(s) => s,
+            None => return ptr::null(),
+        };
+
+        ffi_ptr(
+            self_i
+                .get_characteristic_kind(beat_char)
+                .and_then(|c| c.get(beat_key2_str)),
+            "Difficulty",
+        )
+    })
 }
 
 ///
 /// Gets the key based on the index, converted to a string.
+/// Difficulties are ordered from easiest to hardest, then by name.
 ///
 #[no_mangle]
-pub extern "C" fn BeatStarSong_map_Characteristics_DifficultyStatsGetStrKey(
-    self_i: &BeatStarSong,
+pub unsafe extern "C" fn BeatStarSong_map_Characteristics_DifficultyStatsGetStrKey(
+    self_i: Option<&BeatStarSong>,
     beat_char: BeatStarCharacteristics,
     index: usize,
 ) -> *const c_char {
-    match self_i.characteristics.get(&beat_char) {
-        None => ptr::null(),
-        Some(e) => {
-            let keys: Vec<&RustCStringWrapper> = e.keys().collect();
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        let key = self_i
+            .get_characteristic_kind(beat_char)
+            .and_then(|c| c.diffs.get(index))
+            .map(|d| &d.diff);
+
+        match ffi_ptr(key, "Difficulty index").as_ref() {
+            None => ptr::null(),
+            Some(s) => s.string_data,
+        }
+    })
+}
+
+///
+/// Starts iterating the characteristics of the song in declaration order,
+/// custom ones last by name. Must be freed with `Beatstar_CharacteristicIterFree`
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_CharacteristicIterNew(
+    song: Option<&BeatStarSong>,
+) -> *mut BeatStarCharacteristicIter {
+    ffi_guard(|| {
+        ffi_arg!(song);
+
+        clear_last_error();
+        Box::into_raw(Box::new(BeatStarCharacteristicIter { song, index: 0 }))
+    })
+}
+
+///
+/// Gets the next characteristic, null once every characteristic was visited
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_CharacteristicIterNext(
+    iter: Option<&mut BeatStarCharacteristicIter>,
+) -> *const BeatStarSongCharacteristic {
+    ffi_guard(|| {
+        ffi_arg!(iter);
+
+        clear_last_error();
+        let song = unsafe { &*iter.song };
 
-            match keys.get(index) {
-                None => ptr::null(),
-                Some(s) => s.string_data,
+        match song.characteristics.get(iter.index) {
+            None => ptr::null(),
+            Some(char) => {
+                iter.index += 1;
+                char
             }
         }
-    }
+    })
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[repr(C)]
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_CharacteristicIterFree(iter: *mut BeatStarCharacteristicIter) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !iter.is_null() {
+            drop(Box::from_raw(iter));
+        }
+    })
+}
+
+///
+/// Starts iterating the difficulties of the characteristic from easiest to hardest,
+/// then by name. Must be freed with `Beatstar_DifficultyIterFree`
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_DifficultyIterNew(
+    characteristic: Option<&BeatStarSongCharacteristic>,
+) -> *mut BeatStarDifficultyIter {
+    ffi_guard(|| {
+        ffi_arg!(characteristic);
+
+        clear_last_error();
+        Box::into_raw(Box::new(BeatStarDifficultyIter {
+            characteristic,
+            index: 0,
+        }))
+    })
+}
+
+///
+/// Gets the next difficulty, null once every difficulty was visited
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_DifficultyIterNext(
+    iter: Option<&mut BeatStarDifficultyIter>,
+) -> *const BeatStarSongDifficultyStats {
+    ffi_guard(|| {
+        ffi_arg!(iter);
+
+        clear_last_error();
+        let characteristic = unsafe { &*iter.characteristic };
+
+        match characteristic.diffs.get(iter.index) {
+            None => ptr::null(),
+            Some(diff) => {
+                iter.index += 1;
+                diff
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Beatstar_DifficultyIterFree(iter: *mut BeatStarDifficultyIter) {
+    ffi_guard(|| {
+        clear_last_error();
+        if !iter.is_null() {
+            drop(Box::from_raw(iter));
+        }
+    })
+}
+
+///
+/// Gets the difficulty stats based on the characteristic + typed difficulty
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSong_GetDifficulty(
+    self_i: Option<&BeatStarSong>,
+    beat_char: BeatStarCharacteristics,
+    diff: SongDiffs,
+) -> *const BeatStarSongDifficultyStats {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        ffi_ptr(self_i.get_difficulty(beat_char, diff), "Difficulty")
+    })
+}
+
+///
+/// A difficulty of a song, opaque to C. `BeatStarSongDifficultyStats_GetInfo` reads its fields.
+///
+#[derive(Serialize, Clone, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct BeatStarSongDifficultyStats {
     pub diff: RustCStringWrapper,
-    #[serde(skip_deserializing)]
     pub approximate_pp_value: f32,
-    #[serde(default)]
     pub stars: f32,
-    #[serde(default)]
     pub ranked: bool,
     pub njs: f32,
     #[serde(rename = "NjsOffset")]
@@ -484,23 +2961,194 @@ pub struct BeatStarSongDifficultyStats {
     #[serde(rename = "Char")]
     pub char: RustCStringWrapper,
 
-    #[serde(skip_deserializing)]
     pub diff_characteristics: BeatStarCharacteristics,
 
+    pub difficulty: SongDiffs,
+
+    pub jump_info: BeatStarJumpInfo,
+
     #[serde(rename = "RankedUpdateTime")]
     pub ranked_update_time: RustCStringWrapper,
 
-    #[serde(skip_deserializing)]
     pub ranked_update_time_unix_epoch: UnixTime,
 
     pub requirements: Vec<RustCStringWrapper>,
 }
 
+impl From<&Difficulty> for BeatStarSongDifficultyStats {
+    fn from(diff: &Difficulty) -> Self {
+        BeatStarSongDifficultyStats {
+            diff: RustCStringWrapper::new(diff.name.as_str()),
+            approximate_pp_value: diff.approximate_pp,
+            stars: diff.stars,
+            ranked: diff.ranked,
+            njs: diff.njs,
+            njs_offset: diff.njs_offset,
+            bombs: diff.bombs,
+            notes: diff.notes,
+            obstacles: diff.obstacles,
+            char: RustCStringWrapper::new(diff.characteristic.name()),
+            diff_characteristics: diff.characteristic.kind(),
+            difficulty: diff.difficulty,
+            jump_info: diff.jump_info,
+            ranked_update_time: time_string(diff.ranked_update_time),
+            ranked_update_time_unix_epoch: diff.ranked_update_time.map_or(0, |t| t.timestamp()),
+            requirements: diff
+                .requirements
+                .iter()
+                .map(|r| RustCStringWrapper::new(r.as_str()))
+                .collect(),
+        }
+    }
+}
+
+///
+/// Gets the raw characteristic name of the difficulty, useful for `Custom` characteristics
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongDifficultyStats_CharacteristicName(
+    self_i: Option<&BeatStarSongDifficultyStats>,
+) -> *const c_char {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.char.string_data
+    })
+}
+
+///
+/// Gets the maximum score of the difficulty based on its note count
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongDifficultyStats_MaxScore(self_i: Option<&BeatStarSongDifficultyStats>) -> u32 {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        beatstar_max_score_for_notes(self_i.notes)
+    })
+}
+
+///
+/// Converts a raw score on this difficulty to an accuracy percentage (0-100)
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongDifficultyStats_ScoreToAccuracy(
+    self_i: Option<&BeatStarSongDifficultyStats>,
+    score: u32,
+) -> f32 {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        score_to_accuracy_for_notes(self_i.notes, score)
+    })
+}
+
+///
+/// Converts an accuracy percentage (0-100) on this difficulty to a raw score
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongDifficultyStats_AccuracyToScore(
+    self_i: Option<&BeatStarSongDifficultyStats>,
+    accuracy: f32,
+) -> u32 {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        accuracy_to_score_for_notes(self_i.notes, accuracy)
+    })
+}
+
+///
+/// Calculates the half jump duration, jump distance and reaction time
+/// for a map with the given bpm, NJS and NJS offset
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_CalculateJumpInfo(bpm: f32, njs: f32, njs_offset: f32) -> BeatStarJumpInfo {
+    ffi_guard(|| {
+        clear_last_error();
+        beatstar_jump_info(bpm, njs, njs_offset)
+    })
+}
+
+///
+/// Gets the maximum score of a map with the amount of notes
+///
+#[no_mangle]
+pub extern "C" fn Beatstar_MaxScoreForNotes(notes: u32) -> u32 {
+    ffi_guard(|| {
+        clear_last_error();
+        beatstar_max_score_for_notes(notes)
+    })
+}
+
+///
+/// The plain fields of a difficulty
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BeatStarSongDifficultyInfo {
+    pub diff: BeatStarStringView,
+    pub difficulty: SongDiffs,
+    pub characteristic_name: BeatStarStringView,
+    pub diff_characteristics: BeatStarCharacteristics,
+    pub approximate_pp_value: f32,
+    pub stars: f32,
+    pub ranked: bool,
+    pub njs: f32,
+    pub njs_offset: f32,
+    pub bombs: u32,
+    pub notes: u32,
+    pub obstacles: u32,
+    pub ranked_update_time: BeatStarStringView,
+    pub ranked_update_time_unix_epoch: UnixTime,
+    pub jump_info: BeatStarJumpInfo,
+}
+
+impl From<&BeatStarSongDifficultyStats> for BeatStarSongDifficultyInfo {
+    fn from(diff: &BeatStarSongDifficultyStats) -> Self {
+        BeatStarSongDifficultyInfo {
+            diff: (&diff.diff).into(),
+            difficulty: diff.difficulty,
+            characteristic_name: (&diff.char).into(),
+            diff_characteristics: diff.diff_characteristics,
+            approximate_pp_value: diff.approximate_pp_value,
+            stars: diff.stars,
+            ranked: diff.ranked,
+            njs: diff.njs,
+            njs_offset: diff.njs_offset,
+            bombs: diff.bombs,
+            notes: diff.notes,
+            obstacles: diff.obstacles,
+            ranked_update_time: (&diff.ranked_update_time).into(),
+            ranked_update_time_unix_epoch: diff.ranked_update_time_unix_epoch,
+            jump_info: diff.jump_info,
+        }
+    }
+}
+
+///
+/// Gets the plain fields of the difficulty, the strings are owned by the database
+///
+#[no_mangle]
+pub extern "C" fn BeatStarSongDifficultyStats_GetInfo(
+    self_i: Option<&BeatStarSongDifficultyStats>,
+) -> BeatStarSongDifficultyInfo {
+    ffi_guard(|| {
+        ffi_arg!(self_i);
+
+        clear_last_error();
+        self_i.into()
+    })
+}
+
 vec_extern!(
     BeatStarSongDifficultyStats,
     requirements,
     RustCStringWrapper,
-    BeatStarSongDifficultyStats_ptr,
     BeatStarSongDifficultyStats_requirementsGet,
     BeatStarSongDifficultyStats_requirementsLen
 );
@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use crate::beatstar::data::BeatStarSongSource;
+use crate::beatstar::error::{BeatStarError, BeatStarErrorCode};
+use crate::beatstar::ffi::BeatStarSong;
+use crate::beatstar::level_hash::LevelFiles;
+use crate::beatstar::model::{Characteristic, Difficulty, Song};
+
+pub(crate) const INFO_DAT: &str = "Info.dat";
+
+///
+/// The parts of a level's Info.dat (v2 schema, also used by v3 beatmaps) the library reads
+///
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InfoDat {
+    #[serde(rename = "_version", default)]
+    pub version: Option<String>,
+    /// Only the v4 schema has it, which moved every field the library reads
+    #[serde(rename = "version", default)]
+    v4_version: Option<String>,
+    #[serde(rename = "_songName", default)]
+    pub song_name: String,
+    #[serde(rename = "_songSubName", default)]
+    pub song_sub_name: String,
+    #[serde(rename = "_songAuthorName", default)]
+    pub song_author_name: String,
+    #[serde(rename = "_levelAuthorName", default)]
+    pub level_author_name: String,
+    #[serde(rename = "_beatsPerMinute", default)]
+    pub bpm: f32,
+    #[serde(rename = "_difficultyBeatmapSets", default)]
+    pub difficulty_beatmap_sets: Vec<InfoDatBeatmapSet>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InfoDatBeatmapSet {
+    #[serde(rename = "_beatmapCharacteristicName", default)]
+    pub characteristic_name: String,
+    #[serde(rename = "_difficultyBeatmaps", default)]
+    pub difficulty_beatmaps: Vec<InfoDatBeatmap>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InfoDatBeatmap {
+    #[serde(rename = "_difficulty", default)]
+    pub difficulty: String,
+    #[serde(rename = "_beatmapFilename", default)]
+    pub beatmap_filename: String,
+    #[serde(rename = "_noteJumpMovementSpeed", default)]
+    pub njs: f32,
+    #[serde(rename = "_noteJumpStartBeatOffset", default)]
+    pub njs_offset: f32,
+    #[serde(rename = "_customData", default)]
+    pub custom_data: InfoDatBeatmapCustomData,
+}
+
+///
+/// The SongCore `_customData` of a difficulty
+///
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InfoDatBeatmapCustomData {
+    #[serde(rename = "_requirements", default)]
+    pub requirements: Vec<String>,
+}
+
+///
+/// Object counts of a beatmap file
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BeatmapCounts {
+    pub notes: u32,
+    pub bombs: u32,
+    pub obstacles: u32,
+}
+
+///
+/// Both beatmap schemas in one, v3 files have `colorNotes` and v2 files `_notes`
+///
+#[derive(Deserialize, Default)]
+struct BeatmapFile {
+    #[serde(rename = "colorNotes")]
+    color_notes: Option<Vec<IgnoredAny>>,
+    #[serde(rename = "bombNotes")]
+    bomb_notes: Option<Vec<IgnoredAny>>,
+    obstacles: Option<Vec<IgnoredAny>>,
+
+    #[serde(rename = "_notes")]
+    v2_notes: Option<Vec<V2Note>>,
+    #[serde(rename = "_obstacles")]
+    v2_obstacles: Option<Vec<IgnoredAny>>,
+}
+
+#[derive(Deserialize)]
+struct V2Note {
+    #[serde(rename = "_type", default)]
+    note_type: i32,
+}
+
+/// v2 note type of a bomb, 0 and 1 are the red and blue notes
+const V2_BOMB: i32 = 3;
+
+fn strip_bom(bytes: &[u8]) -> &[u8] {
+    bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes)
+}
+
+impl InfoDat {
+    ///
+    /// Parses Info.dat bytes, ignoring a UTF-8 BOM which some map editors write.
+    /// Fails with `Unsupported` for schemas other than v2, such as v4.
+    ///
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<InfoDat> {
+        let info: InfoDat = serde_json::from_slice(strip_bom(bytes))?;
+
+        // Older v2 files don't always have a version, so only a known other one is rejected
+        let version = info.v4_version.as_deref().or(info.version.as_deref());
+        if let Some(version) = version.filter(|v| v.split('.').next() != Some("2")) {
+            return Err(BeatStarError::new(
+                BeatStarErrorCode::Unsupported,
+                format!("Info.dat version {version} is not supported, only version 2 is"),
+            )
+            .into());
+        }
+
+        Ok(info)
+    }
+
+    ///
+    /// The difficulties with their characteristic name, in the order the game reads them
+    ///
+    pub fn beatmaps(&self) -> impl Iterator<Item = (&str, &InfoDatBeatmap)> {
+        self.difficulty_beatmap_sets.iter().flat_map(|set| {
+            set.difficulty_beatmaps
+                .iter()
+                .map(move |beatmap| (set.characteristic_name.as_str(), beatmap))
+        })
+    }
+
+    ///
+    /// The beatmap file names in the order the game reads them
+    ///
+    pub fn beatmap_filenames(&self) -> impl Iterator<Item = &str> {
+        self.beatmaps()
+            .map(|(_, beatmap)| beatmap.beatmap_filename.as_str())
+    }
+}
+
+impl BeatmapCounts {
+    ///
+    /// Counts the notes, bombs and obstacles of a v2 or v3 beatmap file
+    ///
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<BeatmapCounts> {
+        let file: BeatmapFile = serde_json::from_slice(strip_bom(bytes))?;
+        let len = |objects: &Option<Vec<IgnoredAny>>| objects.as_ref().map_or(0, |o| o.len()) as u32;
+
+        if file.color_notes.is_some() {
+            return Ok(BeatmapCounts {
+                notes: len(&file.color_notes),
+                bombs: len(&file.bomb_notes),
+                obstacles: len(&file.obstacles),
+            });
+        }
+
+        let v2_notes = file.v2_notes.unwrap_or_default();
+        let bombs = v2_notes.iter().filter(|n| n.note_type == V2_BOMB).count() as u32;
+
+        Ok(BeatmapCounts {
+            notes: v2_notes.len() as u32 - bombs,
+            bombs,
+            obstacles: len(&file.v2_obstacles),
+        })
+    }
+}
+
+///
+/// Builds a partial song from a level folder, for maps missing from the scraped data.
+/// Only what the files contain is filled in, so votes, key, upload date and stars stay empty.
+///
+pub fn beatstar_read_local_song(path: &Path) -> anyhow::Result<Song> {
+    let level = LevelFiles::read(path)?;
+    let info = &level.info;
+
+    let difficulties = info
+        .beatmaps()
+        .zip(&level.beatmaps)
+        .map(|((char_name, beatmap), bytes)| {
+            let counts = match bytes {
+                Some(bytes) => BeatmapCounts::parse(bytes),
+                None => Err(anyhow::anyhow!("The file is missing")),
+            };
+            let counts = counts.unwrap_or_else(|e| {
+                event!(
+                    Level::WARN,
+                    "Unable to count objects of {0} in {1}: {2:#}",
+                    beatmap.beatmap_filename,
+                    path.display(),
+                    e
+                );
+                BeatmapCounts::default()
+            });
+
+            Difficulty {
+                requirements: beatmap.custom_data.requirements.clone(),
+                ..Difficulty::new(
+                    Characteristic::from(char_name),
+                    &beatmap.difficulty,
+                    0.0,
+                    false,
+                    beatmap.njs,
+                    beatmap.njs_offset,
+                    info.bpm,
+                    (counts.notes, counts.bombs, counts.obstacles),
+                )
+            }
+        })
+        .collect();
+
+    Ok(Song {
+        hash: level.hash(),
+        key: String::new(),
+        song_name: info.song_name.clone(),
+        song_sub_name: info.song_sub_name.clone(),
+        song_author_name: info.song_author_name.clone(),
+        level_author_name: info.level_author_name.clone(),
+        bpm: info.bpm,
+        duration_secs: 0,
+        upvotes: 0,
+        downvotes: 0,
+        uploaded: None,
+        heat: 0.0,
+        rating: 0.0,
+        source: BeatStarSongSource::Local,
+        difficulties,
+    })
+}
+
+///
+/// The FFI representation of `beatstar_read_local_song`
+///
+pub fn beatstar_local_song(path: &Path) -> anyhow::Result<BeatStarSong> {
+    Ok(BeatStarSong::from(&beatstar_read_local_song(path)?))
+}
+
+///
+/// Finds a file in the level folder. Quest and Linux file systems are case sensitive
+/// while maps are made on Windows, so it falls back to a case insensitive match.
+///
+pub fn find_level_file(folder: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    find_optional_level_file(folder, name)?.ok_or_else(|| {
+        BeatStarError::new(
+            BeatStarErrorCode::NotFound,
+            format!("{0} not found in {1}", name, folder.display()),
+        )
+        .into()
+    })
+}
+
+///
+/// Like `find_level_file`, but a missing file is `None` instead of an error
+///
+pub(crate) fn find_optional_level_file(folder: &Path, name: &str) -> anyhow::Result<Option<PathBuf>> {
+    let exact = folder.join(name);
+    if exact.is_file() {
+        return Ok(Some(exact));
+    }
+
+    let entries = std::fs::read_dir(folder)
+        .with_context(|| format!("Unable to read level folder {0}", folder.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().eq_ignore_ascii_case(name) && entry.path().is_file() {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,25 @@
+use crate::beatstar::ffi::{BeatStarDataFile, BeatStarSong, BeatStarSongCharacteristic};
+
+///
+/// Walks the songs of a database ordered by hash
+///
+pub struct BeatStarSongIter {
+    pub(crate) data: *const BeatStarDataFile,
+    pub(crate) index: usize,
+}
+
+///
+/// Walks the characteristics of a song in declaration order, custom ones last by name
+///
+pub struct BeatStarCharacteristicIter {
+    pub(crate) song: *const BeatStarSong,
+    pub(crate) index: usize,
+}
+
+///
+/// Walks the difficulties of a characteristic from easiest to hardest, then by name
+///
+pub struct BeatStarDifficultyIter {
+    pub(crate) characteristic: *const BeatStarSongCharacteristic,
+    pub(crate) index: usize,
+}
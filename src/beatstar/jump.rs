@@ -0,0 +1,63 @@
+use serde::Serialize;
+
+use crate::beatstar::data::SongDiffs;
+
+/// Half jump duration in beats the game starts from before halving it
+const START_HALF_JUMP_DURATION: f32 = 4.0;
+/// The half jump duration is halved until the half jump distance fits in this.
+/// The game compares against 17.999, so a half jump distance of exactly 18 gets halved.
+const MAX_HALF_JUMP_DISTANCE: f32 = 17.999;
+/// The game never lets the half jump duration (with offset) go under this
+const MIN_HALF_JUMP_DURATION: f32 = 0.25;
+
+///
+/// How far and for how long notes travel before reaching the player
+///
+#[repr(C)]
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct BeatStarJumpInfo {
+    /// Half jump duration in beats, including the NJS offset
+    pub half_jump_duration: f32,
+    /// Jump distance in meters
+    pub jump_distance: f32,
+    /// Time from a note spawning to reaching the player, in milliseconds
+    pub reaction_time_ms: f32,
+}
+
+///
+/// The NJS the game uses for a difficulty when the map doesn't set one
+///
+pub fn default_njs(diff: SongDiffs) -> f32 {
+    match diff {
+        SongDiffs::Easy | SongDiffs::Normal | SongDiffs::Hard => 10.0,
+        SongDiffs::Expert => 12.0,
+        SongDiffs::ExpertPlus | SongDiffs::Unknown => 16.0,
+    }
+}
+
+///
+/// Calculates the jump info with the same half jump algorithm as the game
+///
+pub fn beatstar_jump_info(bpm: f32, njs: f32, njs_offset: f32) -> BeatStarJumpInfo {
+    if bpm <= 0.0 || njs <= 0.0 {
+        return BeatStarJumpInfo::default();
+    }
+
+    let seconds_per_beat = 60.0 / bpm;
+
+    let mut half_jump_duration = START_HALF_JUMP_DURATION;
+    while njs * seconds_per_beat * half_jump_duration > MAX_HALF_JUMP_DISTANCE {
+        half_jump_duration /= 2.0;
+    }
+
+    half_jump_duration = (half_jump_duration + njs_offset).max(MIN_HALF_JUMP_DURATION);
+
+    let jump_duration = seconds_per_beat * half_jump_duration * 2.0;
+
+    BeatStarJumpInfo {
+        half_jump_duration,
+        jump_distance: njs * jump_duration,
+        reaction_time_ms: jump_duration / 2.0 * 1000.0,
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use once_cell::sync::OnceCell;
+
+use crate::beatstar::bplist::{resolve_playlist_in, BPList, ResolvedPlaylist};
+use crate::beatstar::data::UnixTime;
+use crate::beatstar::database::export_songs_sqlite;
+use crate::beatstar::level_hash::{scan_levels_in, LevelScan};
+use crate::beatstar::model::Song;
+use crate::beatstar::overlay::{BeatStarOverlay, SongOverride};
+use crate::beatstar::query::{
+    difficulties_of, mapper_songs_of, query_difficulties_of, ranked_since_of, recently_ranked_of,
+    search_songs_of, BeatStarDifficultyFilter, SongDifficultyPair,
+};
+use crate::beatstar::song_database::{newest_by_key, SongDatabase, SongLookup};
+use crate::beatstar::stats::BeatStarDataStats;
+
+///
+/// The layers stacked over the scraped database, from lowest to highest priority
+///
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
+pub enum BeatStarLayer {
+    /// Songs built from local level folders
+    Local,
+    /// The user's corrected metadata and custom star ratings
+    User,
+}
+
+///
+/// The local and user layers. They're kept apart from the scraped database,
+/// so a view over a refreshed database keeps every local song and correction.
+///
+#[derive(Debug, Default)]
+pub struct BeatStarLayers {
+    pub local: BeatStarOverlay,
+    pub user: BeatStarOverlay,
+}
+
+impl BeatStarLayers {
+    pub fn layer(&self, layer: BeatStarLayer) -> &BeatStarOverlay {
+        match layer {
+            BeatStarLayer::Local => &self.local,
+            BeatStarLayer::User => &self.user,
+        }
+    }
+
+    pub fn layer_mut(&mut self, layer: BeatStarLayer) -> &mut BeatStarOverlay {
+        match layer {
+            BeatStarLayer::Local => &mut self.local,
+            BeatStarLayer::User => &mut self.user,
+        }
+    }
+
+    ///
+    /// The database seen through the layers, user over local over `base`. The base isn't copied,
+    /// only the songs the layers change or add are merged now, so make a new view after changing
+    /// the layers or reloading `base`.
+    ///
+    pub fn view<'a>(&self, base: &'a SongDatabase) -> LayeredDatabase<'a> {
+        let mut replaced = HashMap::new();
+        let mut added = Vec::new();
+
+        // A set ordered by uppercase hash, so the added songs are too
+        for hash in self.changed_hashes() {
+            match base.position(&hash) {
+                Some(index) => {
+                    if let Some(song) = self.look_through(base.song_at(index), &hash) {
+                        replaced.insert(index, song);
+                    }
+                }
+                None => added.extend(self.look_through(None, &hash)),
+            }
+        }
+
+        LayeredDatabase {
+            base,
+            replaced,
+            added,
+            by_key: OnceCell::new(),
+            stats: OnceCell::new(),
+        }
+    }
+
+    ///
+    /// The hashes of the songs the layers change or add, each once, in uppercase
+    ///
+    pub fn changed_hashes(&self) -> BTreeSet<String> {
+        [BeatStarLayer::Local, BeatStarLayer::User]
+            .into_iter()
+            .flat_map(|layer| self.layer(layer).overrides())
+            .map(|song| song.hash.to_ascii_uppercase())
+            .collect()
+    }
+
+    ///
+    /// Merges the layers' entries of `hash` over the base's song
+    ///
+    fn look_through(&self, base: Option<&Song>, hash: &str) -> Option<Song> {
+        let mut overrides = self.overrides(hash);
+
+        let mut song = match base {
+            Some(song) => song.clone(),
+            None => overrides.next()?.to_song(),
+        };
+
+        for song_override in overrides {
+            song_override.apply(&mut song);
+        }
+        Some(song)
+    }
+
+    ///
+    /// The entries of a song in the local then the user layer
+    ///
+    fn overrides<'a>(&'a self, hash: &'a str) -> impl Iterator<Item = &'a SongOverride> + 'a {
+        [BeatStarLayer::Local, BeatStarLayer::User]
+            .into_iter()
+            .filter_map(move |layer| self.layer(layer).get_override(hash))
+    }
+}
+
+///
+/// A database seen through the layers, from `BeatStarLayers::view`. It has the lookups and
+/// queries of `SongDatabase`, songs are ordered like the base's followed by the songs only
+/// the layers have, ordered by hash.
+///
+#[derive(Debug)]
+pub struct LayeredDatabase<'a> {
+    base: &'a SongDatabase,
+
+    /// The merged songs the layers change, by index in the base
+    replaced: HashMap<usize, Song>,
+
+    /// The songs only the layers have, ordered by uppercase hash
+    added: Vec<Song>,
+
+    /// The index in `songs` of the newest upload of each lowercase key, built on first use
+    by_key: OnceCell<HashMap<String, usize>>,
+
+    /// Lazily computed statistics of the merged songs
+    stats: OnceCell<BeatStarDataStats>,
+}
+
+impl<'a> LayeredDatabase<'a> {
+    pub fn base(&self) -> &'a SongDatabase {
+        self.base
+    }
+
+    ///
+    /// Every song: the base's ordered by hash, then the songs only the layers have ordered by hash
+    ///
+    pub fn songs(&self) -> impl Iterator<Item = &Song> + '_ {
+        (0..self.len()).filter_map(move |index| self.song_at(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.base.len() + self.added.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Gets the song at the index in `songs`
+    ///
+    pub fn song_at(&self, index: usize) -> Option<&Song> {
+        match index.checked_sub(self.base.len()) {
+            None => self.replaced.get(&index).or_else(|| self.base.song_at(index)),
+            Some(added) => self.added.get(added),
+        }
+    }
+
+    ///
+    /// The index of the song in `songs`, by hash in any case
+    ///
+    pub(crate) fn position(&self, hash: &str) -> Option<usize> {
+        if let Some(index) = self.base.position(hash) {
+            return Some(index);
+        }
+
+        let hash = hash.to_ascii_uppercase();
+        self.added
+            .binary_search_by(|song| song.hash.to_ascii_uppercase().cmp(&hash))
+            .ok()
+            .map(|index| self.base.len() + index)
+    }
+
+    ///
+    /// Gets a song by hash, in any case
+    ///
+    pub fn get_song(&self, hash: &str) -> Option<&Song> {
+        self.song_at(self.position(hash)?)
+    }
+
+    ///
+    /// Gets a song by its Beat Saver key, ignoring case.
+    /// The newest upload of the merged songs when several share the key.
+    ///
+    pub fn get_song_by_key(&self, key: &str) -> Option<&Song> {
+        let by_key = self.by_key.get_or_init(|| newest_by_key(self.songs()));
+
+        self.song_at(*by_key.get(&key.to_lowercase())?)
+    }
+
+    ///
+    /// Gets a song by hash, falling back to its key
+    ///
+    pub fn get_song_by_id(&self, id: &str) -> Option<&Song> {
+        self.get_song(id).or_else(|| self.get_song_by_key(id))
+    }
+
+    ///
+    /// The uppercase hashes of the songs the layers change or add
+    ///
+    pub fn changed_hashes(&self) -> BTreeSet<String> {
+        self.replaced
+            .values()
+            .chain(&self.added)
+            .map(|song| song.hash.to_ascii_uppercase())
+            .collect()
+    }
+
+    ///
+    /// Iterates every difficulty of every song, in the order of `songs`
+    ///
+    pub fn difficulties(&self) -> impl Iterator<Item = SongDifficultyPair<'_>> {
+        difficulties_of(self.songs())
+    }
+
+    ///
+    /// Gets all the difficulties matching the filter, in the order of `songs`
+    ///
+    pub fn query_difficulties(
+        &self,
+        filter: &BeatStarDifficultyFilter,
+    ) -> Vec<SongDifficultyPair<'_>> {
+        query_difficulties_of(self.songs(), filter)
+    }
+
+    ///
+    /// Gets all the ranked difficulties that were ranked at or after `since`, newest first
+    ///
+    pub fn ranked_since(&self, since: UnixTime) -> Vec<SongDifficultyPair<'_>> {
+        ranked_since_of(self.songs(), since)
+    }
+
+    ///
+    /// Gets the `limit` most recently ranked difficulties, newest first
+    ///
+    pub fn recently_ranked(&self, limit: usize) -> Vec<SongDifficultyPair<'_>> {
+        recently_ranked_of(self.songs(), limit)
+    }
+
+    ///
+    /// Gets up to `limit` songs whose name, sub name, author or mapper contains `text`,
+    /// ignoring case, in the order of `songs`
+    ///
+    pub fn search_songs(&self, text: &str, limit: usize) -> Vec<&Song> {
+        search_songs_of(self.songs(), text, limit)
+    }
+
+    ///
+    /// Gets every song mapped by `mapper`, ignoring case, in the order of `songs`
+    ///
+    pub fn mapper_songs(&self, mapper: &str) -> Vec<&Song> {
+        mapper_songs_of(self.songs(), mapper)
+    }
+
+    ///
+    /// Gets the statistics of the merged songs, computing them on first use
+    ///
+    pub fn stats(&self) -> &BeatStarDataStats {
+        self.stats
+            .get_or_init(|| BeatStarDataStats::from_songs(self.songs()))
+    }
+
+    ///
+    /// Looks up every entry of the playlist in the merged songs, see `SongDatabase::resolve_playlist`
+    ///
+    pub fn resolve_playlist(&self, playlist: &BPList) -> ResolvedPlaylist<'_> {
+        resolve_playlist_in(self, playlist)
+    }
+
+    ///
+    /// Hashes every level folder in `levels_dir` and looks it up in the merged songs
+    ///
+    pub fn scan_levels(&self, levels_dir: &Path) -> anyhow::Result<LevelScan<'_>> {
+        scan_levels_in(self, levels_dir)
+    }
+
+    ///
+    /// Writes the merged songs to SQLite tables, see `SongDatabase::export_sqlite`
+    ///
+    pub fn export_sqlite(&self, path: &Path) -> anyhow::Result<usize> {
+        export_songs_sqlite(&self.songs().collect::<Vec<_>>(), path)
+    }
+}
+
+impl SongLookup for LayeredDatabase<'_> {
+    fn song_by_hash(&self, hash: &str) -> Option<&Song> {
+        self.get_song(hash)
+    }
+
+    fn song_by_key(&self, key: &str) -> Option<&Song> {
+        self.get_song_by_key(key)
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use sha1::{Digest, Sha1};
+use tracing::{event, Level};
+
+use crate::beatstar::info_dat::{find_level_file, find_optional_level_file, InfoDat, INFO_DAT};
+use crate::beatstar::model::Song;
+use crate::beatstar::song_database::{SongDatabase, SongLookup};
+
+///
+/// The raw files of a level folder, in the order they're hashed
+///
+pub(crate) struct LevelFiles {
+    pub info_bytes: Vec<u8>,
+    pub info: InfoDat,
+    /// One per difficulty, in Info.dat order. `None` when the file is missing,
+    /// which SongCore skips when hashing.
+    pub beatmaps: Vec<Option<Vec<u8>>>,
+}
+
+impl LevelFiles {
+    pub fn read(path: &Path) -> anyhow::Result<LevelFiles> {
+        let info_path = find_level_file(path, INFO_DAT)?;
+        let info_bytes = std::fs::read(&info_path)
+            .with_context(|| format!("Unable to read {0}", info_path.display()))?;
+        let info = InfoDat::parse(&info_bytes)
+            .with_context(|| format!("Unable to parse {0}", info_path.display()))?;
+
+        let beatmaps = info
+            .beatmap_filenames()
+            .map(|filename| match find_optional_level_file(path, filename)? {
+                None => {
+                    event!(Level::WARN, "{0} is missing from {1}", filename, path.display());
+                    Ok(None)
+                }
+                Some(beatmap_path) => std::fs::read(&beatmap_path)
+                    .map(Some)
+                    .with_context(|| format!("Unable to read {0}", beatmap_path.display())),
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(LevelFiles {
+            info_bytes,
+            info,
+            beatmaps,
+        })
+    }
+
+    ///
+    /// The uppercase hex SHA-1 of Info.dat followed by every beatmap that exists
+    ///
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(&self.info_bytes);
+        for beatmap in self.beatmaps.iter().flatten() {
+            hasher.update(beatmap);
+        }
+
+        let mut hash = String::with_capacity(40);
+        for byte in hasher.finalize() {
+            let _ = write!(hash, "{byte:02X}");
+        }
+
+        hash
+    }
+}
+
+///
+/// Computes a level's hash the same way Beat Saber does: the uppercase hex SHA-1 of
+/// the raw Info.dat bytes followed by every difficulty beatmap file in Info.dat order.
+/// Missing beatmap files are skipped like SongCore does. Info.dat schemas other than v2,
+/// like v4, fail with `Unsupported` instead of giving a hash the game wouldn't.
+///
+pub fn beatstar_hash_level_folder(path: &Path) -> anyhow::Result<String> {
+    Ok(LevelFiles::read(path)?.hash())
+}
+
+///
+/// The sub folders of `levels_dir` (e.g. CustomLevels), sorted so scans are deterministic
+///
+pub(crate) fn level_folders(levels_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut folders: Vec<PathBuf> = std::fs::read_dir(levels_dir)
+        .with_context(|| format!("Unable to read levels folder {0}", levels_dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    folders.sort();
+
+    Ok(folders)
+}
+
+///
+/// The result of matching a folder of levels to the database
+///
+#[derive(Debug, Default)]
+pub struct LevelScan<'a> {
+    /// Level folders with the database song of the same hash
+    pub matched: Vec<(PathBuf, &'a Song)>,
+    /// Level folders that couldn't be hashed or aren't in the database
+    pub unmatched: Vec<PathBuf>,
+}
+
+impl SongDatabase {
+    ///
+    /// Hashes every level folder in `levels_dir` (e.g. CustomLevels) and looks it up
+    ///
+    pub fn scan_levels(&self, levels_dir: &Path) -> anyhow::Result<LevelScan<'_>> {
+        scan_levels_in(self, levels_dir)
+    }
+}
+
+///
+/// `SongDatabase::scan_levels` over any database, layered or not
+///
+pub(crate) fn scan_levels_in<'a>(
+    data: &'a impl SongLookup,
+    levels_dir: &Path,
+) -> anyhow::Result<LevelScan<'a>> {
+    let mut scan = LevelScan::default();
+
+    for folder in level_folders(levels_dir)? {
+        let song = match beatstar_hash_level_folder(&folder) {
+            Ok(hash) => data.song_by_hash(&hash),
+            Err(e) => {
+                event!(Level::WARN, "Unable to hash level {0}: {1:#}", folder.display(), e);
+                None
+            }
+        };
+
+        match song {
+            Some(song) => scan.matched.push((folder, song)),
+            None => scan.unmatched.push(folder),
+        }
+    }
+
+    event!(
+        Level::INFO,
+        "Matched {0} of {1} levels",
+        scan.matched.len(),
+        scan.matched.len() + scan.unmatched.len()
+    );
+
+    Ok(scan)
+}
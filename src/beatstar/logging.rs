@@ -0,0 +1,218 @@
+use std::ffi::{c_void, CString};
+use std::fmt::Debug;
+use std::fmt::Write;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+
+use tracing::dispatcher::DefaultGuard;
+use tracing::field::{Field, Visit};
+use tracing::{Dispatch, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+///
+/// Verbosity of the library's logs, each level includes the ones before it
+///
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BeatStarLogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<&Level> for BeatStarLogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => BeatStarLogLevel::Error,
+            Level::WARN => BeatStarLogLevel::Warn,
+            Level::INFO => BeatStarLogLevel::Info,
+            Level::DEBUG => BeatStarLogLevel::Debug,
+            Level::TRACE => BeatStarLogLevel::Trace,
+        }
+    }
+}
+
+impl BeatStarLogLevel {
+    fn from_u8(level: u8) -> BeatStarLogLevel {
+        match level {
+            0 => BeatStarLogLevel::Off,
+            1 => BeatStarLogLevel::Error,
+            2 => BeatStarLogLevel::Warn,
+            3 => BeatStarLogLevel::Info,
+            4 => BeatStarLogLevel::Debug,
+            _ => BeatStarLogLevel::Trace,
+        }
+    }
+}
+
+///
+/// Receives every log event of the library. `target` and `message` are only valid during the call.
+/// Can be called from any thread the library logs from, including ones it didn't start on.
+///
+pub type BeatStarLogCallback = Option<
+    unsafe extern "C" fn(
+        level: BeatStarLogLevel,
+        target: *const c_char,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ),
+>;
+
+#[derive(Clone, Copy)]
+struct LogSink {
+    callback: unsafe extern "C" fn(BeatStarLogLevel, *const c_char, *const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The host is responsible for user_data being usable from the threads it calls the library from
+unsafe impl Send for LogSink {}
+unsafe impl Sync for LogSink {}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(BeatStarLogLevel::Info as u8);
+static LOG_SINK: RwLock<Option<LogSink>> = RwLock::new(None);
+
+lazy_static! {
+    ///
+    /// The library's own subscriber. It's the default while inside an FFI call, and the
+    /// global default once a log callback is set, unless the process already has one.
+    ///
+    static ref LOG_DISPATCH: Dispatch = Dispatch::new(
+        tracing_subscriber::registry()
+            .with(filter_fn(level_enabled))
+            .with(CallbackLayer)
+            .with(output_layer().with_filter(filter_fn(|_| log_sink().is_none())))
+    );
+}
+
+///
+/// Sends the library's logs to `callback`. `user_data` must stay valid until the callback
+/// is replaced, and calls already in progress on other threads may still use the old one.
+///
+pub fn set_log_callback(callback: BeatStarLogCallback, user_data: *mut c_void) {
+    let sink = callback.map(|callback| LogSink {
+        callback,
+        user_data,
+    });
+    let has_sink = sink.is_some();
+
+    match LOG_SINK.write() {
+        Ok(mut guard) => *guard = sink,
+        Err(poisoned) => *poisoned.into_inner() = sink,
+    }
+
+    // Events outside the FFI calls (Rust callers, threads like the server's) only reach the
+    // callback through the global default. A subscriber the host already set is left alone.
+    if has_sink {
+        let _ = tracing::dispatcher::set_global_default(LOG_DISPATCH.clone());
+    }
+}
+
+pub fn set_log_level(level: BeatStarLogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn log_level() -> BeatStarLogLevel {
+    BeatStarLogLevel::from_u8(LOG_LEVEL.load(Ordering::Relaxed))
+}
+
+///
+/// Routes the library's events to its own subscriber until the guard is dropped
+///
+pub(crate) fn log_scope() -> DefaultGuard {
+    tracing::dispatcher::set_default(&LOG_DISPATCH)
+}
+
+fn log_sink() -> Option<LogSink> {
+    match LOG_SINK.read() {
+        Ok(guard) => *guard,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+fn level_enabled(metadata: &Metadata<'_>) -> bool {
+    BeatStarLogLevel::from(metadata.level()) <= log_level()
+}
+
+#[cfg(target_os = "android")]
+fn output_layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    paranoid_android::layer(env!("CARGO_PKG_NAME"))
+}
+
+#[cfg(not(target_os = "android"))]
+fn output_layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+}
+
+///
+/// Formats the `message` field followed by the other fields as `name=value`
+///
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {0}={1}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {0}={1:?}", field.name(), value);
+        }
+    }
+}
+
+///
+/// Forwards events to the host's log callback, if one is set
+///
+struct CallbackLayer;
+
+impl<S: Subscriber> Layer<S> for CallbackLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Copied out so the callback can set a new callback without deadlocking
+        let sink = match log_sink() {
+            Some(sink) => sink,
+            None => return,
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let mut message = visitor.message.into_bytes();
+        message.extend_from_slice(visitor.fields.as_bytes());
+        message.retain(|b| *b != 0);
+
+        let target = CString::new(event.metadata().target().replace('\0', "")).unwrap_or_default();
+        let message = CString::new(message).unwrap_or_default();
+
+        unsafe {
+            (sink.callback)(
+                event.metadata().level().into(),
+                target.as_ptr(),
+                message.as_ptr(),
+                sink.user_data,
+            )
+        }
+    }
+}
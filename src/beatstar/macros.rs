@@ -1,65 +1,45 @@
+///
+/// Unwraps a nullable pointer argument of an FFI function, returning the function's
+/// error value with `InvalidArgument` set when it's null. Use inside `ffi_guard`.
+///
 #[macro_export]
-macro_rules! vec_extern {
-    ($clazz:ident, $vec:ident, $r:ty, $func_get_ptr_name:ident, $func_get_name:ident, $func_len_name:ident) => {
-        /// Gets the item in the vector from index
-        #[no_mangle]
-        pub extern "C" fn $func_get_ptr_name(self_i: &$clazz) -> *const $r {
-            return self_i.$vec.as_ptr();
-        }
-
-
-        /// Gets the item in the vector from index
-        #[no_mangle]
-        pub extern "C" fn $func_get_name(self_i: &$clazz, index: usize) -> *const $r {
-            return match (self_i.$vec).get(index) {
-                Some(e) => e,
-                None => ptr::null(),
+macro_rules! ffi_arg {
+    ($arg:ident) => {
+        let $arg = match $arg {
+            Some(value) => value,
+            None => {
+                set_last_error(
+                    BeatStarErrorCode::InvalidArgument,
+                    concat!(stringify!($arg), " is null"),
+                );
+                return $crate::beatstar::error::FfiDefault::ffi_default();
             }
-        }
-
-        /// Gets the length of the vector
-        #[no_mangle]
-        pub extern "C" fn $func_len_name(self_i: &$clazz) -> usize {
-            return (*self_i.$vec).len();
-        }
+        };
     };
 }
 
 #[macro_export]
-macro_rules! map_extern {
-    ($clazz:ident, $hashmap:ident, $k:ty, $r:ty, $func_get_name:ident, $func_len_name:ident, $func_get_key_name:ident) => {
-
-        ///
-        /// Get the value in the hashmap from the key
-        ///
-        #[no_mangle]
-        pub extern "C" fn $func_get_name(self_i: &$clazz, index: &$k) -> *const $r {
-            return match (self_i.$hashmap).get(&index) {
-                Some(e) => e,
-                None => ptr::null(),
-            }
-        }
-
-        ///
-        /// Get the key in the hashmap from the index in it's set.
-        ///
+macro_rules! vec_extern {
+    ($clazz:ident, $vec:ident, $r:ty, $func_get_name:ident, $func_len_name:ident) => {
+        /// Gets the item in the vector from index
         #[no_mangle]
-        pub extern "C" fn $func_get_key_name(self_i: &$clazz, index: usize) -> *const $k {
+        pub extern "C" fn $func_get_name(self_i: Option<&$clazz>, index: usize) -> *const $r {
+            return ffi_guard(|| {
+                ffi_arg!(self_i);
 
-            let keys: Vec<&$k> = (self_i.$hashmap).keys().collect();
-            return match keys.get(index) {
-                Some(e) => *e,
-                None => ptr::null(),
-            };
-            
+                ffi_ptr((self_i.$vec).get(index), "Index")
+            });
         }
 
-        ///
-        /// Get the length of the hashmap
-        ///
+        /// Gets the length of the vector
         #[no_mangle]
-        pub extern "C" fn $func_len_name(self_i: &$clazz) -> usize {
-            return (self_i.$hashmap).len();
+        pub extern "C" fn $func_len_name(self_i: Option<&$clazz>) -> usize {
+            return ffi_guard(|| {
+                ffi_arg!(self_i);
+
+                clear_last_error();
+                (*self_i.$vec).len()
+            });
         }
     };
 }
@@ -1,14 +1,41 @@
-use crate::beatstar::ffi::BeatStarDataFile;
 use once_cell::sync::OnceCell;
 use std::env;
 
-mod data;
-mod database;
+mod abi;
+pub mod bplist;
+pub mod data;
+pub mod database;
+mod error;
+pub mod export;
+pub mod info_dat;
 
 #[macro_use]
 mod macros;
 mod ffi;
+mod iter;
+mod jump;
+pub mod layers;
+pub mod level_hash;
+mod logging;
+pub mod model;
 mod numstuff;
+pub mod overlay;
+pub mod query;
+pub mod score;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod snapshot_diff;
+pub mod song_database;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod stats;
+
+pub use error::{error_code, BeatStarError, BeatStarErrorCode};
+pub use ffi::{
+    BeatStarDataFile, BeatStarSong, BeatStarSongDifficultyStats, RustCStringWrapper,
+};
+pub use jump::BeatStarJumpInfo;
+pub use song_database::SongDatabase;
 
 static BEAT_STAR_FILE: OnceCell<BeatStarDataFile> = OnceCell::new();
 // static BEAT_STAR_MUTEX: Mutex<i8> = Mutex::new(0);
@@ -19,6 +46,73 @@ mod tests {
     use database::*;
     use stopwatch::Stopwatch;
 
+    const TEST_SONGS_JSON: &str = r#"[
+        {
+            "Bpm": 128.0, "Upvotes": 120, "Downvotes": 4, "Duration": 180,
+            "Key": "1a2b", "SongName": "Test Song", "SongSubName": "", "SongAuthorName": "Artist",
+            "LevelAuthorName": "Mapper", "Uploaded": "2021-05-01T10:00:00Z",
+            "Hash": "AAAA000000000000000000000000000000000001",
+            "Diffs": [
+                {
+                    "Diff": "Expert", "Stars": 6.5, "Ranked": true, "Njs": 18.0, "NjsOffset": 0.0,
+                    "Bombs": 10, "Notes": 700, "Obstacles": 4, "Char": "Standard",
+                    "RankedUpdateTime": "2021-06-01T12:00:00Z", "Requirements": []
+                },
+                {
+                    "Diff": "ExpertPlus", "Stars": 8.25, "Ranked": true, "Njs": 20.0, "NjsOffset": -0.25,
+                    "Bombs": 12, "Notes": 950, "Obstacles": 6, "Char": "Standard",
+                    "RankedUpdateTime": "2021-06-08T12:00:00.5", "Requirements": ["Noodle Extensions"]
+                },
+                {
+                    "Diff": "Hard", "Stars": 0, "Ranked": false, "Njs": 14.0, "NjsOffset": 0.5,
+                    "Bombs": 0, "Notes": 300, "Obstacles": 0, "Char": "OneSaber",
+                    "RankedUpdateTime": null, "Requirements": []
+                }
+            ]
+        },
+        {
+            "Bpm": 200.0, "Upvotes": 3, "Downvotes": 9, "Duration": 95,
+            "Key": "ff01", "SongName": "Other Song", "SongSubName": "Remix", "SongAuthorName": "Someone",
+            "LevelAuthorName": "Mapper", "Uploaded": "2022-01-15T08:30:00Z",
+            "Hash": "BBBB000000000000000000000000000000000002",
+            "Diffs": [
+                {
+                    "Diff": "Normal", "Stars": 2.0, "Ranked": true, "Njs": 10.0, "NjsOffset": 0.0,
+                    "Bombs": 0, "Notes": 200, "Obstacles": 1, "Char": "Standard",
+                    "RankedUpdateTime": "", "Requirements": []
+                },
+                {
+                    "Diff": "Expert", "Stars": 0, "Ranked": false, "Njs": 16.0, "NjsOffset": 0.0,
+                    "Bombs": 0, "Notes": 410, "Obstacles": 0, "Char": "Ghost",
+                    "RankedUpdateTime": null, "Requirements": []
+                }
+            ]
+        }
+    ]"#;
+
+    fn test_database() -> BeatStarDataFile {
+        SongDatabase::from_songs(beatstar_json_content(TEST_SONGS_JSON).unwrap()).into()
+    }
+
+    // Zips the json like the scraped database, under a temp path unique to `name`
+    fn write_test_zip(name: &str, json: &str) -> std::path::PathBuf {
+        use std::io::Write;
+
+        let path = env::temp_dir().join(format!("songdatacore_{0}_{1}.zip", name, std::process::id()));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file("combinedScrappedData.json", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(json.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    // Reads a view that must outlive the returned string, as its owner does in the tests
+    fn view_str(view: &ffi::BeatStarStringView) -> &str {
+        let bytes = unsafe { std::slice::from_raw_parts(view.data as *const u8, view.len) };
+        std::str::from_utf8(bytes).unwrap()
+    }
+
     #[test]
     fn download_db() -> anyhow::Result<()> {
         let mut stopwatch = Stopwatch::start_new();
@@ -43,10 +137,10 @@ mod tests {
         let diff = (song
             .expect("Could not fetch song database")
             .expect("Could not find song in database")
-            .diffs)[0]
+            .difficulties)[0]
             .clone();
-        println!("Got the notes!: {0} {1}", diff.diff.to_string(), diff.notes);
-        println!("Got the stars!: {0} {1}", diff.diff.to_string(), diff.stars);
+        println!("Got the notes!: {0} {1}", diff.name, diff.notes);
+        println!("Got the stars!: {0} {1}", diff.name, diff.stars);
     }
 
     #[test]
@@ -81,18 +175,19 @@ mod tests {
         download_db().unwrap();
         // assert_eq!(2 + 2, 4);
 
-        let song = beatstar_get_song("B9BED84A127130BF80AFF18DB677EDD215CE0AB5")
+        let song = beatstar_retrieve_data_file()
             .unwrap()
+            .get_song("B9BED84A127130BF80AFF18DB677EDD215CE0AB5")
             .unwrap();
 
         for _i in 0..3 {
             let diff_map_size = song.characteristics.len();
             println!("Characteristics size: {diff_map_size}");
             assert_eq!(diff_map_size, 2);
-            for (chara, diff_map) in &song.characteristics {
-                println!("Got the char!: {0} {1}", chara, diff_map.len());
+            for chara in &song.characteristics {
+                println!("Got the char!: {0} {1}", chara.name.to_string(), chara.diffs.len());
 
-                for (_diff_name, diff) in diff_map {
+                for diff in &chara.diffs {
                     println!(
                         "Got the diff!: {0} with pp {1}",
                         diff.diff.to_string(),
@@ -102,4 +197,1415 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_song_diffs() {
+        use crate::beatstar::data::SongDiffs;
+        use std::str::FromStr;
+
+        assert_eq!(SongDiffs::from_str("ExpertPlus"), Ok(SongDiffs::ExpertPlus));
+        assert_eq!(SongDiffs::from_str("Expert+"), Ok(SongDiffs::ExpertPlus));
+        assert_eq!(SongDiffs::from_str("hard"), Ok(SongDiffs::Hard));
+        assert!(SongDiffs::from_str("Insane").is_err());
+        assert!(SongDiffs::Easy < SongDiffs::ExpertPlus);
+        assert!(SongDiffs::ExpertPlus < SongDiffs::Unknown);
+    }
+
+    #[test]
+    fn ranked_timestamps() {
+        let db = test_database();
+        let db = db.database();
+
+        let unix_time = |time| parse_unix_time(time).unwrap();
+        let ranked = db.recently_ranked(10);
+        let times: Vec<_> = ranked
+            .iter()
+            .map(|(_, diff)| diff.ranked_update_time.unwrap().timestamp())
+            .collect();
+        assert_eq!(
+            times,
+            vec![
+                unix_time("2021-06-08T12:00:00Z"),
+                unix_time("2021-06-01T12:00:00Z")
+            ]
+        );
+
+        let since = db.ranked_since(unix_time("2021-06-05T00:00:00Z"));
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].1.name, "ExpertPlus");
+        assert!(parse_unix_time("").is_none());
+    }
+
+    #[test]
+    fn custom_characteristics() {
+        use crate::beatstar::data::{BeatStarCharacteristics, SongDiffs};
+        use crate::beatstar::ffi::*;
+
+        let db = test_database();
+        let song = db.get_song("BBBB000000000000000000000000000000000002").unwrap();
+
+        // custom characteristics are in the same view as the known ones, under the counted name
+        let names: Vec<String> = song.characteristics.iter().map(|c| c.name.to_string()).collect();
+        assert_eq!(names, vec!["Standard", "Ghost"]);
+        assert!(song
+            .characteristics
+            .iter()
+            .all(|c| db.characteristic_count(&c.name.to_string()).is_some()));
+
+        let ghost = song.get_difficulty_by_name("Ghost", SongDiffs::Expert).unwrap();
+        assert_eq!(ghost.diff_characteristics, BeatStarCharacteristics::Custom);
+        assert_eq!(
+            song.get_characteristic("Ghost").unwrap().kind,
+            BeatStarCharacteristics::Custom
+        );
+        assert!(song.get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::Normal).is_some());
+        assert_eq!(ghost.notes, 410);
+        let info = crate::beatstar::ffi::BeatStarSongDifficultyStats_GetInfo(Some(ghost));
+        assert_eq!(info.characteristic_name.len, "Ghost".len());
+
+        assert_eq!(db.characteristic_count("Ghost"), Some(&1));
+        assert_eq!(db.characteristic_count("Standard"), Some(&3));
+        let names: Vec<String> = (0..BeatStarDataFile_map_CharacteristicCountsLen(Some(&db)))
+            .map(|i| unsafe { &*BeatStarDataFile_map_CharacteristicCountsGetKey(Some(&db), i) }.to_string())
+            .collect();
+        assert_eq!(names, vec!["Ghost", "OneSaber", "Standard"]);
+    }
+
+    #[test]
+    fn dataset_stats() {
+        use crate::beatstar::data::SongDiffs;
+
+        let db = test_database();
+        let stats = db.database().stats();
+
+        assert_eq!(stats.song_count, 2);
+        assert_eq!(stats.difficulty_count, 5);
+        assert_eq!(stats.ranked_song_count, 2);
+        assert_eq!(stats.ranked_difficulty_count, 3);
+        assert_eq!(stats.ranked_counts["Standard"][&SongDiffs::Expert], 1);
+        assert_eq!(stats.stars.count, 3);
+        assert_eq!(stats.stars.max, 8.25);
+        assert_eq!(stats.stars.percentiles.p50, 6.5);
+        assert_eq!(stats.bpm.histogram.iter().map(|b| b.count).sum::<usize>(), 2);
+        assert_eq!(stats.uploads_per_month["2021-05"], 1);
+        assert!(std::ptr::eq(stats, db.database().stats()));
+
+        let json: serde_json::Value = serde_json::from_str(&db.stats_json().to_string()).unwrap();
+        assert_eq!(json["song_count"], 2);
+        assert_eq!(json["ranked_counts"]["Standard"]["ExpertPlus"], 1);
+
+        // Spellings of a difficulty are counted together, and maps without an NJS use the default
+        let songs = beatstar_json_content(
+            &TEST_SONGS_JSON
+                .replace("\"Diff\": \"ExpertPlus\"", "\"Diff\": \"Expert+\"")
+                .replace("\"Njs\": 20.0", "\"Njs\": 0"),
+        )
+        .unwrap();
+        let spelled = SongDatabase::from_songs(songs);
+        let spelled_stats = spelled.stats();
+        assert_eq!(spelled_stats.ranked_counts["Standard"][&SongDiffs::ExpertPlus], 1);
+        assert_eq!(spelled_stats.njs.count, 5);
+        assert!(spelled_stats.njs.min > 0.0);
+    }
+
+    #[test]
+    fn max_score() {
+        use crate::beatstar::score::*;
+
+        assert_eq!(beatstar_max_score_for_notes(0), 0);
+        assert_eq!(beatstar_max_score_for_notes(1), 115);
+        assert_eq!(beatstar_max_score_for_notes(5), 1035);
+        assert_eq!(beatstar_max_score_for_notes(13), 4715);
+        assert_eq!(beatstar_max_score_for_notes(14), 5635);
+
+        let db = test_database();
+        let diff = db
+            .database()
+            .difficulties()
+            .map(|(_, diff)| diff)
+            .find(|d| d.notes == 700)
+            .unwrap();
+        let max_score = beatstar_max_score(diff);
+        assert_eq!(max_score, 4715 + 687 * 920);
+        assert_eq!(beatstar_score_to_accuracy(diff, max_score), 100.0);
+        assert_eq!(beatstar_accuracy_to_score(diff, 50.0), max_score / 2);
+        assert_eq!(beatstar_accuracy_to_score(diff, 150.0), max_score);
+
+        // The C ABI gives the same from the difficulty's FFI form
+        let ffi_diff = db.songs().flat_map(|s| &s.diffs).find(|d| d.notes == 700);
+        assert_eq!(crate::beatstar::ffi::BeatStarSongDifficultyStats_MaxScore(ffi_diff), max_score);
+        assert_eq!(
+            crate::beatstar::ffi::BeatStarSongDifficultyStats_AccuracyToScore(ffi_diff, 50.0),
+            max_score / 2
+        );
+    }
+
+    #[test]
+    fn jump_distance() {
+        use crate::beatstar::jump::beatstar_jump_info;
+        use crate::beatstar::query::BeatStarDifficultyFilter;
+
+        // 128 bpm at 18 njs halves the starting 4 beats down to 2
+        let jump = beatstar_jump_info(128.0, 18.0, 0.0);
+        assert_eq!(jump.half_jump_duration, 2.0);
+        assert!((jump.reaction_time_ms - 937.5).abs() < 0.01);
+        assert!((jump.jump_distance - 33.75).abs() < 0.01);
+
+        // offsets can't push the half jump duration under the minimum
+        assert_eq!(beatstar_jump_info(128.0, 18.0, -5.0).half_jump_duration, 0.25);
+
+        // a half jump distance of exactly 18 is still halved, like the game's 17.999 check
+        for (bpm, njs, half_jump_duration) in [(120.0, 18.0, 1.0), (200.0, 15.0, 2.0), (160.0, 24.0, 1.0)] {
+            let jump = beatstar_jump_info(bpm, njs, 0.0);
+            assert_eq!(jump.half_jump_duration, half_jump_duration, "{bpm} bpm at {njs} njs");
+            assert!((jump.jump_distance - 18.0).abs() < 0.01, "{bpm} bpm at {njs} njs");
+        }
+
+        let db = test_database();
+        let filter = BeatStarDifficultyFilter {
+            max_reaction_time_ms: 800.0,
+            ..Default::default()
+        };
+        let fast = db.database().query_difficulties(&filter);
+        let fast: Vec<_> = fast.iter().map(|(_, d)| d.characteristic.name()).collect();
+        assert_eq!(fast, vec!["Standard", "Ghost"]);
+
+        // difficulties without an NJS are filtered on the default NJS of the difficulty
+        let no_njs = SongDatabase::from_songs(
+            beatstar_json_content(&TEST_SONGS_JSON.replace("\"Njs\": 10.0", "\"Njs\": 0"))
+                .unwrap(),
+        );
+        let filter = BeatStarDifficultyFilter {
+            min_njs: 10.0,
+            max_njs: 10.0,
+            ..Default::default()
+        };
+        let defaulted = no_njs.query_difficulties(&filter);
+        assert_eq!(defaulted.len(), 1);
+        assert_eq!((defaulted[0].1.njs, defaulted[0].1.effective_njs()), (0.0, 10.0));
+    }
+
+    #[test]
+    fn last_error_reporting() {
+        use crate::beatstar::error::*;
+        use crate::beatstar::ffi::*;
+
+        let song = unsafe { Beatstar_GetSong(std::ptr::null()) };
+        assert!(song.is_null());
+        assert_eq!(Beatstar_LastError(), BeatStarErrorCode::InvalidArgument);
+        assert!(!Beatstar_LastErrorMessage().is_null());
+
+        let path = std::ffi::CString::new("/nonexistent/db.zip").unwrap();
+        let missing = unsafe { Beatstar_RetrieveDatabaseLocal(path.as_ptr()) };
+        // the database may have already been loaded by another test
+        if missing.is_null() {
+            assert_eq!(Beatstar_LastError(), BeatStarErrorCode::Io);
+        }
+
+        let corrupt = beatstar_zip_content(b"not a zip".to_vec()).unwrap_err();
+        assert_eq!(error_code(&corrupt), BeatStarErrorCode::CorruptData);
+
+        let mut empty = Vec::new();
+        zip::ZipWriter::new(std::io::Cursor::new(&mut empty)).finish().unwrap();
+        let empty = beatstar_zip_content(empty).unwrap_err();
+        assert_eq!(error_code(&empty), BeatStarErrorCode::CorruptData);
+        assert!(empty.to_string().contains("empty"));
+        // Rust callers reach the code through the re-exported error
+        let error = empty.downcast_ref::<crate::beatstar::BeatStarError>().unwrap();
+        assert_eq!(error.code, crate::beatstar::BeatStarErrorCode::CorruptData);
+
+        Beatstar_MaxScoreForNotes(1);
+        assert_eq!(Beatstar_LastError(), BeatStarErrorCode::Ok);
+        assert!(Beatstar_LastErrorMessage().is_null());
+    }
+
+    #[test]
+    fn null_inputs_and_strings() {
+        use crate::beatstar::data::BeatStarCharacteristics;
+        use crate::beatstar::error::*;
+        use crate::beatstar::ffi::*;
+
+        assert!(BeatStarSong_GetInfo(None).hash.data.is_null());
+        assert_eq!(last_error(), BeatStarErrorCode::InvalidArgument);
+
+        assert_eq!(BeatStarSong_DiffLen(None), 0);
+        assert_eq!(last_error(), BeatStarErrorCode::InvalidArgument);
+
+        assert!(BeatStarDataFile_map_SongsGet(None, None).is_null());
+        assert!(Beatstar_SongIterNext(None).is_null());
+        assert!(Beatstar_CharacteristicIterNext(None).is_null());
+        assert!(BeatStarDataFile_QueryDifficulties(None, None).is_null());
+
+        let db = test_database();
+        let song = BeatStarDataFile_SongAt(Some(&db), 0);
+        let song = unsafe { song.as_ref() };
+        assert!(unsafe {
+            BeatStarSong_map_Characteristics_DifficultyStatsGet(
+                song,
+                BeatStarCharacteristics::Standard,
+                std::ptr::null(),
+            )
+        }
+        .is_null());
+        assert_eq!(last_error(), BeatStarErrorCode::InvalidArgument);
+
+        // Interior nul bytes are dropped instead of panicking
+        let wrapper = RustCStringWrapper::new("Ghost\0Song");
+        assert_eq!(wrapper.to_string(), "GhostSong");
+        assert_eq!(wrapper.clone(), wrapper);
+        assert_eq!(BeatStarStringView::from(&wrapper).len, 9);
+
+        unsafe {
+            Beatstar_StringFree(std::ffi::CString::new("owned").unwrap().into_raw());
+            Beatstar_StringFree(std::ptr::null_mut());
+        }
+        assert_eq!(last_error(), BeatStarErrorCode::Ok);
+    }
+
+    #[test]
+    fn panic_guard() {
+        use crate::beatstar::error::*;
+
+        let result: *const u8 = ffi_guard(|| panic!("boom"));
+        assert!(result.is_null());
+        assert_eq!(last_error(), BeatStarErrorCode::Panic);
+
+        let message = unsafe { std::ffi::CStr::from_ptr(last_error_message()) };
+        assert!(message.to_str().unwrap().contains("boom"));
+    }
+
+    #[test]
+    fn abi_check() {
+        use crate::beatstar::abi::*;
+        use crate::beatstar::error::*;
+        use crate::beatstar::ffi::*;
+
+        let mut sizes = Beatstar_AbiSizes();
+        assert_eq!(sizes, BeatStarAbiSizes::current());
+        assert!(unsafe { Beatstar_CheckAbi(BEATSTAR_ABI_VERSION, &sizes) });
+        assert_eq!(last_error(), BeatStarErrorCode::Ok);
+
+        assert!(!unsafe { Beatstar_CheckAbi(BEATSTAR_ABI_VERSION + 1, &sizes) });
+        assert_eq!(last_error(), BeatStarErrorCode::AbiMismatch);
+
+        sizes.song_info += 4;
+        assert!(!unsafe { Beatstar_CheckAbi(BEATSTAR_ABI_VERSION, &sizes) });
+        let message = unsafe { std::ffi::CStr::from_ptr(last_error_message()) };
+        assert!(message.to_str().unwrap().contains("song_info"));
+
+        let version = unsafe { std::ffi::CStr::from_ptr(Beatstar_Version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+
+    // Tests that change the process wide log callback or level hold this
+    static LOG_SETTINGS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn log_callback() {
+        use crate::beatstar::error::ffi_guard;
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::logging::BeatStarLogLevel;
+        use std::ffi::{c_void, CStr};
+        use std::os::raw::c_char;
+        use std::sync::Mutex;
+        use tracing::{event, Level};
+
+        type Logs = Mutex<Vec<(BeatStarLogLevel, String, String)>>;
+
+        unsafe extern "C" fn collect(
+            level: BeatStarLogLevel,
+            target: *const c_char,
+            message: *const c_char,
+            user_data: *mut c_void,
+        ) {
+            let logs = &*(user_data as *const Logs);
+            let target = CStr::from_ptr(target).to_str().unwrap().to_string();
+            let message = CStr::from_ptr(message).to_str().unwrap().to_string();
+            // Other tests log through the same callback while this one runs
+            if message.starts_with("log_callback") {
+                logs.lock().unwrap().push((level, target, message));
+            }
+        }
+
+        let _settings = LOG_SETTINGS.lock().unwrap_or_else(|e| e.into_inner());
+        // Leaked since other threads may still be calling the callback after it's reset
+        let logs: &'static Logs = Box::leak(Box::default());
+        Beatstar_SetLogCallback(Some(collect), logs as *const Logs as *mut c_void);
+
+        ffi_guard(|| {
+            event!(Level::INFO, count = 3, "log_callback info");
+            event!(Level::DEBUG, "log_callback debug");
+        });
+
+        // Events outside the FFI calls reach the callback too, from any thread
+        std::thread::spawn(|| event!(Level::WARN, "log_callback thread"))
+            .join()
+            .unwrap();
+
+        Beatstar_SetLogLevel(BeatStarLogLevel::Warn);
+        ffi_guard(|| event!(Level::INFO, "log_callback filtered"));
+        Beatstar_SetLogLevel(BeatStarLogLevel::Info);
+
+        Beatstar_SetLogCallback(None, std::ptr::null_mut());
+        ffi_guard(|| event!(Level::INFO, "log_callback after reset"));
+
+        let target = "song_data_core_rust::beatstar::tests".to_string();
+        assert_eq!(
+            *logs.lock().unwrap(),
+            vec![
+                (
+                    BeatStarLogLevel::Info,
+                    target.clone(),
+                    "log_callback info count=3".to_string()
+                ),
+                (BeatStarLogLevel::Warn, target, "log_callback thread".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn ordered_iteration() {
+        use crate::beatstar::data::BeatStarCharacteristics;
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::model::beatstar_parse_songs;
+
+        let db = test_database();
+        let hashes: Vec<String> = db.songs().map(|song| song.hash.to_string()).collect();
+        assert_eq!(
+            hashes,
+            vec![
+                "AAAA000000000000000000000000000000000001",
+                "BBBB000000000000000000000000000000000002"
+            ]
+        );
+
+        let iter = Beatstar_SongIterNew(Some(&db));
+        let song = unsafe { &*Beatstar_SongIterNext(iter.as_mut()) };
+        assert_eq!(song.hash.to_string(), hashes[0]);
+        assert!(!Beatstar_SongIterNext(unsafe { iter.as_mut() }).is_null());
+        assert!(Beatstar_SongIterNext(unsafe { iter.as_mut() }).is_null());
+        unsafe { Beatstar_SongIterFree(iter) };
+
+        // walks every characteristic of every song, custom ones included
+        let walk = |song: &BeatStarSong| {
+            let mut walked = vec![];
+            let chars = Beatstar_CharacteristicIterNew(Some(song));
+            while let Some(char) = unsafe { Beatstar_CharacteristicIterNext(chars.as_mut()).as_ref() } {
+                let diffs = Beatstar_DifficultyIterNew(Some(char));
+                while let Some(diff) = unsafe { Beatstar_DifficultyIterNext(diffs.as_mut()).as_ref() } {
+                    walked.push((char.kind, char.name.to_string(), diff.diff.to_string()));
+                }
+                unsafe { Beatstar_DifficultyIterFree(diffs) };
+            }
+            unsafe { Beatstar_CharacteristicIterFree(chars) };
+            walked
+        };
+
+        let standard = |diff: &str| (BeatStarCharacteristics::Standard, "Standard".to_string(), diff.to_string());
+        assert_eq!(
+            walk(song),
+            vec![
+                standard("Expert"),
+                standard("ExpertPlus"),
+                (BeatStarCharacteristics::OneSaber, "OneSaber".to_string(), "Hard".to_string())
+            ]
+        );
+        assert_eq!(
+            walk(db.song_at(1).unwrap()),
+            vec![
+                standard("Normal"),
+                (BeatStarCharacteristics::Custom, "Ghost".to_string(), "Expert".to_string())
+            ]
+        );
+
+        // unparsable difficulties are all Unknown, their raw names keep the order stable
+        let odd = beatstar_parse_songs(
+            &TEST_SONGS_JSON
+                .replace("\"Diff\": \"Expert\", \"Stars\": 6.5", "\"Diff\": \"Zeta\", \"Stars\": 6.5")
+                .replace("\"Diff\": \"ExpertPlus\"", "\"Diff\": \"Alpha\""),
+        )
+        .unwrap();
+        let names: Vec<String> = walk(&BeatStarSong::from(&odd[0]))
+            .into_iter()
+            .map(|(_, _, diff)| diff)
+            .collect();
+        assert_eq!(names, vec!["Alpha", "Zeta", "Hard"]);
+    }
+
+    #[test]
+    fn level_hash_and_scan() {
+        use crate::beatstar::error::*;
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::level_hash::beatstar_hash_level_folder;
+        use sha1::{Digest, Sha1};
+        use std::ffi::{CStr, CString};
+        use std::fs;
+
+        let levels = env::temp_dir().join(format!("songdatacore_levels_{0}", std::process::id()));
+        let _ = fs::remove_dir_all(&levels);
+        let level = levels.join("1a2b (Test Song - Mapper)");
+        fs::create_dir_all(&level).unwrap();
+        fs::create_dir_all(levels.join("broken")).unwrap();
+
+        // Lowercase file names and a BOM, as some editors write them
+        let info = "\u{FEFF}{\"_difficultyBeatmapSets\": [{\"_beatmapCharacteristicName\": \"Standard\", \"_difficultyBeatmaps\": [\
+            {\"_difficulty\": \"Expert\", \"_beatmapFilename\": \"ExpertStandard.dat\"}, \
+            {\"_difficulty\": \"Hard\", \"_beatmapFilename\": \"HardStandard.dat\"}]}]}";
+        fs::write(level.join("info.dat"), info).unwrap();
+        fs::write(level.join("expertstandard.dat"), "expert").unwrap();
+        fs::write(level.join("HardStandard.dat"), "hard").unwrap();
+
+        let expected: String = Sha1::new()
+            .chain_update(info)
+            .chain_update("expert")
+            .chain_update("hard")
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        assert_eq!(beatstar_hash_level_folder(&level).unwrap(), expected);
+
+        let c_level = CString::new(level.to_str().unwrap()).unwrap();
+        let hash = unsafe { Beatstar_HashLevelFolder(c_level.as_ptr()) };
+        assert_eq!(unsafe { CStr::from_ptr(hash) }.to_str().unwrap(), expected);
+        unsafe { Beatstar_StringFree(hash) };
+
+        let db: BeatStarDataFile = SongDatabase::from_songs(
+            beatstar_json_content(
+                &TEST_SONGS_JSON.replace("AAAA000000000000000000000000000000000001", &expected),
+            )
+            .unwrap(),
+        )
+        .into();
+
+        let c_levels = CString::new(levels.to_str().unwrap()).unwrap();
+        let scan = unsafe { BeatStarDataFile_ScanLevels(Some(&db), c_levels.as_ptr()) };
+        let scan_ref = unsafe { scan.as_ref() };
+        assert_eq!(BeatStarLevelScan_MatchedLen(scan_ref), 1);
+        let song = unsafe { &*BeatStarLevelScan_MatchedSong(scan_ref, 0) };
+        assert_eq!(song.song_name.to_string(), "Test Song");
+        let folder = unsafe { CStr::from_ptr(BeatStarLevelScan_MatchedFolder(scan_ref, 0)) };
+        assert_eq!(folder.to_str().unwrap(), level.to_str().unwrap());
+        assert_eq!(BeatStarLevelScan_UnmatchedLen(scan_ref), 1);
+        assert!(BeatStarLevelScan_UnmatchedFolder(scan_ref, 1).is_null());
+        unsafe { BeatStarLevelScan_Free(scan) };
+
+        // SongCore skips missing beatmaps instead of failing the level
+        fs::remove_file(level.join("HardStandard.dat")).unwrap();
+        let expected: String = Sha1::new()
+            .chain_update(info)
+            .chain_update("expert")
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        assert_eq!(beatstar_hash_level_folder(&level).unwrap(), expected);
+
+        fs::write(
+            level.join("info.dat"),
+            "{\"version\": \"4.0.0\", \"difficultyBeatmaps\": []}",
+        )
+        .unwrap();
+        let err = beatstar_hash_level_folder(&level).unwrap_err();
+        assert_eq!(error_code(&err), BeatStarErrorCode::Unsupported);
+
+        fs::remove_dir_all(&levels).unwrap();
+    }
+
+    #[test]
+    fn local_song_overlay() {
+        use crate::beatstar::data::{BeatStarCharacteristics, BeatStarSongSource, SongDiffs};
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::model::Characteristic;
+        use crate::beatstar::info_dat::beatstar_local_song;
+        use crate::beatstar::level_hash::beatstar_hash_level_folder;
+        use crate::beatstar::overlay::{BeatStarOverlay, DifficultyOverride, SongOverride};
+        use std::fs;
+
+        let levels = env::temp_dir().join(format!("songdatacore_local_{0}", std::process::id()));
+        let _ = fs::remove_dir_all(&levels);
+        let level = levels.join("new map");
+        fs::create_dir_all(&level).unwrap();
+
+        fs::write(
+            level.join("Info.dat"),
+            r#"{
+                "_songName": "New Song", "_songSubName": "Extended", "_songAuthorName": "Band",
+                "_levelAuthorName": "Mapper", "_beatsPerMinute": 150,
+                "_difficultyBeatmapSets": [{
+                    "_beatmapCharacteristicName": "Standard",
+                    "_difficultyBeatmaps": [
+                        {"_difficulty": "Hard", "_beatmapFilename": "Hard.dat",
+                         "_noteJumpMovementSpeed": 14, "_noteJumpStartBeatOffset": 0.5},
+                        {"_difficulty": "ExpertPlus", "_beatmapFilename": "ExpertPlus.dat",
+                         "_noteJumpMovementSpeed": 19, "_noteJumpStartBeatOffset": -0.5,
+                         "_customData": {"_requirements": ["Mapping Extensions"]}}
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        // v2, where bombs are notes of type 3
+        fs::write(
+            level.join("Hard.dat"),
+            r#"{"_version": "2.2.0", "_notes": [{"_type": 0}, {"_type": 1}, {"_type": 3}], "_obstacles": [{}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            level.join("ExpertPlus.dat"),
+            r#"{"version": "3.2.0", "colorNotes": [{}, {}, {}, {}], "bombNotes": [{}, {}], "obstacles": []}"#,
+        )
+        .unwrap();
+
+        let song = beatstar_local_song(&level).unwrap();
+        let hash = beatstar_hash_level_folder(&level).unwrap();
+        assert_eq!(song.hash.to_string(), hash);
+        assert_eq!(song.source, BeatStarSongSource::Local);
+        assert_eq!(song.song_name.to_string(), "New Song");
+        assert_eq!(song.song_sub_name.to_string(), "Extended");
+        assert_eq!(song.song_author_name.to_string(), "Band");
+        assert_eq!(song.level_author_name.to_string(), "Mapper");
+        assert_eq!(song.bpm, 150.0);
+
+        let hard = song.get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::Hard).unwrap();
+        assert_eq!((hard.notes, hard.bombs, hard.obstacles), (2, 1, 1));
+        assert_eq!((hard.njs, hard.njs_offset), (14.0, 0.5));
+        assert!(hard.requirements.is_empty());
+
+        let expert_plus = song
+            .get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::ExpertPlus)
+            .unwrap();
+        assert_eq!((expert_plus.notes, expert_plus.bombs, expert_plus.obstacles), (4, 2, 0));
+        assert_eq!((expert_plus.njs, expert_plus.njs_offset), (19.0, -0.5));
+        assert_eq!(expert_plus.requirements[0].to_string(), "Mapping Extensions");
+        assert!(expert_plus.jump_info.jump_distance > 0.0);
+
+        let db = test_database();
+        assert!(db.get_song(&hash).is_none());
+
+        let mut overlay = BeatStarOverlay::default();
+        assert_eq!(overlay.load_levels(&levels).unwrap(), 1);
+        let found = db.database().get_song_with_overlay(&overlay, &hash).unwrap();
+        assert_eq!(found.song_name, "New Song");
+        assert_eq!(found.source, BeatStarSongSource::Local);
+
+        let c_hash = std::ffi::CString::new(hash.as_str()).unwrap();
+        let found = unsafe {
+            BeatStarDataFile_GetSongWithOverlay(Some(&db), Some(&overlay), c_hash.as_ptr())
+        };
+        assert_eq!(BeatStarSong_Source(unsafe { found.as_ref() }), BeatStarSongSource::Local);
+
+        // Scraped songs still come from the database
+        let scraped = db
+            .database()
+            .get_song_with_overlay(&overlay, "AAAA000000000000000000000000000000000001")
+            .unwrap();
+        assert_eq!(scraped.source, BeatStarSongSource::Scraped);
+
+        // Merging keeps the fields the new entry doesn't set, 0 included, and matches
+        // difficulties on their parsed names
+        overlay.insert(SongOverride {
+            hash: hash.clone(),
+            source: BeatStarSongSource::User,
+            song_name: Some("New Song (Fixed)".to_string()),
+            difficulties: vec![
+                DifficultyOverride {
+                    characteristic: Characteristic::Standard,
+                    difficulty: SongDiffs::Hard,
+                    name: "hard".to_string(),
+                    notes: Some(3),
+                    bombs: Some(0),
+                    ..Default::default()
+                },
+                DifficultyOverride {
+                    characteristic: Characteristic::Standard,
+                    difficulty: SongDiffs::ExpertPlus,
+                    name: "Expert+".to_string(),
+                    stars: Some(9.5),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        });
+
+        let merged = overlay.get(&hash).unwrap();
+        assert_eq!(merged.song_name, "New Song (Fixed)");
+        assert_eq!(merged.song_author_name, "Band");
+        assert_eq!(merged.source, BeatStarSongSource::Local);
+        assert_eq!(merged.difficulties.len(), 2);
+        let hard = merged.difficulty(&Characteristic::Standard, SongDiffs::Hard).unwrap();
+        assert_eq!((hard.notes, hard.bombs, hard.njs), (3, 0, 14.0));
+        let expert_plus = merged.difficulty(&Characteristic::Standard, SongDiffs::ExpertPlus).unwrap();
+        assert_eq!((expert_plus.name.as_str(), expert_plus.stars), ("ExpertPlus", 9.5));
+
+        fs::remove_dir_all(&levels).unwrap();
+    }
+
+    #[test]
+    fn layered_view() {
+        use crate::beatstar::error::*;
+        use crate::beatstar::bplist::BPList;
+        use crate::beatstar::data::{BeatStarCharacteristics, BeatStarSongSource, SongDiffs};
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::model::Characteristic;
+        use crate::beatstar::layers::BeatStarLayer;
+        use crate::beatstar::overlay::{DifficultyOverride, SongOverride};
+        use crate::beatstar::query::BeatStarDifficultyFilter;
+        use std::ffi::CString;
+
+        const BASE_HASH: &str = "AAAA000000000000000000000000000000000001";
+        let base = test_database();
+
+        let layers = Beatstar_LayersNew();
+        let local = unsafe { BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::Local).as_mut() }.unwrap();
+
+        // The local layer has a newer NJS for a scraped song and a song the base doesn't have
+        local.insert(SongOverride {
+            hash: BASE_HASH.to_string(),
+            song_name: Some("Local Name".to_string()),
+            source: BeatStarSongSource::Local,
+            difficulties: vec![DifficultyOverride {
+                characteristic: Characteristic::Standard,
+                name: "Expert".to_string(),
+                difficulty: SongDiffs::Expert,
+                njs: Some(19.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        local.insert(SongOverride {
+            hash: "CCCC000000000000000000000000000000000003".to_string(),
+            song_name: Some("Local Only".to_string()),
+            source: BeatStarSongSource::Local,
+            ..Default::default()
+        });
+
+        let user = unsafe { BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::User).as_mut() };
+        let c = |s: &str| CString::new(s).unwrap();
+        unsafe {
+            assert!(BeatStarOverlay_SetMetadata(
+                user,
+                c(BASE_HASH).as_ptr(),
+                c("User Name").as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+            ));
+            let user = BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::User).as_mut();
+            assert!(BeatStarOverlay_SetStars(
+                user,
+                c(BASE_HASH).as_ptr(),
+                c("Standard").as_ptr(),
+                c("Expert").as_ptr(),
+                7.0,
+            ));
+            // Parsed like the scraped names, 0 is a rating like any other
+            let user = BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::User).as_mut();
+            assert!(BeatStarOverlay_SetStars(
+                user,
+                c(BASE_HASH).as_ptr(),
+                c("standard").as_ptr(),
+                c("Expert+").as_ptr(),
+                0.0,
+            ));
+            let user = BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::User).as_mut();
+            assert!(!BeatStarOverlay_SetStars(
+                user,
+                c(BASE_HASH).as_ptr(),
+                c("Standard").as_ptr(),
+                c("Expert++").as_ptr(),
+                1.0,
+            ));
+            assert_eq!(Beatstar_LastError(), BeatStarErrorCode::InvalidArgument);
+            let user = BeatStarLayers_Layer(layers.as_mut(), BeatStarLayer::User).as_mut();
+            assert!(BeatStarOverlay_SetMetadata(
+                user,
+                c("CCCC000000000000000000000000000000000003").as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                c("Local Band").as_ptr(),
+                std::ptr::null(),
+            ));
+        }
+
+        let view_ptr = BeatStarLayers_View(unsafe { layers.as_ref() }, Some(&base));
+        let view = unsafe { view_ptr.as_ref() };
+
+        let song = unsafe { &*BeatStarLayeredDatabase_GetSong(view, c(BASE_HASH).as_ptr()) };
+        assert_eq!(song.song_name.to_string(), "User Name");
+        assert_eq!(song.song_author_name.to_string(), "Artist");
+        assert_eq!(song.source, BeatStarSongSource::Scraped);
+        assert_eq!(song.diffs.len(), 3);
+        let expert = song.get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::Expert).unwrap();
+        assert_eq!((expert.stars, expert.njs, expert.notes), (7.0, 19.0, 700));
+        assert!(expert.approximate_pp_value > 0.0);
+        let expert_plus = song
+            .get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::ExpertPlus)
+            .unwrap();
+        assert_eq!((expert_plus.stars, expert_plus.approximate_pp_value), (0.0, 0.0));
+
+        // Untouched songs are the base's own
+        let other = unsafe {
+            BeatStarLayeredDatabase_GetSong(view, c("BBBB000000000000000000000000000000000002").as_ptr())
+        };
+        assert!(std::ptr::eq(other, base.get_song("BBBB000000000000000000000000000000000002").unwrap()));
+
+        // A correction doesn't hide that the song comes from a local level
+        let local_only = unsafe {
+            &*BeatStarLayeredDatabase_GetSong(view, c("CCCC000000000000000000000000000000000003").as_ptr())
+        };
+        assert_eq!(local_only.source, BeatStarSongSource::Local);
+        assert_eq!(local_only.song_author_name.to_string(), "Local Band");
+
+        // Queries see the composed values
+        let filter = BeatStarDifficultyFilter {
+            min_stars: 6.9,
+            max_stars: 7.1,
+            ..Beatstar_DifficultyFilterDefault()
+        };
+        let list = BeatStarLayeredDatabase_QueryDifficulties(view, Some(&filter));
+        assert_eq!(BeatStarSongDifficultyList_Len(unsafe { list.as_ref() }), 1);
+        unsafe { BeatStarSongDifficultyList_Free(list) };
+
+        // So do the other lookups and queries: iteration, keys, the ranked feed and stats
+        assert_eq!(BeatStarLayeredDatabase_SongsLen(view), 3);
+        assert!(std::ptr::eq(BeatStarLayeredDatabase_SongAt(view, 2), local_only));
+        assert!(std::ptr::eq(
+            unsafe { BeatStarLayeredDatabase_GetSongById(view, c("1A2B").as_ptr()) },
+            song
+        ));
+        let ranked = BeatStarLayeredDatabase_RankedSince(view, 0);
+        assert!(unsafe { &*ranked }
+            .entries
+            .iter()
+            .any(|entry| unsafe { (*entry.diff).stars } == 7.0));
+        unsafe { BeatStarSongDifficultyList_Free(ranked) };
+        let stats = unsafe { std::ffi::CStr::from_ptr(BeatStarLayeredDatabase_StatsJson(view)) };
+        assert!(stats.to_str().unwrap().contains("\"song_count\":3"));
+
+        let merged = view.unwrap().view();
+        assert_eq!(merged.search_songs("user name", 10).len(), 1);
+        assert!(base.database().search_songs("user name", 10).is_empty());
+        assert_eq!(merged.mapper_songs("mapper").len(), 2);
+        assert_eq!(merged.get_song_by_key("1A2B").unwrap().song_name, "User Name");
+        let playlist = BPList::from_songs("Layers", merged.songs());
+        assert_eq!(merged.resolve_playlist(&playlist).summary.found, 3);
+        assert_eq!(base.database().resolve_playlist(&playlist).summary.found, 2);
+
+        // The base is untouched, and refreshing it keeps the layers
+        let base_song = base.database().get_song(BASE_HASH).unwrap();
+        assert_eq!(base_song.song_name, "Test Song");
+        let refreshed = SongDatabase::from_songs(
+            beatstar_json_content(&TEST_SONGS_JSON.replace("\"Upvotes\": 120", "\"Upvotes\": 150"))
+                .unwrap(),
+        );
+        let refreshed_view = unsafe { layers.as_ref() }.unwrap().view(&refreshed);
+        assert_eq!(refreshed_view.songs().count(), 3);
+        let song = refreshed_view.get_song(BASE_HASH).unwrap();
+        assert_eq!((song.song_name.as_str(), song.upvotes), ("User Name", 150));
+
+        unsafe {
+            BeatStarLayeredDatabase_Free(view_ptr);
+            Beatstar_LayersFree(layers);
+        }
+    }
+
+    #[test]
+    fn write_file_atomically() {
+        use crate::beatstar::database::{beatstar_temp_path, beatstar_write_file_atomically};
+
+        let dir = env::temp_dir();
+        let path = dir.join(format!("songdatacore_atomic_{0}.zip", std::process::id()));
+        beatstar_write_file_atomically(&path, b"old").unwrap();
+        beatstar_write_file_atomically(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!beatstar_temp_path(&path).exists());
+        std::fs::remove_file(&path).unwrap();
+
+        // Renaming over a directory fails, which must leave it and clean up the temp file
+        let occupied = dir.join(format!("songdatacore_atomic_{0}_dir", std::process::id()));
+        std::fs::create_dir_all(&occupied).unwrap();
+        assert!(beatstar_write_file_atomically(&occupied, b"new").is_err());
+        assert!(occupied.is_dir());
+        assert!(!beatstar_temp_path(&occupied).exists());
+        std::fs::remove_dir(&occupied).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_export() {
+        let db = test_database();
+        let db = db.database();
+        let dir = env::temp_dir();
+        let first = dir.join(format!("songdatacore_export_{0}_a.sqlite", std::process::id()));
+        let second = dir.join(format!("songdatacore_export_{0}_b.sqlite", std::process::id()));
+
+        assert_eq!(db.export_sqlite(&first).unwrap(), 0);
+        db.export_sqlite(&second).unwrap();
+        // Exporting over an existing file replaces it, without leaving the temp file behind
+        db.export_sqlite(&second).unwrap();
+        assert_eq!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+        assert!(!crate::beatstar::database::beatstar_temp_path(&second).exists());
+
+        // A failed export leaves what was at the path and cleans up the temp file
+        let occupied = dir.join(format!("songdatacore_export_{0}_dir", std::process::id()));
+        std::fs::create_dir_all(&occupied).unwrap();
+        assert!(db.export_sqlite(&occupied).is_err());
+        assert!(occupied.is_dir());
+        assert!(!crate::beatstar::database::beatstar_temp_path(&occupied).exists());
+        std::fs::remove_dir(&occupied).unwrap();
+
+        // Duplicate difficulties are reported and the first one kept
+        let duplicated = SongDatabase::from_songs(
+            beatstar_json_content(&TEST_SONGS_JSON.replace(
+                "\"Diff\": \"Normal\", \"Stars\": 2.0",
+                "\"Diff\": \"Expert\", \"Stars\": 2.0",
+            ).replace("\"Char\": \"Ghost\"", "\"Char\": \"Standard\""))
+            .unwrap(),
+        );
+        assert_eq!(duplicated.export_sqlite(&second).unwrap(), 1);
+        let conn = rusqlite::Connection::open(&second).unwrap();
+        let stars: f64 = conn
+            .query_row(
+                "SELECT stars FROM difficulties WHERE song_hash LIKE 'BBBB%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stars, 2.0);
+        drop(conn);
+
+        let conn = rusqlite::Connection::open(&first).unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM songs"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM difficulties"), 5);
+        assert_eq!(count("SELECT COUNT(*) FROM characteristics WHERE kind = 'Custom'"), 1);
+
+        let (song_name, stars, pp, ranked_time): (String, f64, f64, i64) = conn
+            .query_row(
+                "SELECT s.song_name, d.stars, d.pp, d.ranked_update_unix_time \
+                 FROM difficulties d \
+                 JOIN songs s ON s.hash = d.song_hash \
+                 JOIN difficulty_requirements r USING (song_hash, characteristic, difficulty) \
+                 WHERE r.requirement = 'Noodle Extensions'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(song_name, "Test Song");
+        assert_eq!(stars, 8.25);
+        assert!(pp > 0.0);
+        assert_eq!(ranked_time, parse_unix_time("2021-06-08T12:00:00Z").unwrap());
+
+        drop(conn);
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn sqlite_export_unsupported() {
+        use crate::beatstar::error::BeatStarErrorCode;
+        use crate::beatstar::ffi::*;
+        use std::ffi::CString;
+
+        let db = test_database();
+        let path = CString::new("unused.sqlite").unwrap();
+        assert!(!unsafe { BeatStarDataFile_ExportSqlite(Some(&db), path.as_ptr()) });
+        assert_eq!(Beatstar_LastError(), BeatStarErrorCode::Unsupported);
+    }
+
+    #[test]
+    fn export_formats() {
+        use crate::beatstar::bplist::BPList;
+        use crate::beatstar::export::{beatstar_write_csv, beatstar_write_json};
+        use crate::beatstar::query::BeatStarDifficultyFilter;
+
+        let db = test_database();
+        let db = db.database();
+        let ranked = db.query_difficulties(&BeatStarDifficultyFilter {
+            ranked_only: true,
+            ..Default::default()
+        });
+
+        let mut csv = Vec::new();
+        beatstar_write_csv(&ranked, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), ranked.len() + 1);
+        assert!(lines[0].starts_with("hash,key,song_name"));
+        assert!(lines.iter().any(|line| line.ends_with(",Noodle Extensions")));
+
+        // The exported JSON loads back like the scraped data
+        let songs: Vec<&model::Song> = db.songs().iter().collect();
+        let mut json = Vec::new();
+        beatstar_write_json(&songs, &mut json).unwrap();
+        let reloaded = beatstar_json_content(std::str::from_utf8(&json).unwrap()).unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(&reloaded[0], songs[0]);
+
+        // Each song is listed once with its matching difficulties
+        let playlist = BPList::from_difficulties("Ranked", &ranked);
+        assert_eq!(playlist.songs.len(), 2);
+        let difficulties: usize = playlist.songs.iter().map(|s| s.difficulties.len()).sum();
+        assert_eq!(difficulties, ranked.len());
+
+        let text = serde_json::to_string(&playlist).unwrap();
+        assert!(text.contains("\"playlistTitle\":\"Ranked\""));
+        assert_eq!(serde_json::from_str::<BPList>(&text).unwrap(), playlist);
+    }
+
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn http_server() {
+        use crate::beatstar::server::{BeatStarServer, BeatStarServerConfig};
+        use std::sync::Arc;
+
+        let path = write_test_zip("server", TEST_SONGS_JSON);
+
+        let config = |addr: &str| BeatStarServerConfig {
+            addr: addr.to_string(),
+            db_path: path.to_str().unwrap().to_string(),
+            admin_token: Some("secret".to_string()),
+        };
+        assert!(BeatStarServer::bind(config("0.0.0.0:0")).is_err());
+
+        let server = Arc::new(BeatStarServer::bind(config("127.0.0.1:0")).unwrap());
+        let base = format!("http://{0}", server.local_addr().unwrap());
+        let thread = std::thread::spawn({
+            let server = server.clone();
+            move || server.run()
+        });
+
+        let get = |endpoint: &str| match ureq::get(&format!("{base}{endpoint}")).call() {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => panic!("{e}"),
+        };
+        let json = |response: ureq::Response| response.into_json::<serde_json::Value>().unwrap();
+
+        let song = get("/songs/1A2B");
+        assert_eq!(song.status(), 200);
+        assert!(song.header("Cache-Control").unwrap().contains("max-age"));
+        let etag = song.header("ETag").unwrap().to_string();
+        assert_eq!(json(song)["hash"], "AAAA000000000000000000000000000000000001");
+
+        let cached = ureq::get(&format!("{base}/songs/1a2b"))
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(cached.status(), 304);
+
+        let found = json(get("/search?q=other%20SONG"));
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(found[0]["song_name"], "Other Song");
+
+        let hard = json(get("/query?ranked=true&min_stars=7"));
+        assert_eq!(hard.as_array().unwrap().len(), 1);
+        assert_eq!(hard[0]["difficulty"]["name"], "ExpertPlus");
+
+        assert_eq!(json(get("/mappers/mapper"))["song_count"], 2);
+        assert_eq!(json(get("/stats"))["song_count"], 2);
+        assert_eq!(get("/songs/missing").status(), 404);
+        assert_eq!(get("/query?min_stars=lots").status(), 400);
+        assert_eq!(get("/nothing").status(), 404);
+
+        // Refreshing picks up the new file and changes the ETag
+        write_test_zip("server", &TEST_SONGS_JSON.replace("\"Upvotes\": 120", "\"Upvotes\": 150"));
+        let refresh = |token: &str| {
+            match ureq::post(&format!("{base}/admin/refresh"))
+                .set("X-Admin-Token", token)
+                .call()
+            {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(e) => panic!("{e}"),
+            }
+        };
+        assert_eq!(refresh("wrong").status(), 401);
+        assert_eq!(json(refresh("secret"))["generation"], 2);
+
+        let song = get("/songs/1a2b");
+        assert_ne!(song.header("ETag").unwrap(), etag);
+        assert_eq!(json(song)["upvotes"], 150);
+
+        // A file that doesn't parse keeps the current data and generation
+        write_test_zip("server", "not json");
+        assert_eq!(refresh("secret").status(), 500);
+        assert_eq!(json(get("/info"))["generation"], 2);
+        assert_eq!(json(get("/songs/1a2b"))["upvotes"], 150);
+        write_test_zip("server", TEST_SONGS_JSON);
+        assert_eq!(json(refresh("secret"))["generation"], 3);
+
+        server.stop();
+        thread.join().unwrap();
+
+        // Without a token the admin endpoint is disabled
+        let server = Arc::new(
+            BeatStarServer::bind(BeatStarServerConfig {
+                admin_token: None,
+                ..config("127.0.0.1:0")
+            })
+            .unwrap(),
+        );
+        let addr = server.local_addr().unwrap();
+        let thread = std::thread::spawn({
+            let server = server.clone();
+            move || server.run()
+        });
+        match ureq::post(&format!("http://{addr}/admin/refresh")).call() {
+            Err(ureq::Error::Status(status, _)) => assert_eq!(status, 403),
+            other => panic!("{other:?}"),
+        }
+
+        server.stop();
+        thread.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn snapshot_diff() {
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::snapshot_diff::beatstar_diff;
+        use std::ffi::{CStr, CString};
+
+        let old = test_database();
+        let mut changed = beatstar_json_content(
+            &TEST_SONGS_JSON
+                .replace("\"Upvotes\": 120", "\"Upvotes\": 150")
+                .replace("\"SongName\": \"Test Song\"", "\"SongName\": \"Test Song (Fixed)\"")
+                .replace("\"Stars\": 6.5", "\"Stars\": 6.8")
+                .replace(
+                    "\"Stars\": 8.25, \"Ranked\": true",
+                    "\"Stars\": 8.25, \"Ranked\": false",
+                )
+                .replace(
+                    "\"Diff\": \"Hard\", \"Stars\": 0, \"Ranked\": false",
+                    "\"Diff\": \"Hard\", \"Stars\": 3.0, \"Ranked\": true",
+                ),
+        )
+        .unwrap();
+        // The second song is replaced by a different song with a ranked difficulty
+        changed[1].hash = "CCCC000000000000000000000000000000000003".to_string();
+        changed[1].key = "ff02".to_string();
+        let new: BeatStarDataFile = SongDatabase::from_songs(changed.clone()).into();
+
+        let diff = beatstar_diff(old.database(), new.database());
+        assert_eq!(diff.added_songs, vec!["CCCC000000000000000000000000000000000003"]);
+        assert_eq!(diff.removed_songs, vec!["BBBB000000000000000000000000000000000002"]);
+
+        let names = |changes: &[crate::beatstar::snapshot_diff::DifficultyChange]| {
+            changes
+                .iter()
+                .map(|c| format!("{0}/{1}/{2}", &c.hash[..4], c.characteristic, c.difficulty))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&diff.newly_ranked),
+            ["AAAA/OneSaber/Hard", "CCCC/Standard/Normal"]
+        );
+        assert_eq!(
+            names(&diff.unranked),
+            ["AAAA/Standard/ExpertPlus", "BBBB/Standard/Normal"]
+        );
+        assert_eq!(names(&diff.star_changes), ["AAAA/Standard/Expert"]);
+        assert_eq!((diff.star_changes[0].old_stars, diff.star_changes[0].new_stars), (6.5, 6.8));
+
+        assert_eq!(diff.vote_swings.len(), 1);
+        assert_eq!(diff.vote_swings[0].swing(), 30);
+        assert!(diff.vote_swings[0].new_rating > diff.vote_swings[0].old_rating);
+
+        assert_eq!(diff.metadata_edits.len(), 1);
+        assert_eq!(diff.metadata_edits[0].field, "SongName");
+        assert_eq!(diff.metadata_edits[0].new_value, "Test Song (Fixed)");
+
+        assert!(beatstar_diff(new.database(), new.database()).is_empty());
+
+        // Through the C ABI, with both snapshots alive at once
+        let report = Beatstar_Diff(Some(&old), Some(&new));
+        let report_ref = unsafe { report.as_ref() };
+        assert_eq!(BeatStarSnapshotDiff_NewlyRankedLen(report_ref), 2);
+        assert_eq!(BeatStarSnapshotDiff_UnrankedLen(report_ref), 2);
+        let added = unsafe { BeatStarSnapshotDiff_AddedSongsGet(report_ref, 0).as_ref() }.unwrap();
+        assert_eq!(added.to_string(), diff.added_songs[0]);
+        let edit = unsafe { BeatStarSnapshotDiff_MetadataEditsGet(report_ref, 0).as_ref() }.unwrap();
+        assert_eq!(view_str(&edit.old_value), "Test Song");
+        assert!(BeatStarSnapshotDiff_VoteSwingsGet(report_ref, 1).is_null());
+
+        let json = unsafe { CStr::from_ptr(BeatStarSnapshotDiff_Json(report_ref)) };
+        let json: serde_json::Value = serde_json::from_slice(json.to_bytes()).unwrap();
+        assert_eq!(json["star_changes"][0]["difficulty"], "Expert");
+        unsafe { BeatStarSnapshotDiff_Free(report) };
+
+        // A re-upload of the second song is paired with the newest old upload of its key
+        let mut older = old.database().songs()[1].clone();
+        older.hash = "DDDD000000000000000000000000000000000004".to_string();
+        older.uploaded = crate::beatstar::model::parse_time("2020-01-01T00:00:00Z");
+        let mut old_songs = old.database().songs().to_vec();
+        old_songs.push(older);
+        let old_versions = SongDatabase::from_songs(old_songs);
+
+        changed[1].key = "FF01".to_string();
+        changed[1].upvotes += 20;
+        changed[1].difficulties[0].stars = 2.5;
+        let reuploaded = SongDatabase::from_songs(changed);
+
+        let diff = beatstar_diff(&old_versions, &reuploaded);
+        assert!(diff.added_songs.is_empty());
+        assert_eq!(diff.removed_songs, vec!["DDDD000000000000000000000000000000000004"]);
+        assert_eq!(diff.updated_songs.len(), 1);
+        assert_eq!(diff.updated_songs[0].key, "FF01");
+        assert_eq!(diff.updated_songs[0].old_hash, "BBBB000000000000000000000000000000000002");
+        assert_eq!(diff.updated_songs[0].new_hash, "CCCC000000000000000000000000000000000003");
+        assert_eq!(names(&diff.star_changes), ["AAAA/Standard/Expert", "CCCC/Standard/Normal"]);
+        assert_eq!(diff.vote_swings.iter().map(|s| s.swing()).collect::<Vec<_>>(), [30, 20]);
+        assert!(diff
+            .metadata_edits
+            .iter()
+            .any(|e| e.hash.starts_with("CCCC") && e.field == "Key" && e.new_value == "FF01"));
+
+        let report = BeatStarSnapshotDiff::new(&diff).unwrap();
+        let update = unsafe { BeatStarSnapshotDiff_UpdatedSongsGet(Some(&report), 0).as_ref() }.unwrap();
+        assert_eq!(view_str(&update.old_hash), diff.updated_songs[0].old_hash);
+
+        let missing = CString::new("/nonexistent/old.zip").unwrap();
+        assert!(unsafe { Beatstar_DatabaseOpen(missing.as_ptr()) }.is_null());
+        assert_ne!(Beatstar_LastError(), crate::beatstar::error::BeatStarErrorCode::Ok);
+    }
+
+
+    #[test]
+    fn resolve_playlist() {
+        use crate::beatstar::bplist::{BPList, BeatStarPlaylistEntryStatus};
+        use crate::beatstar::ffi::*;
+        use std::ffi::{CStr, CString};
+
+        const PLAYLIST: &str = r#"{
+            "playlistTitle": "Old favourites",
+            "playlistAuthor": "Someone",
+            "songs": [
+                {
+                    "hash": "aaaa000000000000000000000000000000000001",
+                    "difficulties": [
+                        { "characteristic": "Standard", "name": "expertPlus" },
+                        { "characteristic": "Standard", "name": "Easy" }
+                    ]
+                },
+                { "hash": "DDDD000000000000000000000000000000000004", "key": "FF01" },
+                { "hash": "EEEE000000000000000000000000000000000005", "key": "zzzz" },
+                { "hash": "FFFF000000000000000000000000000000000006" }
+            ]
+        }"#;
+
+        let db = test_database();
+        let path = env::temp_dir().join(format!("songdatacore_{0}.bplist", std::process::id()));
+        std::fs::write(&path, PLAYLIST).unwrap();
+
+        let playlist = BPList::read(&path).unwrap();
+        let resolved = db.database().resolve_playlist(&playlist);
+        let statuses: Vec<BeatStarPlaylistEntryStatus> =
+            resolved.entries.iter().map(|entry| entry.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                BeatStarPlaylistEntryStatus::Found,
+                BeatStarPlaylistEntryStatus::StaleHash,
+                BeatStarPlaylistEntryStatus::Missing,
+                BeatStarPlaylistEntryStatus::Missing,
+            ]
+        );
+        assert_eq!(
+            resolved.entries[1].song.unwrap().hash.to_string(),
+            "BBBB000000000000000000000000000000000002"
+        );
+        assert_eq!(resolved.summary.found, 1);
+        assert_eq!(resolved.summary.stale_hashes, 1);
+        assert_eq!(resolved.summary.missing, 2);
+        assert_eq!(resolved.summary.duration_secs, 180 + 95);
+
+        let difficulties = &resolved.entries[0].difficulties;
+        assert_eq!(difficulties[0].stats.unwrap().stars, 8.25);
+        assert!(difficulties[1].stats.is_none());
+
+        // Keys pick their newest upload, and empty keys match nothing
+        let mut songs = db.database().songs().to_vec();
+        let mut older = songs[1].clone();
+        older.hash = "CCCC000000000000000000000000000000000003".to_string();
+        older.uploaded = crate::beatstar::model::parse_time("2020-01-01T00:00:00Z");
+        let mut keyless = songs[0].clone();
+        keyless.hash = "9999000000000000000000000000000000000009".to_string();
+        keyless.key = String::new();
+        songs.extend([older, keyless]);
+        let uploads = SongDatabase::from_songs(songs);
+
+        let mut keyed = playlist.clone();
+        keyed.songs[2].key = Some(String::new());
+        let resolved_uploads = uploads.resolve_playlist(&keyed);
+        assert_eq!(
+            resolved_uploads.entries[1].song.unwrap().hash,
+            "BBBB000000000000000000000000000000000002"
+        );
+        assert_eq!(resolved_uploads.entries[2].status, BeatStarPlaylistEntryStatus::Missing);
+        assert_eq!(
+            uploads.get_song_by_key("ff01").unwrap().hash,
+            "BBBB000000000000000000000000000000000002"
+        );
+        assert_eq!(
+            uploads.get_song_by_id("FF01").unwrap().hash,
+            resolved_uploads.entries[1].song.unwrap().hash
+        );
+        assert!(uploads.get_song_by_key("").is_none());
+
+        // Through the C ABI
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let handle = unsafe { BeatStarDataFile_ResolvePlaylist(Some(&db), c_path.as_ptr()) };
+        let handle_ref = unsafe { handle.as_ref() };
+        let title = unsafe { CStr::from_ptr(BeatStarResolvedPlaylist_Title(handle_ref)) };
+        assert_eq!(title.to_str().unwrap(), "Old favourites");
+        assert_eq!(BeatStarResolvedPlaylist_Summary(handle_ref), resolved.summary);
+        assert_eq!(BeatStarResolvedPlaylist_EntriesLen(handle_ref), 4);
+        assert_eq!(BeatStarResolvedPlaylist_DifficultiesLen(handle_ref), 2);
+
+        let entry = unsafe { BeatStarResolvedPlaylist_EntriesGet(handle_ref, 2).as_ref() }.unwrap();
+        assert_eq!(entry.status, BeatStarPlaylistEntryStatus::Missing);
+        assert!(entry.song.is_null());
+        assert_eq!(view_str(&entry.hash), resolved.entries[2].hash);
+        let difficulty =
+            unsafe { BeatStarResolvedPlaylist_DifficultiesGet(handle_ref, 0).as_ref() }.unwrap();
+        assert_eq!(difficulty.entry_index, 0);
+        assert_eq!(view_str(&difficulty.name), resolved.entries[0].difficulties[0].name);
+        assert_eq!(BeatStarSongDifficultyStats_GetInfo(unsafe { difficulty.stats.as_ref() }).stars, 8.25);
+        unsafe { BeatStarResolvedPlaylist_Free(handle) };
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(unsafe { BeatStarDataFile_ResolvePlaylist(Some(&db), c_path.as_ptr()) }.is_null());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rust_model() {
+        use crate::beatstar::data::{BeatStarCharacteristics, SongDiffs};
+        use crate::beatstar::model::*;
+        use chrono::{TimeZone, Utc};
+
+        let songs = beatstar_parse_songs(TEST_SONGS_JSON).unwrap();
+        assert_eq!(songs.len(), 2);
+
+        let song = &songs[0];
+        assert_eq!(song.key, "1a2b");
+        assert_eq!(song.uploaded, Utc.with_ymd_and_hms(2021, 5, 1, 10, 0, 0).single());
+        assert!(song.is_ranked());
+        assert!(song.rating > songs[1].rating);
+
+        let expert_plus = song
+            .difficulty(&Characteristic::Standard, SongDiffs::ExpertPlus)
+            .unwrap();
+        assert!(expert_plus.approximate_pp > 0.0);
+        assert_eq!(expert_plus.requirements, vec!["Noodle Extensions".to_string()]);
+        // no timezone in the scraped value, read as UTC
+        assert_eq!(
+            expert_plus.ranked_update_time.unwrap().timestamp_millis(),
+            Utc.with_ymd_and_hms(2021, 6, 8, 12, 0, 0).unwrap().timestamp_millis() + 500
+        );
+        assert!(song
+            .difficulty(&Characteristic::OneSaber, SongDiffs::Hard)
+            .unwrap()
+            .ranked_update_time
+            .is_none());
+
+        let ghost = &songs[1].difficulties[1];
+        assert_eq!(ghost.characteristic, Characteristic::Custom("Ghost".to_string()));
+        assert_eq!(ghost.characteristic.kind(), BeatStarCharacteristics::Custom);
+        assert!(songs[1].difficulties[0].ranked_update_time.is_none());
+
+        let json = serde_json::to_value(song).unwrap();
+        assert_eq!(json["uploaded"], "2021-05-01T10:00:00Z");
+        assert_eq!(json["difficulties"][2]["characteristic"], "OneSaber");
+
+        // the FFI representation is built from the model
+        let ffi_song = BeatStarSong::from(song);
+        assert_eq!(ffi_song.hash.to_string(), song.hash);
+        assert_eq!(ffi_song.uploaded.to_string(), "2021-05-01T10:00:00Z");
+        assert_eq!(ffi_song.uploaded_unix_time, song.uploaded.unwrap().timestamp());
+        let ffi_diff = ffi_song
+            .get_difficulty(BeatStarCharacteristics::Standard, SongDiffs::ExpertPlus)
+            .unwrap();
+        assert_eq!(ffi_diff.approximate_pp_value, expert_plus.approximate_pp);
+        assert_eq!(ffi_diff.jump_info, expert_plus.jump_info);
+        assert_eq!(
+            BeatStarSong::from(&songs[1])
+                .get_difficulty_by_name("Ghost", SongDiffs::Expert)
+                .unwrap()
+                .notes,
+            410
+        );
+
+        // unparsable names stay apart instead of all becoming "Unknown"
+        let odd = beatstar_parse_songs(
+            &TEST_SONGS_JSON
+                .replace("\"Diff\": \"Expert\", \"Stars\": 6.5", "\"Diff\": \"Insane\", \"Stars\": 6.5")
+                .replace("\"Diff\": \"ExpertPlus\"", "\"Diff\": \"Crazy\""),
+        )
+        .unwrap();
+        let names: Vec<&str> = odd[0].difficulties.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["Insane", "Crazy", "Hard"]);
+        assert_eq!(odd[0].difficulties[0].difficulty, SongDiffs::Unknown);
+        let ffi_odd = BeatStarSong::from(&odd[0]);
+        assert_eq!(ffi_odd.get_characteristic("Standard").unwrap().diffs.len(), 2);
+        assert_eq!(ffi_odd.diffs[1].diff.to_string(), "Crazy");
+    }
+
+    #[test]
+    fn song_database_instances() {
+        use crate::beatstar::error::*;
+        use crate::beatstar::ffi::*;
+        use crate::beatstar::model::beatstar_parse_songs;
+        use std::ffi::{CStr, CString};
+
+        let old_path = write_test_zip("instance_old", TEST_SONGS_JSON);
+        let new_path = write_test_zip(
+            "instance_new",
+            &TEST_SONGS_JSON.replace("\"SongName\": \"Test Song\"", "\"SongName\": \"Renamed\""),
+        );
+
+        let old = SongDatabase::open(&old_path).unwrap();
+        let new = SongDatabase::open(&new_path).unwrap();
+        assert_eq!(old.len(), 2);
+        let hash = "aaaa000000000000000000000000000000000001";
+        assert_eq!(old.get_song(hash).unwrap().song_name, "Test Song");
+        assert_eq!(new.get_song(hash).unwrap().song_name, "Renamed");
+        assert_eq!(new.get_song_by_id("ff01").unwrap().key, "ff01");
+        assert_eq!(old.search_songs("remix", 10).len(), 1);
+
+        let from_songs = SongDatabase::from_songs(beatstar_parse_songs(TEST_SONGS_JSON).unwrap());
+        assert_eq!(from_songs.songs(), old.songs());
+        assert_eq!(from_songs.characteristic_counts(), old.characteristic_counts());
+
+        let c_path = CString::new(new_path.to_str().unwrap()).unwrap();
+        let handle = unsafe { Beatstar_DatabaseOpen(c_path.as_ptr()) };
+        assert!(!handle.is_null());
+        let handle_ref = unsafe { handle.as_ref() };
+
+        let id = CString::new("1A2B").unwrap();
+        let song = unsafe { BeatStarDataFile_GetSongById(handle_ref, id.as_ptr()) };
+        assert!(!song.is_null());
+        let name = unsafe { CStr::from_ptr((*song).song_name.string_data) };
+        assert_eq!(name.to_str().unwrap(), "Renamed");
+
+        let upper = CString::new(hash.to_uppercase()).unwrap();
+        assert_eq!(unsafe { BeatStarDataFile_GetSong(handle_ref, upper.as_ptr()) }, song);
+        unsafe { Beatstar_DatabaseFree(handle) };
+
+        // the other instances are untouched by the freed handle
+        assert_eq!(old.get_song(hash).unwrap().song_name, "Test Song");
+
+        let missing = CString::new("/nonexistent/db.zip").unwrap();
+        assert!(unsafe { Beatstar_DatabaseOpen(missing.as_ptr()) }.is_null());
+        assert_eq!(Beatstar_LastError(), BeatStarErrorCode::Io);
+        assert!(unsafe { BeatStarDataFile_GetSongById(None, id.as_ptr()) }.is_null());
+        assert_eq!(Beatstar_LastError(), BeatStarErrorCode::InvalidArgument);
+
+        std::fs::remove_file(&old_path).unwrap();
+        std::fs::remove_file(&new_path).unwrap();
+    }
 }
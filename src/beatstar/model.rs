@@ -0,0 +1,357 @@
+//! The Rust-facing model of the scraped database: owned strings, enums and chrono timestamps.
+//! The `BeatStar*` FFI structs are built from it for C and C++.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::beatstar::data::{BeatStarCharacteristics, BeatStarSongSource, SongDiffs};
+use crate::beatstar::database::{
+    beatstar_zip_json, calculate_heatmap, calculate_pp, calculate_rating,
+};
+use crate::beatstar::jump::{beatstar_jump_info, default_njs, BeatStarJumpInfo};
+
+///
+/// The characteristic of a difficulty. Characteristics added by mods keep their raw name.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Characteristic {
+    Standard,
+    OneSaber,
+    NoArrows,
+    Lightshow,
+    Degree90,
+    Degree360,
+    Lawless,
+    #[default]
+    Unknown,
+    Custom(String),
+}
+
+impl Characteristic {
+    ///
+    /// The name the game uses, e.g. `90Degree`
+    ///
+    pub fn name(&self) -> &str {
+        match self {
+            Characteristic::Standard => "Standard",
+            Characteristic::OneSaber => "OneSaber",
+            Characteristic::NoArrows => "NoArrows",
+            Characteristic::Lightshow => "Lightshow",
+            Characteristic::Degree90 => "90Degree",
+            Characteristic::Degree360 => "360Degree",
+            Characteristic::Lawless => "Lawless",
+            Characteristic::Unknown => "Unknown",
+            Characteristic::Custom(name) => name,
+        }
+    }
+
+    ///
+    /// The FFI enum, `Custom` for characteristics added by mods
+    ///
+    pub fn kind(&self) -> BeatStarCharacteristics {
+        match self {
+            Characteristic::Standard => BeatStarCharacteristics::Standard,
+            Characteristic::OneSaber => BeatStarCharacteristics::OneSaber,
+            Characteristic::NoArrows => BeatStarCharacteristics::NoArrows,
+            Characteristic::Lightshow => BeatStarCharacteristics::Lightshow,
+            Characteristic::Degree90 => BeatStarCharacteristics::Degree90,
+            Characteristic::Degree360 => BeatStarCharacteristics::Degree360,
+            Characteristic::Lawless => BeatStarCharacteristics::Lawless,
+            Characteristic::Unknown => BeatStarCharacteristics::Unknown,
+            Characteristic::Custom(_) => BeatStarCharacteristics::Custom,
+        }
+    }
+}
+
+impl From<&str> for Characteristic {
+    fn from(name: &str) -> Self {
+        match BeatStarCharacteristics::from_str(name) {
+            Ok(BeatStarCharacteristics::Standard) => Characteristic::Standard,
+            Ok(BeatStarCharacteristics::OneSaber) => Characteristic::OneSaber,
+            Ok(BeatStarCharacteristics::NoArrows) => Characteristic::NoArrows,
+            Ok(BeatStarCharacteristics::Lightshow) => Characteristic::Lightshow,
+            Ok(BeatStarCharacteristics::Degree90) => Characteristic::Degree90,
+            Ok(BeatStarCharacteristics::Degree360) => Characteristic::Degree360,
+            Ok(BeatStarCharacteristics::Lawless) => Characteristic::Lawless,
+            Ok(BeatStarCharacteristics::Unknown) => Characteristic::Unknown,
+            Ok(BeatStarCharacteristics::Custom) | Err(_) => {
+                Characteristic::Custom(name.to_string())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Characteristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Characteristic {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+///
+/// A difficulty of a song with its derived values filled in
+///
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Difficulty {
+    pub characteristic: Characteristic,
+    /// As written in the data, `difficulty` is `Unknown` when this doesn't parse
+    pub name: String,
+    pub difficulty: SongDiffs,
+    pub stars: f32,
+    pub ranked: bool,
+    /// 0 unless ranked
+    pub approximate_pp: f32,
+    /// As set by the map, the jump info uses the game's default when it's 0
+    pub njs: f32,
+    pub njs_offset: f32,
+    pub notes: u32,
+    pub bombs: u32,
+    pub obstacles: u32,
+    pub jump_info: BeatStarJumpInfo,
+    pub ranked_update_time: Option<DateTime<Utc>>,
+    pub requirements: Vec<String>,
+}
+
+///
+/// A song of the database
+///
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Song {
+    /// Uppercase, as in the scraped data
+    pub hash: String,
+    pub key: String,
+    pub song_name: String,
+    pub song_sub_name: String,
+    pub song_author_name: String,
+    pub level_author_name: String,
+    pub bpm: f32,
+    pub duration_secs: u32,
+    pub upvotes: u32,
+    pub downvotes: u32,
+    pub uploaded: Option<DateTime<Utc>>,
+    pub heat: f32,
+    pub rating: f32,
+    pub source: BeatStarSongSource,
+    pub difficulties: Vec<Difficulty>,
+}
+
+impl Difficulty {
+    ///
+    /// A difficulty with the derived values (pp and jump info) calculated from the others
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        characteristic: Characteristic,
+        name: &str,
+        stars: f32,
+        ranked: bool,
+        njs: f32,
+        njs_offset: f32,
+        bpm: f32,
+        counts: (u32, u32, u32),
+    ) -> Difficulty {
+        let (notes, bombs, obstacles) = counts;
+
+        let mut difficulty = Difficulty {
+            characteristic,
+            name: name.to_string(),
+            difficulty: parse_difficulty(name),
+            stars,
+            ranked,
+            njs,
+            njs_offset,
+            notes,
+            bombs,
+            obstacles,
+            ..Default::default()
+        };
+        difficulty.update_derived(bpm);
+        difficulty
+    }
+
+    ///
+    /// Recalculates the pp and jump info, after the stars, NJS or the song's bpm changed
+    ///
+    pub(crate) fn update_derived(&mut self, bpm: f32) {
+        self.approximate_pp = calculate_pp(self.stars, self.ranked);
+        self.jump_info = beatstar_jump_info(bpm, self.effective_njs(), self.njs_offset);
+    }
+
+    ///
+    /// The NJS the game plays the difficulty at, maps without one use the difficulty's default
+    ///
+    pub fn effective_njs(&self) -> f32 {
+        if self.njs > 0.0 {
+            self.njs
+        } else {
+            default_njs(self.difficulty)
+        }
+    }
+}
+
+impl Song {
+    pub fn is_ranked(&self) -> bool {
+        self.difficulties.iter().any(|d| d.ranked)
+    }
+
+    pub fn difficulty(
+        &self,
+        characteristic: &Characteristic,
+        difficulty: SongDiffs,
+    ) -> Option<&Difficulty> {
+        self.difficulties
+            .iter()
+            .find(|d| d.characteristic == *characteristic && d.difficulty == difficulty)
+    }
+}
+
+///
+/// A difficulty as in the scraped JSON
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScrapedDifficulty {
+    diff: String,
+    #[serde(rename = "Char")]
+    characteristic: String,
+    #[serde(default)]
+    stars: f32,
+    #[serde(default)]
+    ranked: bool,
+    njs: f32,
+    njs_offset: f32,
+    bombs: u32,
+    notes: u32,
+    obstacles: u32,
+    #[serde(default)]
+    ranked_update_time: Option<String>,
+    requirements: Vec<String>,
+}
+
+///
+/// A song as in the scraped JSON
+///
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScrapedSong {
+    hash: String,
+    key: String,
+    song_name: String,
+    song_sub_name: String,
+    song_author_name: String,
+    level_author_name: String,
+    bpm: f32,
+    #[serde(rename = "Duration")]
+    duration_secs: u32,
+    upvotes: u32,
+    downvotes: u32,
+    uploaded: Option<String>,
+    diffs: Vec<ScrapedDifficulty>,
+}
+
+///
+/// Parses a scraped timestamp, tolerating empty values and a missing timezone (assumed UTC)
+///
+pub fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    let time = time.trim();
+    if time.is_empty() {
+        return None;
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(time) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .map(|date| Utc.from_utc_datetime(&date))
+}
+
+///
+/// Formats a timestamp like the scraped data, empty when there's none
+///
+pub fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        .unwrap_or_default()
+}
+
+pub(crate) fn parse_difficulty(name: &str) -> SongDiffs {
+    SongDiffs::from_str(name).unwrap_or(SongDiffs::Unknown)
+}
+
+impl From<ScrapedSong> for Song {
+    fn from(scraped: ScrapedSong) -> Self {
+        let bpm = scraped.bpm;
+        let uploaded = scraped.uploaded.as_deref().and_then(parse_time);
+
+        let difficulties = scraped
+            .diffs
+            .into_iter()
+            .map(|diff| Difficulty {
+                ranked_update_time: diff.ranked_update_time.as_deref().and_then(parse_time),
+                requirements: diff.requirements,
+                ..Difficulty::new(
+                    Characteristic::from(diff.characteristic.as_str()),
+                    &diff.diff,
+                    diff.stars,
+                    diff.ranked,
+                    diff.njs,
+                    diff.njs_offset,
+                    bpm,
+                    (diff.notes, diff.bombs, diff.obstacles),
+                )
+            })
+            .collect();
+
+        Song {
+            heat: uploaded.map_or(0.0, |date| {
+                calculate_heatmap(scraped.upvotes, scraped.downvotes, date.timestamp())
+            }),
+            rating: calculate_rating(scraped.upvotes, scraped.downvotes),
+            hash: scraped.hash,
+            key: scraped.key,
+            song_name: scraped.song_name,
+            song_sub_name: scraped.song_sub_name,
+            song_author_name: scraped.song_author_name,
+            level_author_name: scraped.level_author_name,
+            bpm,
+            duration_secs: scraped.duration_secs,
+            upvotes: scraped.upvotes,
+            downvotes: scraped.downvotes,
+            uploaded,
+            source: BeatStarSongSource::Scraped,
+            difficulties,
+        }
+    }
+}
+
+///
+/// Parses the scraped JSON array into songs with their derived values
+///
+pub fn beatstar_parse_songs(json: &str) -> anyhow::Result<Vec<Song>> {
+    let scraped: Vec<ScrapedSong> = serde_json::from_str(json)?;
+
+    Ok(scraped.into_iter().map(Song::from).collect())
+}
+
+///
+/// Reads the songs of a database zip, as downloaded by `beatstar_fetch_database_zip`
+///
+pub fn beatstar_read_songs_zip(bytes: &[u8]) -> anyhow::Result<Vec<Song>> {
+    beatstar_parse_songs(&beatstar_zip_json(bytes)?)
+}
+
+///
+/// Reads the songs of a database zip file
+///
+pub fn beatstar_read_songs_file(path: impl AsRef<Path>) -> anyhow::Result<Vec<Song>> {
+    beatstar_read_songs_zip(&std::fs::read(path)?)
+}
@@ -0,0 +1,403 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use tracing::{event, Level};
+
+use crate::beatstar::data::{BeatStarSongSource, SongDiffs};
+use crate::beatstar::database::{calculate_heatmap, calculate_rating};
+use crate::beatstar::ffi::{BeatStarDataFile, BeatStarSong};
+use crate::beatstar::info_dat::beatstar_read_local_song;
+use crate::beatstar::level_hash::level_folders;
+use crate::beatstar::model::{Characteristic, Difficulty, Song};
+use crate::beatstar::song_database::SongDatabase;
+
+///
+/// The fields of a difficulty a layer sets, `None` falls through to the lower layers
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DifficultyOverride {
+    pub characteristic: Characteristic,
+    pub difficulty: SongDiffs,
+    /// The raw name, for difficulties the lower layers don't have and to tell `Unknown` ones apart
+    pub name: String,
+    pub stars: Option<f32>,
+    pub ranked: Option<bool>,
+    pub njs: Option<f32>,
+    pub njs_offset: Option<f32>,
+    pub notes: Option<u32>,
+    pub bombs: Option<u32>,
+    pub obstacles: Option<u32>,
+    pub ranked_update_time: Option<DateTime<Utc>>,
+    pub requirements: Option<Vec<String>>,
+}
+
+///
+/// The fields of a song a layer sets, `None` falls through to the lower layers
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongOverride {
+    pub hash: String,
+    /// Where the entry comes from, the most complete source of a merge wins
+    pub source: BeatStarSongSource,
+    pub key: Option<String>,
+    pub song_name: Option<String>,
+    pub song_sub_name: Option<String>,
+    pub song_author_name: Option<String>,
+    pub level_author_name: Option<String>,
+    pub bpm: Option<f32>,
+    pub duration_secs: Option<u32>,
+    pub upvotes: Option<u32>,
+    pub downvotes: Option<u32>,
+    pub uploaded: Option<DateTime<Utc>>,
+    pub difficulties: Vec<DifficultyOverride>,
+}
+
+fn merge_option<T>(into: &mut Option<T>, from: Option<T>) {
+    if from.is_some() {
+        *into = from;
+    }
+}
+
+fn apply_option<T: Clone>(into: &mut T, from: &Option<T>) {
+    if let Some(value) = from {
+        *into = value.clone();
+    }
+}
+
+impl DifficultyOverride {
+    ///
+    /// Whether this sets fields of `diff`. Difficulties are matched on their parsed characteristic
+    /// and difficulty, so `Expert+` and `ExpertPlus` are the same; only `Unknown` ones compare names.
+    ///
+    pub fn is_for(&self, characteristic: &Characteristic, difficulty: SongDiffs, name: &str) -> bool {
+        self.characteristic == *characteristic
+            && self.difficulty == difficulty
+            && (difficulty != SongDiffs::Unknown || self.name == name)
+    }
+
+    ///
+    /// Merges a later entry of the same difficulty, its set fields win
+    ///
+    pub fn merge(&mut self, other: DifficultyOverride) {
+        merge_option(&mut self.stars, other.stars);
+        merge_option(&mut self.ranked, other.ranked);
+        merge_option(&mut self.njs, other.njs);
+        merge_option(&mut self.njs_offset, other.njs_offset);
+        merge_option(&mut self.notes, other.notes);
+        merge_option(&mut self.bombs, other.bombs);
+        merge_option(&mut self.obstacles, other.obstacles);
+        merge_option(&mut self.ranked_update_time, other.ranked_update_time);
+        merge_option(&mut self.requirements, other.requirements);
+    }
+
+    ///
+    /// Sets the fields this has on a difficulty of a lower layer
+    ///
+    pub fn apply(&self, diff: &mut Difficulty) {
+        apply_option(&mut diff.stars, &self.stars);
+        apply_option(&mut diff.ranked, &self.ranked);
+        apply_option(&mut diff.njs, &self.njs);
+        apply_option(&mut diff.njs_offset, &self.njs_offset);
+        apply_option(&mut diff.notes, &self.notes);
+        apply_option(&mut diff.bombs, &self.bombs);
+        apply_option(&mut diff.obstacles, &self.obstacles);
+        if self.ranked_update_time.is_some() {
+            diff.ranked_update_time = self.ranked_update_time;
+        }
+        apply_option(&mut diff.requirements, &self.requirements);
+    }
+}
+
+impl SongOverride {
+    ///
+    /// The fields a level folder knows: the names, bpm and the difficulties' NJS,
+    /// offsets, object counts and requirements. Votes and ratings are left to the scraped data.
+    ///
+    pub fn local(song: Song) -> SongOverride {
+        SongOverride {
+            hash: song.hash,
+            source: BeatStarSongSource::Local,
+            song_name: Some(song.song_name),
+            song_sub_name: Some(song.song_sub_name),
+            song_author_name: Some(song.song_author_name),
+            level_author_name: Some(song.level_author_name),
+            bpm: Some(song.bpm),
+            difficulties: song
+                .difficulties
+                .into_iter()
+                .map(|diff| DifficultyOverride {
+                    characteristic: diff.characteristic,
+                    difficulty: diff.difficulty,
+                    name: diff.name,
+                    njs: Some(diff.njs),
+                    njs_offset: Some(diff.njs_offset),
+                    notes: Some(diff.notes),
+                    bombs: Some(diff.bombs),
+                    obstacles: Some(diff.obstacles),
+                    requirements: Some(diff.requirements),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// An empty correction of the user, only holding the hash
+    ///
+    fn correction(hash: &str) -> SongOverride {
+        SongOverride {
+            hash: hash.to_ascii_uppercase(),
+            source: BeatStarSongSource::User,
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Merges a later entry of the same song, its set fields win.
+    /// The source is the most complete of the two: scraped, then local, then the user's.
+    ///
+    pub fn merge(&mut self, other: SongOverride) {
+        self.source = self.source.min(other.source);
+        merge_option(&mut self.key, other.key);
+        merge_option(&mut self.song_name, other.song_name);
+        merge_option(&mut self.song_sub_name, other.song_sub_name);
+        merge_option(&mut self.song_author_name, other.song_author_name);
+        merge_option(&mut self.level_author_name, other.level_author_name);
+        merge_option(&mut self.bpm, other.bpm);
+        merge_option(&mut self.duration_secs, other.duration_secs);
+        merge_option(&mut self.upvotes, other.upvotes);
+        merge_option(&mut self.downvotes, other.downvotes);
+        merge_option(&mut self.uploaded, other.uploaded);
+
+        for diff in other.difficulties {
+            match self
+                .difficulties
+                .iter_mut()
+                .find(|d| d.is_for(&diff.characteristic, diff.difficulty, &diff.name))
+            {
+                Some(existing) => existing.merge(diff),
+                None => self.difficulties.push(diff),
+            }
+        }
+    }
+
+    ///
+    /// Sets the fields this has on the same song of a lower layer, then updates the derived values
+    ///
+    pub fn apply(&self, song: &mut Song) {
+        song.source = song.source.min(self.source);
+        apply_option(&mut song.key, &self.key);
+        apply_option(&mut song.song_name, &self.song_name);
+        apply_option(&mut song.song_sub_name, &self.song_sub_name);
+        apply_option(&mut song.song_author_name, &self.song_author_name);
+        apply_option(&mut song.level_author_name, &self.level_author_name);
+        apply_option(&mut song.bpm, &self.bpm);
+        apply_option(&mut song.duration_secs, &self.duration_secs);
+        apply_option(&mut song.upvotes, &self.upvotes);
+        apply_option(&mut song.downvotes, &self.downvotes);
+        if self.uploaded.is_some() {
+            song.uploaded = self.uploaded;
+        }
+
+        if self.upvotes.is_some() || self.downvotes.is_some() || self.uploaded.is_some() {
+            song.rating = calculate_rating(song.upvotes, song.downvotes);
+            song.heat = song.uploaded.map_or(0.0, |date| {
+                calculate_heatmap(song.upvotes, song.downvotes, date.timestamp())
+            });
+        }
+
+        for diff_override in &self.difficulties {
+            match song.difficulties.iter_mut().find(|d| {
+                diff_override.is_for(&d.characteristic, d.difficulty, &d.name)
+            }) {
+                Some(diff) => diff_override.apply(diff),
+                None => {
+                    let mut diff = Difficulty {
+                        characteristic: diff_override.characteristic.clone(),
+                        name: diff_override.name.clone(),
+                        difficulty: diff_override.difficulty,
+                        ..Default::default()
+                    };
+                    diff_override.apply(&mut diff);
+                    song.difficulties.push(diff);
+                }
+            }
+        }
+
+        for diff in &mut song.difficulties {
+            diff.update_derived(song.bpm);
+        }
+    }
+
+    ///
+    /// The song as only this entry knows it, for songs the lower layers don't have
+    ///
+    pub fn to_song(&self) -> Song {
+        let mut song = Song {
+            hash: self.hash.to_ascii_uppercase(),
+            source: self.source,
+            ..Default::default()
+        };
+        self.apply(&mut song);
+        song
+    }
+}
+
+///
+/// Songs layered over the loaded database, keyed by uppercase hash.
+/// Local songs are merged in here so they can be looked up like scraped ones.
+///
+#[derive(Debug, Default)]
+pub struct BeatStarOverlay {
+    overrides: HashMap<String, SongOverride>,
+
+    /// Every entry as a song of its own, rebuilt when the entry changes
+    songs: HashMap<String, Song>,
+
+    /// The FFI form of `songs`
+    ffi_songs: HashMap<String, BeatStarSong>,
+}
+
+impl BeatStarOverlay {
+    ///
+    /// Adds the entry, merging it into the one with the same hash if there's one
+    ///
+    pub fn insert(&mut self, song: SongOverride) {
+        let hash = song.hash.to_ascii_uppercase();
+
+        let song = match self.overrides.entry(hash.clone()) {
+            Entry::Occupied(entry) => {
+                let existing = entry.into_mut();
+                existing.merge(song);
+                existing
+            }
+            Entry::Vacant(entry) => entry.insert(song),
+        };
+
+        let song = song.to_song();
+        self.ffi_songs.insert(hash.clone(), BeatStarSong::from(&song));
+        self.songs.insert(hash, song);
+    }
+
+    ///
+    /// Adds a local song for every level folder in `levels_dir` (e.g. CustomLevels).
+    /// Folders that can't be read are skipped, returns how many songs were added.
+    ///
+    pub fn load_levels(&mut self, levels_dir: &Path) -> anyhow::Result<usize> {
+        let mut added = 0;
+
+        for folder in level_folders(levels_dir)? {
+            match beatstar_read_local_song(&folder) {
+                Ok(song) => {
+                    self.insert(SongOverride::local(song));
+                    added += 1;
+                }
+                Err(e) => {
+                    event!(Level::WARN, "Unable to read level {0}: {1:#}", folder.display(), e)
+                }
+            }
+        }
+
+        event!(Level::INFO, "Loaded {0} local levels", added);
+        Ok(added)
+    }
+
+    ///
+    /// Corrects the song's names, `None` keeps the value of the lower layers
+    ///
+    pub fn set_metadata(
+        &mut self,
+        hash: &str,
+        song_name: Option<&str>,
+        song_sub_name: Option<&str>,
+        song_author_name: Option<&str>,
+        level_author_name: Option<&str>,
+    ) {
+        let text = |value: Option<&str>| value.map(str::to_string);
+
+        self.insert(SongOverride {
+            song_name: text(song_name),
+            song_sub_name: text(song_sub_name),
+            song_author_name: text(song_author_name),
+            level_author_name: text(level_author_name),
+            ..SongOverride::correction(hash)
+        });
+    }
+
+    ///
+    /// Sets a custom star rating on a difficulty, 0 included
+    ///
+    pub fn set_stars(
+        &mut self,
+        hash: &str,
+        characteristic: Characteristic,
+        difficulty: SongDiffs,
+        stars: f32,
+    ) {
+        self.insert(SongOverride {
+            difficulties: vec![DifficultyOverride {
+                characteristic,
+                difficulty,
+                name: difficulty.to_string(),
+                stars: Some(stars),
+                ..Default::default()
+            }],
+            ..SongOverride::correction(hash)
+        });
+    }
+
+    ///
+    /// Gets the entry of a song by hash, in any case
+    ///
+    pub fn get_override(&self, hash: &str) -> Option<&SongOverride> {
+        self.overrides.get(&hash.to_ascii_uppercase())
+    }
+
+    pub fn overrides(&self) -> impl Iterator<Item = &SongOverride> {
+        self.overrides.values()
+    }
+
+    ///
+    /// Gets a song as only this overlay knows it by hash, in any case
+    ///
+    pub fn get(&self, hash: &str) -> Option<&Song> {
+        self.songs.get(&hash.to_ascii_uppercase())
+    }
+
+    pub fn songs(&self) -> impl Iterator<Item = &Song> {
+        self.songs.values()
+    }
+
+    pub(crate) fn ffi_song(&self, hash: &str) -> Option<&BeatStarSong> {
+        self.ffi_songs.get(&hash.to_ascii_uppercase())
+    }
+}
+
+impl SongDatabase {
+    ///
+    /// Looks the song up in the database, falling back to the overlay for songs it doesn't have
+    ///
+    pub fn get_song_with_overlay<'a>(
+        &'a self,
+        overlay: &'a BeatStarOverlay,
+        hash: &str,
+    ) -> Option<&'a Song> {
+        self.get_song(hash).or_else(|| overlay.get(hash))
+    }
+}
+
+impl BeatStarDataFile {
+    ///
+    /// The FFI form of `SongDatabase::get_song_with_overlay`
+    ///
+    pub(crate) fn get_song_with_overlay<'a>(
+        &'a self,
+        overlay: &'a BeatStarOverlay,
+        hash: &str,
+    ) -> Option<&'a BeatStarSong> {
+        self.get_song(hash).or_else(|| overlay.ffi_song(hash))
+    }
+}
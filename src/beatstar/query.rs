@@ -0,0 +1,192 @@
+use crate::beatstar::data::UnixTime;
+use crate::beatstar::model::{Difficulty, Song};
+use crate::beatstar::song_database::SongDatabase;
+
+///
+/// A difficulty along with the song that owns it
+///
+pub type SongDifficultyPair<'a> = (&'a Song, &'a Difficulty);
+
+///
+/// Ranges a difficulty has to be in to match a query. Ranges are inclusive,
+/// the default filter matches every difficulty. Difficulties without an NJS
+/// are matched on the game's default NJS for the difficulty.
+///
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeatStarDifficultyFilter {
+    pub ranked_only: bool,
+    pub min_stars: f32,
+    pub max_stars: f32,
+    pub min_njs: f32,
+    pub max_njs: f32,
+    pub min_reaction_time_ms: f32,
+    pub max_reaction_time_ms: f32,
+}
+
+impl Default for BeatStarDifficultyFilter {
+    fn default() -> Self {
+        BeatStarDifficultyFilter {
+            ranked_only: false,
+            min_stars: 0.0,
+            max_stars: f32::INFINITY,
+            min_njs: 0.0,
+            max_njs: f32::INFINITY,
+            min_reaction_time_ms: 0.0,
+            max_reaction_time_ms: f32::INFINITY,
+        }
+    }
+}
+
+impl BeatStarDifficultyFilter {
+    pub fn matches(&self, diff: &Difficulty) -> bool {
+        (!self.ranked_only || diff.ranked)
+            && (self.min_stars..=self.max_stars).contains(&diff.stars)
+            && (self.min_njs..=self.max_njs).contains(&diff.effective_njs())
+            && (self.min_reaction_time_ms..=self.max_reaction_time_ms)
+                .contains(&diff.jump_info.reaction_time_ms)
+    }
+}
+
+impl SongDatabase {
+    ///
+    /// Iterates every difficulty of every song, ordered by song hash
+    ///
+    pub fn difficulties(&self) -> impl Iterator<Item = SongDifficultyPair<'_>> {
+        difficulties_of(self.songs())
+    }
+
+    ///
+    /// Gets all the difficulties matching the filter, ordered by song hash
+    ///
+    pub fn query_difficulties(
+        &self,
+        filter: &BeatStarDifficultyFilter,
+    ) -> Vec<SongDifficultyPair<'_>> {
+        query_difficulties_of(self.songs(), filter)
+    }
+
+    ///
+    /// Gets all the ranked difficulties that were ranked at or after `since`, newest first
+    ///
+    pub fn ranked_since(&self, since: UnixTime) -> Vec<SongDifficultyPair<'_>> {
+        ranked_since_of(self.songs(), since)
+    }
+
+    ///
+    /// Gets the `limit` most recently ranked difficulties, newest first
+    ///
+    pub fn recently_ranked(&self, limit: usize) -> Vec<SongDifficultyPair<'_>> {
+        recently_ranked_of(self.songs(), limit)
+    }
+
+    ///
+    /// Gets up to `limit` songs whose name, sub name, author or mapper contains `text`,
+    /// ignoring case, ordered by hash
+    ///
+    pub fn search_songs(&self, text: &str, limit: usize) -> Vec<&Song> {
+        search_songs_of(self.songs(), text, limit)
+    }
+
+    ///
+    /// Gets every song mapped by `mapper`, ignoring case, ordered by hash
+    ///
+    pub fn mapper_songs(&self, mapper: &str) -> Vec<&Song> {
+        mapper_songs_of(self.songs(), mapper)
+    }
+}
+
+// The queries over any list of songs, shared by `SongDatabase` and `LayeredDatabase`
+
+pub(crate) fn difficulties_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+) -> impl Iterator<Item = SongDifficultyPair<'a>> {
+    songs
+        .into_iter()
+        .flat_map(|song| song.difficulties.iter().map(move |diff| (song, diff)))
+}
+
+pub(crate) fn query_difficulties_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+    filter: &BeatStarDifficultyFilter,
+) -> Vec<SongDifficultyPair<'a>> {
+    difficulties_of(songs)
+        .filter(|(_, diff)| filter.matches(diff))
+        .collect()
+}
+
+pub(crate) fn ranked_since_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+    since: UnixTime,
+) -> Vec<SongDifficultyPair<'a>> {
+    let mut ranked: Vec<SongDifficultyPair> = difficulties_of(songs)
+        .filter(|(_, diff)| diff.ranked && ranked_time(diff) >= since)
+        .collect();
+
+    sort_newest_ranked(&mut ranked);
+    ranked
+}
+
+pub(crate) fn recently_ranked_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+    limit: usize,
+) -> Vec<SongDifficultyPair<'a>> {
+    let mut ranked: Vec<SongDifficultyPair> = difficulties_of(songs)
+        .filter(|(_, diff)| diff.ranked && ranked_time(diff) > 0)
+        .collect();
+
+    sort_newest_ranked(&mut ranked);
+    ranked.truncate(limit);
+    ranked
+}
+
+pub(crate) fn search_songs_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+    text: &str,
+    limit: usize,
+) -> Vec<&'a Song> {
+    let text = text.to_lowercase();
+
+    songs
+        .into_iter()
+        .filter(|song| {
+            [
+                &song.song_name,
+                &song.song_sub_name,
+                &song.song_author_name,
+                &song.level_author_name,
+            ]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&text))
+        })
+        .take(limit)
+        .collect()
+}
+
+pub(crate) fn mapper_songs_of<'a>(
+    songs: impl IntoIterator<Item = &'a Song>,
+    mapper: &str,
+) -> Vec<&'a Song> {
+    songs
+        .into_iter()
+        .filter(|song| song.level_author_name.eq_ignore_ascii_case(mapper))
+        .collect()
+}
+
+///
+/// When the difficulty was ranked, 0 if unknown
+///
+fn ranked_time(diff: &Difficulty) -> UnixTime {
+    diff.ranked_update_time.map_or(0, |time| time.timestamp())
+}
+
+fn sort_newest_ranked(ranked: &mut [SongDifficultyPair]) {
+    ranked.sort_by(|(a_song, a), (b_song, b)| {
+        ranked_time(b)
+            .cmp(&ranked_time(a))
+            // keep the order deterministic for diffs ranked at the same time
+            .then_with(|| a_song.hash.cmp(&b_song.hash))
+            .then_with(|| a.difficulty.cmp(&b.difficulty))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
@@ -0,0 +1,56 @@
+use crate::beatstar::model::Difficulty;
+
+/// Highest score a single note can give before the combo multiplier
+pub const MAX_NOTE_SCORE: u32 = 115;
+
+///
+/// Gets the maximum score of a map with `notes` notes.
+///
+/// The combo multiplier ramps 1x -> 2x -> 4x -> 8x, needing 2, 4 and 8 notes at
+/// each step. So the first note is 1x, the next 4 are 2x, the next 8 are 4x and the rest 8x.
+///
+pub const fn beatstar_max_score_for_notes(notes: u32) -> u32 {
+    match notes {
+        0..=1 => notes * MAX_NOTE_SCORE,
+        2..=5 => MAX_NOTE_SCORE + (notes - 1) * 2 * MAX_NOTE_SCORE,
+        6..=13 => 1035 + (notes - 5) * 4 * MAX_NOTE_SCORE,
+        _ => 4715u32.saturating_add((notes - 13).saturating_mul(8 * MAX_NOTE_SCORE)),
+    }
+}
+
+///
+/// Gets the maximum score of the difficulty based on its note count
+///
+pub fn beatstar_max_score(diff: &Difficulty) -> u32 {
+    beatstar_max_score_for_notes(diff.notes)
+}
+
+///
+/// Converts a raw score to an accuracy percentage (0-100).
+/// Returns 0 for difficulties without notes.
+///
+pub fn beatstar_score_to_accuracy(diff: &Difficulty, score: u32) -> f32 {
+    score_to_accuracy_for_notes(diff.notes, score)
+}
+
+///
+/// Converts an accuracy percentage (0-100) to the raw score, rounded down
+///
+pub fn beatstar_accuracy_to_score(diff: &Difficulty, accuracy: f32) -> u32 {
+    accuracy_to_score_for_notes(diff.notes, accuracy)
+}
+
+pub(crate) fn score_to_accuracy_for_notes(notes: u32, score: u32) -> f32 {
+    let max_score = beatstar_max_score_for_notes(notes);
+    if max_score == 0 {
+        return 0.0;
+    }
+
+    (score as f64 / max_score as f64 * 100.0) as f32
+}
+
+pub(crate) fn accuracy_to_score_for_notes(notes: u32, accuracy: f32) -> u32 {
+    let max_score = beatstar_max_score_for_notes(notes) as f64;
+
+    (max_score * (accuracy as f64 / 100.0)).clamp(0.0, max_score).floor() as u32
+}
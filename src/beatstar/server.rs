@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, TryLockError};
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{event, span, Level};
+
+use crate::beatstar::database::{beatstar_fetch_database_zip, beatstar_write_file_atomically};
+use crate::beatstar::model::{format_time, Difficulty, Song};
+use crate::beatstar::query::BeatStarDifficultyFilter;
+use crate::beatstar::song_database::SongDatabase;
+
+/// How long clients may reuse a response before asking again
+const MAX_AGE_SECS: u32 = 60;
+
+/// The threads handling requests, so a slow request or a refresh doesn't hold up the others
+const WORKERS: usize = 4;
+
+pub struct BeatStarServerConfig {
+    /// Must be a loopback address, `127.0.0.1:0` picks a free port
+    pub addr: String,
+    /// The database zip, read on start and on every refresh
+    pub db_path: String,
+    /// Required in the `X-Admin-Token` header of admin requests, which are refused without it.
+    /// Browsers can't send the header cross-site without a preflight, so pages can't forge them.
+    pub admin_token: Option<String>,
+}
+
+///
+/// A loaded database. Requests hold on to the snapshot they started with,
+/// so a refresh never changes the data under a request.
+///
+struct Snapshot {
+    data: SongDatabase,
+    generation: u64,
+    modified: SystemTime,
+}
+
+impl Snapshot {
+    fn etag(&self) -> String {
+        let modified = DateTime::<Utc>::from(self.modified).timestamp();
+        format!("\"{0}-{1}\"", self.generation, modified)
+    }
+}
+
+///
+/// Serves the database as JSON over HTTP on loopback
+///
+pub struct BeatStarServer {
+    server: Server,
+    config: BeatStarServerConfig,
+    snapshot: RwLock<Arc<Snapshot>>,
+    /// The generation of the last successful refresh, locked while refreshing so only one runs at a time
+    generation: Mutex<u64>,
+}
+
+///
+/// The song fields listed in search and query results
+///
+#[derive(Serialize)]
+struct SongSummary {
+    hash: String,
+    key: String,
+    song_name: String,
+    song_sub_name: String,
+    song_author_name: String,
+    level_author_name: String,
+    bpm: f32,
+    duration_secs: u32,
+    rating: f32,
+}
+
+impl From<&Song> for SongSummary {
+    fn from(song: &Song) -> Self {
+        SongSummary {
+            hash: song.hash.clone(),
+            key: song.key.clone(),
+            song_name: song.song_name.clone(),
+            song_sub_name: song.song_sub_name.clone(),
+            song_author_name: song.song_author_name.clone(),
+            level_author_name: song.level_author_name.clone(),
+            bpm: song.bpm,
+            duration_secs: song.duration_secs,
+            rating: song.rating,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct QueryResult<'a> {
+    song: SongSummary,
+    difficulty: &'a Difficulty,
+}
+
+#[derive(Serialize)]
+struct MapperInfo {
+    name: String,
+    song_count: usize,
+    ranked_difficulty_count: usize,
+    upvotes: u64,
+    downvotes: u64,
+    first_upload: String,
+    last_upload: String,
+    songs: Vec<SongSummary>,
+}
+
+struct Reply {
+    status: u16,
+    body: serde_json::Value,
+    /// Successful reads of the snapshot, sent with cache headers
+    cacheable: bool,
+}
+
+impl Reply {
+    fn ok<T: Serialize>(body: T) -> anyhow::Result<Reply> {
+        Ok(Reply {
+            status: 200,
+            body: serde_json::to_value(body)?,
+            cacheable: true,
+        })
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Reply {
+        Reply {
+            status,
+            body: json!({ "error": message.into() }),
+            cacheable: false,
+        }
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+///
+/// Decodes `%XX` escapes and `+` in a query string component
+///
+fn decode_component(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2]))
+            {
+                (Some(high), Some(low)) => {
+                    decoded.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn query_params(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => (decode_component(name), decode_component(value)),
+            None => (decode_component(pair), String::new()),
+        })
+        .collect()
+}
+
+fn param<T: std::str::FromStr>(
+    params: &HashMap<String, String>,
+    name: &str,
+    default: T,
+) -> Result<T, Reply> {
+    match params.get(name) {
+        None => Ok(default),
+        Some(value) => value
+            .parse()
+            .map_err(|_| Reply::error(400, format!("Invalid value for {name}: {value}"))),
+    }
+}
+
+fn difficulty_filter(params: &HashMap<String, String>) -> Result<BeatStarDifficultyFilter, Reply> {
+    let default = BeatStarDifficultyFilter::default();
+
+    Ok(BeatStarDifficultyFilter {
+        ranked_only: param(params, "ranked", default.ranked_only)?,
+        min_stars: param(params, "min_stars", default.min_stars)?,
+        max_stars: param(params, "max_stars", default.max_stars)?,
+        min_njs: param(params, "min_njs", default.min_njs)?,
+        max_njs: param(params, "max_njs", default.max_njs)?,
+        min_reaction_time_ms: param(params, "min_reaction_ms", default.min_reaction_time_ms)?,
+        max_reaction_time_ms: param(params, "max_reaction_ms", default.max_reaction_time_ms)?,
+    })
+}
+
+fn mapper_info(name: &str, songs: Vec<&Song>) -> MapperInfo {
+    let mut uploads: Vec<_> = songs.iter().filter_map(|song| song.uploaded).collect();
+    uploads.sort();
+
+    MapperInfo {
+        name: name.to_string(),
+        song_count: songs.len(),
+        ranked_difficulty_count: songs
+            .iter()
+            .flat_map(|song| &song.difficulties)
+            .filter(|diff| diff.ranked)
+            .count(),
+        upvotes: songs.iter().map(|song| song.upvotes as u64).sum(),
+        downvotes: songs.iter().map(|song| song.downvotes as u64).sum(),
+        first_upload: format_time(uploads.first().copied()),
+        last_upload: format_time(uploads.last().copied()),
+        songs: songs.into_iter().map(SongSummary::from).collect(),
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .expect("header names and values are ASCII")
+}
+
+fn request_header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+fn snapshot_of(data: SongDatabase, db_path: &str, generation: u64) -> Snapshot {
+    let modified = std::fs::metadata(db_path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or_else(|_| SystemTime::now());
+
+    Snapshot {
+        data,
+        generation,
+        modified,
+    }
+}
+
+impl BeatStarServer {
+    ///
+    /// Loads the database and listens on the address. Only loopback addresses are allowed,
+    /// and the admin endpoint is only enabled with a token.
+    ///
+    pub fn bind(config: BeatStarServerConfig) -> anyhow::Result<BeatStarServer> {
+        let addr: SocketAddr = config
+            .addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Unable to resolve {0}", config.addr))?;
+
+        if !addr.ip().is_loopback() {
+            return Err(anyhow!(
+                "Refusing to listen on {addr}, only loopback is allowed"
+            ));
+        }
+
+        let snapshot = snapshot_of(SongDatabase::open(&config.db_path)?, &config.db_path, 1);
+        let server = Server::http(addr).map_err(|e| anyhow!("Unable to listen on {addr}: {e}"))?;
+
+        Ok(BeatStarServer {
+            server,
+            config,
+            snapshot: RwLock::new(Arc::new(snapshot)),
+            generation: Mutex::new(1),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    ///
+    /// Handles requests on a few worker threads until `stop` is called
+    ///
+    pub fn run(&self) {
+        let span = span!(Level::TRACE, "beatstar_server");
+
+        event!(Level::INFO, "Serving on {0:?}", self.local_addr());
+        std::thread::scope(|scope| {
+            for _ in 0..WORKERS {
+                scope.spawn(|| {
+                    let _guard = span.enter();
+                    for request in self.server.incoming_requests() {
+                        self.handle(request);
+                    }
+                });
+            }
+        });
+    }
+
+    ///
+    /// Makes `run` return, the requests being handled are finished first
+    ///
+    pub fn stop(&self) {
+        // Each unblock stops one worker
+        for _ in 0..WORKERS {
+            self.server.unblock();
+        }
+    }
+
+    ///
+    /// Reloads the database file, downloading it first if `download` is set. Waits for a
+    /// refresh that's already running, requests keep using the current data meanwhile.
+    /// Returns the new generation, which is part of every ETag.
+    ///
+    pub fn refresh(&self, download: bool) -> anyhow::Result<u64> {
+        let mut generation = self.generation.lock().unwrap_or_else(PoisonError::into_inner);
+        self.refresh_locked(&mut generation, download)
+    }
+
+    ///
+    /// Loads the new data and swaps it in. Nothing changes when it fails: a download only
+    /// replaces the file once it parsed, and the generation only moves on success.
+    ///
+    fn refresh_locked(&self, generation: &mut u64, download: bool) -> anyhow::Result<u64> {
+        let db_path = &self.config.db_path;
+
+        let data = if download {
+            let bytes = beatstar_fetch_database_zip()?;
+            let data = SongDatabase::from_zip(&bytes)?;
+            beatstar_write_file_atomically(Path::new(db_path), &bytes)?;
+            data
+        } else {
+            SongDatabase::open(db_path)?
+        };
+
+        *generation += 1;
+        let snapshot = Arc::new(snapshot_of(data, db_path, *generation));
+
+        match self.snapshot.write() {
+            Ok(mut guard) => *guard = snapshot,
+            Err(poisoned) => *poisoned.into_inner() = snapshot,
+        }
+
+        event!(
+            Level::INFO,
+            "Refreshed the database, generation {0}",
+            generation
+        );
+        Ok(*generation)
+    }
+
+    fn snapshot(&self) -> Arc<Snapshot> {
+        match self.snapshot.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn handle(&self, request: Request) {
+        let snapshot = self.snapshot();
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let params = query_params(query);
+
+        event!(Level::DEBUG, "{0} {1}", request.method(), url);
+
+        let reply = match (request.method(), path) {
+            (Method::Post, "/admin/refresh") => self.admin_refresh(&request, &params),
+            (Method::Get, _) => self
+                .route(&snapshot, path, &params)
+                .unwrap_or_else(|e| Reply::error(500, format!("{e:#}"))),
+            _ => Reply::error(405, "Method not allowed"),
+        };
+
+        let etag = snapshot.etag();
+        let response =
+            if reply.cacheable && request_header(&request, "If-None-Match") == Some(&etag) {
+                Response::from_string("").with_status_code(304)
+            } else {
+                Response::from_string(reply.body.to_string())
+                    .with_status_code(reply.status)
+                    .with_header(header("Content-Type", "application/json"))
+            };
+
+        let response = if reply.cacheable {
+            let modified = DateTime::<Utc>::from(snapshot.modified);
+            response
+                .with_header(header(
+                    "Cache-Control",
+                    &format!("public, max-age={MAX_AGE_SECS}"),
+                ))
+                .with_header(header("ETag", &etag))
+                .with_header(header(
+                    "Last-Modified",
+                    &modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+                ))
+        } else {
+            response.with_header(header("Cache-Control", "no-store"))
+        };
+
+        if let Err(e) = request.respond(response) {
+            event!(Level::WARN, "Unable to respond to {0}: {1}", url, e);
+        }
+    }
+
+    fn admin_refresh(&self, request: &Request, params: &HashMap<String, String>) -> Reply {
+        let token = match &self.config.admin_token {
+            Some(token) => token,
+            None => return Reply::error(403, "Refreshing needs the server to have an admin token"),
+        };
+        if request_header(request, "X-Admin-Token") != Some(token.as_str()) {
+            return Reply::error(401, "Missing or wrong X-Admin-Token");
+        }
+
+        let download = match param(params, "download", false) {
+            Ok(download) => download,
+            Err(reply) => return reply,
+        };
+
+        // Don't tie up another worker waiting for the running refresh
+        let mut generation = match self.generation.try_lock() {
+            Ok(generation) => generation,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                return Reply::error(409, "A refresh is already running")
+            }
+        };
+
+        match self.refresh_locked(&mut generation, download) {
+            Ok(generation) => Reply {
+                status: 200,
+                body: json!({
+                    "generation": generation,
+                    "songs": self.snapshot().data.len(),
+                }),
+                cacheable: false,
+            },
+            Err(e) => Reply::error(500, format!("Unable to refresh: {e:#}")),
+        }
+    }
+
+    fn route(
+        &self,
+        snapshot: &Snapshot,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> anyhow::Result<Reply> {
+        let data = &snapshot.data;
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(decode_component)
+            .collect();
+
+        match segments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["info"] => Reply::ok(json!({
+                "db_path": self.config.db_path,
+                "generation": snapshot.generation,
+                "modified": DateTime::<Utc>::from(snapshot.modified).to_rfc3339(),
+                "song_count": data.len(),
+                "difficulty_count": data.difficulties().count(),
+            })),
+            ["songs", id] => match data.get_song_by_id(id) {
+                Some(song) => Reply::ok(song),
+                None => Ok(Reply::error(404, format!("No song with hash or key {id}"))),
+            },
+            ["search"] => {
+                let text = match params.get("q") {
+                    Some(text) if !text.is_empty() => text,
+                    _ => return Ok(Reply::error(400, "Missing q")),
+                };
+                let limit = match param(params, "limit", 20usize) {
+                    Ok(limit) => limit,
+                    Err(reply) => return Ok(reply),
+                };
+
+                let songs: Vec<SongSummary> = data
+                    .search_songs(text, limit)
+                    .into_iter()
+                    .map(SongSummary::from)
+                    .collect();
+                Reply::ok(songs)
+            }
+            ["query"] => {
+                let (filter, limit) =
+                    match (difficulty_filter(params), param(params, "limit", 50usize)) {
+                        (Ok(filter), Ok(limit)) => (filter, limit),
+                        (Err(reply), _) | (_, Err(reply)) => return Ok(reply),
+                    };
+
+                let results: Vec<QueryResult> = data
+                    .query_difficulties(&filter)
+                    .into_iter()
+                    .take(limit)
+                    .map(|(song, difficulty)| QueryResult {
+                        song: SongSummary::from(song),
+                        difficulty,
+                    })
+                    .collect();
+                Reply::ok(results)
+            }
+            ["stats"] => Reply::ok(data.stats()),
+            ["mappers", name] => match data.mapper_songs(name) {
+                songs if songs.is_empty() => {
+                    Ok(Reply::error(404, format!("No songs mapped by {name}")))
+                }
+                songs => Reply::ok(mapper_info(name, songs)),
+            },
+            _ => Ok(Reply::error(404, format!("Unknown endpoint {path}"))),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::beatstar::model::{Difficulty, Song};
+use crate::beatstar::song_database::{newest_by_key, SongDatabase};
+
+///
+/// How much the net votes (upvotes minus downvotes) of a song must move to be reported
+///
+pub const BEATSTAR_MIN_VOTE_SWING: i64 = 10;
+
+///
+/// Star ratings closer than this are the same rating rounded differently
+///
+const STAR_EPSILON: f32 = 0.005;
+
+///
+/// What changed between two snapshots of the database. The lists are ordered by hash,
+/// then characteristic and difficulty, so the same snapshots always give the same report.
+///
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added_songs: Vec<String>,
+    pub removed_songs: Vec<String>,
+    /// Songs re-uploaded under a new hash, their changes are reported under the new hash
+    pub updated_songs: Vec<SongUpdate>,
+    /// Difficulties that became ranked, including those of added songs
+    pub newly_ranked: Vec<DifficultyChange>,
+    /// Difficulties that lost their rank or were removed while ranked
+    pub unranked: Vec<DifficultyChange>,
+    /// Ranked difficulties whose star rating changed
+    pub star_changes: Vec<DifficultyChange>,
+    /// Songs whose net votes moved by at least `BEATSTAR_MIN_VOTE_SWING`, largest swing first
+    pub vote_swings: Vec<VoteSwing>,
+    pub metadata_edits: Vec<MetadataEdit>,
+}
+
+///
+/// A removed and an added song with the same Beat Saver key
+///
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SongUpdate {
+    pub key: String,
+    pub old_hash: String,
+    pub new_hash: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct DifficultyChange {
+    pub hash: String,
+    pub characteristic: String,
+    pub difficulty: String,
+    /// 0 when the difficulty wasn't there
+    pub old_stars: f32,
+    /// 0 when the difficulty is gone
+    pub new_stars: f32,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct VoteSwing {
+    pub hash: String,
+    pub old_upvotes: u32,
+    pub old_downvotes: u32,
+    pub new_upvotes: u32,
+    pub new_downvotes: u32,
+    pub old_rating: f32,
+    pub new_rating: f32,
+}
+
+impl VoteSwing {
+    ///
+    /// How much the net votes moved, negative when the song lost votes
+    ///
+    pub fn swing(&self) -> i64 {
+        (self.new_upvotes as i64 - self.new_downvotes as i64)
+            - (self.old_upvotes as i64 - self.old_downvotes as i64)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MetadataEdit {
+    pub hash: String,
+    /// The field name as in the scraped data, e.g. `SongName`
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+///
+/// The difficulties of a song in a stable order
+///
+fn sorted_diffs(song: Option<&Song>) -> Vec<&Difficulty> {
+    let mut diffs: Vec<&Difficulty> = song
+        .map(|s| s.difficulties.iter().collect())
+        .unwrap_or_default();
+    diffs.sort_by(|a, b| {
+        (a.characteristic.name(), a.difficulty, &a.name).cmp(&(
+            b.characteristic.name(),
+            b.difficulty,
+            &b.name,
+        ))
+    });
+    diffs
+}
+
+fn same_difficulty(a: &Difficulty, b: &Difficulty) -> bool {
+    a.characteristic == b.characteristic && a.name == b.name
+}
+
+fn metadata(song: &Song) -> [(&'static str, String); 7] {
+    [
+        ("Key", song.key.clone()),
+        ("SongName", song.song_name.clone()),
+        ("SongSubName", song.song_sub_name.clone()),
+        ("SongAuthorName", song.song_author_name.clone()),
+        ("LevelAuthorName", song.level_author_name.clone()),
+        ("Bpm", song.bpm.to_string()),
+        ("Duration", song.duration_secs.to_string()),
+    ]
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == SnapshotDiff::default()
+    }
+
+    fn diff_difficulties(
+        &mut self,
+        hash: &str,
+        old: Option<&Song>,
+        new: Option<&Song>,
+    ) {
+        let old_diffs = sorted_diffs(old);
+        let new_diffs = sorted_diffs(new);
+        let change =
+            |diff: &Difficulty, old_stars: f32, new_stars: f32| DifficultyChange {
+                hash: hash.to_string(),
+                characteristic: diff.characteristic.name().to_string(),
+                difficulty: diff.name.clone(),
+                old_stars,
+                new_stars,
+            };
+
+        for new_diff in &new_diffs {
+            match old_diffs.iter().find(|d| same_difficulty(d, new_diff)) {
+                Some(old_diff) => match (old_diff.ranked, new_diff.ranked) {
+                    (false, true) => {
+                        self.newly_ranked
+                            .push(change(new_diff, old_diff.stars, new_diff.stars))
+                    }
+                    (true, false) => {
+                        self.unranked
+                            .push(change(new_diff, old_diff.stars, new_diff.stars))
+                    }
+                    (true, true) if (old_diff.stars - new_diff.stars).abs() > STAR_EPSILON => self
+                        .star_changes
+                        .push(change(new_diff, old_diff.stars, new_diff.stars)),
+                    _ => {}
+                },
+                None if new_diff.ranked => {
+                    self.newly_ranked
+                        .push(change(new_diff, 0.0, new_diff.stars))
+                }
+                None => {}
+            }
+        }
+
+        for old_diff in &old_diffs {
+            if old_diff.ranked && !new_diffs.iter().any(|d| same_difficulty(d, old_diff)) {
+                self.unranked.push(change(old_diff, old_diff.stars, 0.0));
+            }
+        }
+    }
+
+    fn diff_song(&mut self, old: &Song, new: &Song) {
+        let hash = new.hash.clone();
+
+        let swing = VoteSwing {
+            hash: hash.clone(),
+            old_upvotes: old.upvotes,
+            old_downvotes: old.downvotes,
+            new_upvotes: new.upvotes,
+            new_downvotes: new.downvotes,
+            old_rating: old.rating,
+            new_rating: new.rating,
+        };
+        if swing.swing().abs() >= BEATSTAR_MIN_VOTE_SWING {
+            self.vote_swings.push(swing);
+        }
+
+        for ((field, old_value), (_, new_value)) in metadata(old).into_iter().zip(metadata(new)) {
+            if old_value != new_value {
+                self.metadata_edits.push(MetadataEdit {
+                    hash: hash.clone(),
+                    field: field.to_string(),
+                    old_value,
+                    new_value,
+                });
+            }
+        }
+
+        self.diff_difficulties(&hash, Some(old), Some(new));
+    }
+}
+
+///
+/// Compares two snapshots of the database: added, removed and re-uploaded songs, rank and
+/// star changes, vote swings and metadata edits. Songs are matched by hash, then songs that
+/// are only in one snapshot are paired by Beat Saver key.
+///
+pub fn beatstar_diff(old: &SongDatabase, new: &SongDatabase) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+
+    let removed: Vec<&Song> = old
+        .songs()
+        .iter()
+        .filter(|s| new.get_song(&s.hash).is_none())
+        .collect();
+    let mut by_key = newest_by_key(removed.iter().copied());
+    let mut paired: HashSet<usize> = HashSet::new();
+
+    for new_song in new.songs() {
+        if let Some(old_song) = old.get_song(&new_song.hash) {
+            diff.diff_song(old_song, new_song);
+            continue;
+        }
+
+        let reuploaded = match new_song.key.is_empty() {
+            true => None,
+            false => by_key.remove(&new_song.key.to_lowercase()),
+        };
+        match reuploaded {
+            Some(index) => {
+                let old_song = removed[index];
+                diff.diff_song(old_song, new_song);
+                paired.insert(index);
+                diff.updated_songs.push(SongUpdate {
+                    key: new_song.key.clone(),
+                    old_hash: old_song.hash.clone(),
+                    new_hash: new_song.hash.clone(),
+                });
+            }
+            None => {
+                let hash = new_song.hash.clone();
+                diff.diff_difficulties(&hash, None, Some(new_song));
+                diff.added_songs.push(hash);
+            }
+        }
+    }
+
+    for (index, old_song) in removed.into_iter().enumerate() {
+        if !paired.contains(&index) {
+            let hash = old_song.hash.clone();
+            diff.diff_difficulties(&hash, Some(old_song), None);
+            diff.removed_songs.push(hash);
+        }
+    }
+
+    // Removed songs were walked after the rest, the sort is stable so each song keeps its order
+    diff.unranked.sort_by(|a, b| a.hash.cmp(&b.hash));
+    diff.vote_swings.sort_by(|a, b| {
+        b.swing()
+            .abs()
+            .cmp(&a.swing().abs())
+            .then_with(|| a.hash.cmp(&b.hash))
+    });
+
+    diff
+}
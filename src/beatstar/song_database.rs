@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::Context;
+use once_cell::sync::OnceCell;
+use stopwatch::Stopwatch;
+use tracing::{event, span, Level};
+
+use crate::beatstar::database::beatstar_fetch_database_zip;
+use crate::beatstar::model::{beatstar_read_songs_zip, Song};
+use crate::beatstar::stats::BeatStarDataStats;
+
+///
+/// A loaded database that owns its songs, so several can live side by side.
+/// The global `beatstar_*` functions and `Beatstar_RetrieveDatabase*` share a default instance.
+///
+#[derive(Debug)]
+pub struct SongDatabase {
+    /// Ordered by hash and never modified after loading, so the cached values below can't go stale
+    songs: Vec<Song>,
+
+    /// The index in `songs` of each uppercase hash
+    by_hash: HashMap<String, usize>,
+
+    /// The index in `songs` of the newest upload of each lowercase key
+    by_key: HashMap<String, usize>,
+
+    /// Lazily computed statistics of this snapshot
+    pub(crate) stats: OnceCell<BeatStarDataStats>,
+}
+
+///
+/// The index of each song by lowercase Beat Saver key, skipping songs without one.
+/// When several songs share a key the newest upload wins, then the first one.
+///
+pub(crate) fn newest_by_key<'a>(songs: impl IntoIterator<Item = &'a Song>) -> HashMap<String, usize> {
+    let songs: Vec<&Song> = songs.into_iter().collect();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for (index, song) in songs.iter().enumerate().filter(|(_, s)| !s.key.is_empty()) {
+        let entry = by_key.entry(song.key.to_lowercase()).or_insert(index);
+        if song.uploaded > songs[*entry].uploaded {
+            *entry = index;
+        }
+    }
+    by_key
+}
+
+///
+/// The lookups playlists and level scans need, so they work on a `SongDatabase`
+/// and on a `LayeredDatabase` alike
+///
+pub(crate) trait SongLookup {
+    fn song_by_hash(&self, hash: &str) -> Option<&Song>;
+    fn song_by_key(&self, key: &str) -> Option<&Song>;
+}
+
+impl SongLookup for SongDatabase {
+    fn song_by_hash(&self, hash: &str) -> Option<&Song> {
+        self.get_song(hash)
+    }
+
+    fn song_by_key(&self, key: &str) -> Option<&Song> {
+        self.get_song_by_key(key)
+    }
+}
+
+impl SongDatabase {
+    ///
+    /// Builds a database from songs of the Rust model, indexing their hashes and keys.
+    /// Songs are ordered by hash, the last of several songs with the same hash wins.
+    ///
+    pub fn from_songs(songs: impl IntoIterator<Item = Song>) -> SongDatabase {
+        let unique: HashMap<String, Song> = songs
+            .into_iter()
+            .map(|song| (song.hash.to_ascii_uppercase(), song))
+            .collect();
+
+        let mut songs: Vec<(String, Song)> = unique.into_iter().collect();
+        songs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let by_hash = songs
+            .iter()
+            .enumerate()
+            .map(|(index, (hash, _))| (hash.clone(), index))
+            .collect();
+
+        let songs: Vec<Song> = songs.into_iter().map(|(_, song)| song).collect();
+        let by_key = newest_by_key(&songs);
+
+        SongDatabase {
+            songs,
+            by_hash,
+            by_key,
+            stats: OnceCell::new(),
+        }
+    }
+
+    ///
+    /// Reads a database zip, as downloaded by `beatstar_fetch_database_zip`
+    ///
+    pub fn from_zip(bytes: &[u8]) -> anyhow::Result<SongDatabase> {
+        let mut stopwatch = Stopwatch::start_new();
+
+        let songs = beatstar_read_songs_zip(bytes)
+            .context("Failed to parse scrapped beat saver data zip.")?;
+        event!(
+            Level::INFO,
+            "Parsed beat file into json data in {0}ms",
+            stopwatch.elapsed().as_millis()
+        );
+
+        let database = SongDatabase::from_songs(songs);
+
+        for (char, count) in database.characteristic_counts() {
+            event!(
+                Level::INFO,
+                "Loaded {1} diffs with characteristic {0}",
+                char,
+                count
+            );
+        }
+
+        event!(
+            Level::INFO,
+            "Fully parsed beat file in {0}ms ({1} songs)",
+            stopwatch.elapsed().as_millis(),
+            database.len()
+        );
+
+        stopwatch.stop();
+        Ok(database)
+    }
+
+    ///
+    /// Reads a database zip file
+    ///
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<SongDatabase> {
+        let span = span!(Level::TRACE, "beatstar_database_update");
+        let _guard = span.enter();
+
+        event!(Level::INFO, "Fetching from file");
+        let bytes = std::fs::read(path)?;
+
+        SongDatabase::from_zip(&bytes)
+    }
+
+    ///
+    /// Downloads the latest database
+    ///
+    pub fn download() -> anyhow::Result<SongDatabase> {
+        SongDatabase::from_zip(&beatstar_fetch_database_zip()?)
+    }
+
+    ///
+    /// The songs ordered by hash
+    ///
+    pub fn songs(&self) -> &[Song] {
+        &self.songs
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+
+    ///
+    /// Gets the song at the index when ordered by hash
+    ///
+    pub fn song_at(&self, index: usize) -> Option<&Song> {
+        self.songs.get(index)
+    }
+
+    ///
+    /// The index of the song in `songs`, by hash in any case
+    ///
+    pub(crate) fn position(&self, hash: &str) -> Option<usize> {
+        self.by_hash.get(&hash.to_ascii_uppercase()).copied()
+    }
+
+    ///
+    /// Gets a song by hash, in any case
+    ///
+    pub fn get_song(&self, hash: &str) -> Option<&Song> {
+        self.position(hash).map(|index| &self.songs[index])
+    }
+
+    ///
+    /// Gets a song by its Beat Saver key, ignoring case.
+    /// The newest upload when several songs share the key.
+    ///
+    pub fn get_song_by_key(&self, key: &str) -> Option<&Song> {
+        self.by_key
+            .get(&key.to_lowercase())
+            .map(|index| &self.songs[*index])
+    }
+
+    ///
+    /// Gets a song by hash, falling back to its key
+    ///
+    pub fn get_song_by_id(&self, id: &str) -> Option<&Song> {
+        self.get_song(id).or_else(|| self.get_song_by_key(id))
+    }
+
+    ///
+    /// How many diffs there are for each characteristic name, including custom ones
+    ///
+    pub fn characteristic_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for diff in self.songs.iter().flat_map(|song| &song.difficulties) {
+            *counts.entry(diff.characteristic.name()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+use anyhow::Context;
+use rusqlite::{params, Connection};
+use tracing::{event, span, Level};
+
+use crate::beatstar::database::beatstar_temp_path;
+use crate::beatstar::model::{format_time, Difficulty, Song};
+use crate::beatstar::score::beatstar_max_score_for_notes;
+
+///
+/// Difficulties and requirements are keyed by name instead of row ids,
+/// so the same song has the same keys in every snapshot.
+///
+const SCHEMA: &str = "
+CREATE TABLE songs (
+    hash TEXT PRIMARY KEY NOT NULL,
+    key TEXT NOT NULL,
+    song_name TEXT NOT NULL,
+    song_sub_name TEXT NOT NULL,
+    song_author_name TEXT NOT NULL,
+    level_author_name TEXT NOT NULL,
+    bpm REAL NOT NULL,
+    duration_secs INTEGER NOT NULL,
+    upvotes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    uploaded TEXT NOT NULL,
+    uploaded_unix_time INTEGER NOT NULL,
+    heat REAL NOT NULL,
+    rating REAL NOT NULL,
+    source TEXT NOT NULL
+) WITHOUT ROWID;
+
+CREATE TABLE characteristics (
+    name TEXT PRIMARY KEY NOT NULL,
+    -- The known characteristic, Custom for the ones added by mods
+    kind TEXT NOT NULL
+) WITHOUT ROWID;
+
+CREATE TABLE difficulties (
+    song_hash TEXT NOT NULL REFERENCES songs (hash),
+    characteristic TEXT NOT NULL REFERENCES characteristics (name),
+    difficulty TEXT NOT NULL,
+    -- Easy is 0 up to ExpertPlus, Unknown last
+    difficulty_rank INTEGER NOT NULL,
+    stars REAL NOT NULL,
+    ranked INTEGER NOT NULL,
+    pp REAL NOT NULL,
+    njs REAL NOT NULL,
+    njs_offset REAL NOT NULL,
+    notes INTEGER NOT NULL,
+    bombs INTEGER NOT NULL,
+    obstacles INTEGER NOT NULL,
+    max_score INTEGER NOT NULL,
+    jump_distance REAL NOT NULL,
+    reaction_time_ms REAL NOT NULL,
+    ranked_update_time TEXT NOT NULL,
+    ranked_update_unix_time INTEGER NOT NULL,
+    PRIMARY KEY (song_hash, characteristic, difficulty)
+) WITHOUT ROWID;
+
+CREATE TABLE requirements (
+    name TEXT PRIMARY KEY NOT NULL
+) WITHOUT ROWID;
+
+CREATE TABLE difficulty_requirements (
+    song_hash TEXT NOT NULL,
+    characteristic TEXT NOT NULL,
+    difficulty TEXT NOT NULL,
+    requirement TEXT NOT NULL REFERENCES requirements (name),
+    PRIMARY KEY (song_hash, characteristic, difficulty, requirement),
+    FOREIGN KEY (song_hash, characteristic, difficulty)
+        REFERENCES difficulties (song_hash, characteristic, difficulty)
+) WITHOUT ROWID;
+
+CREATE INDEX songs_uploaded ON songs (uploaded_unix_time);
+CREATE INDEX songs_level_author ON songs (level_author_name);
+CREATE INDEX difficulties_stars ON difficulties (stars);
+CREATE INDEX difficulties_ranked_update ON difficulties (ranked_update_unix_time);
+CREATE INDEX difficulty_requirements_requirement ON difficulty_requirements (requirement);
+";
+
+///
+/// The difficulties of a song in a stable order. Duplicates keep their scraped order.
+///
+fn sorted_diffs(song: &Song) -> Vec<&Difficulty> {
+    let mut diffs: Vec<&Difficulty> = song.difficulties.iter().collect();
+    diffs.sort_by(|a, b| {
+        (a.characteristic.name(), a.difficulty, &a.name)
+            .cmp(&(b.characteristic.name(), b.difficulty, &b.name))
+    });
+    diffs
+}
+
+///
+/// Inserts the songs with their difficulties. A difficulty with the same characteristic
+/// and name as an earlier one of its song is skipped with a warning, returns how many were.
+///
+fn insert_songs(db: &Connection, songs: &[&Song]) -> rusqlite::Result<usize> {
+    let mut insert_song = db.prepare(
+        "INSERT INTO songs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+    )?;
+    let mut insert_diff = db.prepare(
+        "INSERT INTO difficulties VALUES \
+         (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+    )?;
+    let mut insert_requirement =
+        db.prepare("INSERT OR IGNORE INTO difficulty_requirements VALUES (?1, ?2, ?3, ?4)")?;
+    let mut duplicates = 0;
+
+    for song in songs {
+        let hash = &song.hash;
+        let mut seen = HashSet::new();
+
+        insert_song.execute(params![
+            hash,
+            song.key,
+            song.song_name,
+            song.song_sub_name,
+            song.song_author_name,
+            song.level_author_name,
+            song.bpm,
+            song.duration_secs,
+            song.upvotes,
+            song.downvotes,
+            format_time(song.uploaded),
+            song.uploaded.map_or(0, |time| time.timestamp()),
+            song.heat,
+            song.rating,
+            format!("{0:?}", song.source),
+        ])?;
+
+        for diff in sorted_diffs(song) {
+            let char_name = diff.characteristic.name();
+            let diff_name = &diff.name;
+
+            if !seen.insert((char_name, diff_name)) {
+                event!(
+                    Level::WARN,
+                    "Skipping duplicate difficulty {0} {1} of {2}",
+                    char_name,
+                    diff_name,
+                    hash
+                );
+                duplicates += 1;
+                continue;
+            }
+
+            insert_diff.execute(params![
+                hash,
+                char_name,
+                diff_name,
+                diff.difficulty as i32,
+                diff.stars,
+                diff.ranked,
+                diff.approximate_pp,
+                diff.njs,
+                diff.njs_offset,
+                diff.notes,
+                diff.bombs,
+                diff.obstacles,
+                beatstar_max_score_for_notes(diff.notes),
+                diff.jump_info.jump_distance,
+                diff.jump_info.reaction_time_ms,
+                format_time(diff.ranked_update_time),
+                diff.ranked_update_time.map_or(0, |time| time.timestamp()),
+            ])?;
+
+            for requirement in &diff.requirements {
+                insert_requirement.execute(params![
+                    hash,
+                    char_name,
+                    diff_name,
+                    requirement
+                ])?;
+            }
+        }
+    }
+
+    Ok(duplicates)
+}
+
+///
+/// Writes the songs, difficulties, characteristics and requirements to normalized SQLite
+/// tables, replacing the file. Rows are written in a fixed order, so the same data
+/// always gives the same file and snapshots can be compared with `sqldiff`.
+///
+/// The tables are written to a temp file renamed over `path` once committed, so a failed
+/// export leaves the previous file as it was. A song's difficulties are keyed by
+/// characteristic and name, duplicates after the first are skipped and counted in the result.
+///
+pub(crate) fn export_songs_sqlite(songs: &[&Song], path: &Path) -> anyhow::Result<usize> {
+    let span = span!(Level::TRACE, "beatstar_export_sqlite");
+    let _guard = span.enter();
+
+    let temp_path = beatstar_temp_path(path);
+    let result = write_sqlite(songs, &temp_path).and_then(|duplicates| {
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Unable to replace {0}", path.display()))?;
+        Ok(duplicates)
+    });
+
+    match &result {
+        Ok(duplicates) => event!(
+            Level::INFO,
+            "Exported {0} songs to {1}, skipped {2} duplicate difficulties",
+            songs.len(),
+            path.display(),
+            duplicates
+        ),
+        Err(_) => {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+    }
+    result
+}
+
+fn write_sqlite(songs: &[&Song], path: &Path) -> anyhow::Result<usize> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Unable to replace {0}", path.display()))?;
+    }
+
+    let mut db = Connection::open(path)
+        .with_context(|| format!("Unable to create {0}", path.display()))?;
+    let tx = db.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    // Sorted so the lookup tables are in name order
+    let mut characteristics = BTreeMap::new();
+    let mut requirements = BTreeSet::new();
+    for diff in songs.iter().flat_map(|song| &song.difficulties) {
+        characteristics.insert(diff.characteristic.name(), diff.characteristic.kind());
+        for requirement in &diff.requirements {
+            requirements.insert(requirement.as_str());
+        }
+    }
+
+    {
+        let mut insert_char = tx.prepare("INSERT INTO characteristics VALUES (?1, ?2)")?;
+        for (name, kind) in &characteristics {
+            insert_char.execute(params![name, kind.to_string()])?;
+        }
+
+        let mut insert_requirement = tx.prepare("INSERT INTO requirements VALUES (?1)")?;
+        for name in &requirements {
+            insert_requirement.execute(params![name])?;
+        }
+    }
+
+    let duplicates = insert_songs(&tx, songs)?;
+    tx.commit()?;
+    db.close().map_err(|(_, e)| e)?;
+
+    Ok(duplicates)
+}
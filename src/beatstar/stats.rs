@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::beatstar::data::SongDiffs;
+use crate::beatstar::ffi::{BeatStarDataFile, BeatStarLayeredDatabase, RustCStringWrapper};
+use crate::beatstar::model::Song;
+use crate::beatstar::song_database::SongDatabase;
+
+/// How many equal width buckets each histogram is split into
+pub(crate) const HISTOGRAM_BUCKETS: usize = 20;
+
+///
+/// Aggregate statistics over an entire database snapshot
+///
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BeatStarDataStats {
+    pub song_count: usize,
+    pub difficulty_count: usize,
+    pub ranked_song_count: usize,
+    pub ranked_difficulty_count: usize,
+
+    /// Ranked difficulty count keyed by characteristic name, then parsed difficulty,
+    /// so `Expert+` and `ExpertPlus` are counted together
+    pub ranked_counts: BTreeMap<String, BTreeMap<SongDiffs, usize>>,
+
+    /// Stars of ranked difficulties
+    pub stars: Distribution,
+    /// Note jump speed of every difficulty, the difficulty's default when the map has none
+    pub njs: Distribution,
+    /// Notes per second of every difficulty
+    pub nps: Distribution,
+    pub bpm: Distribution,
+    pub duration_secs: Distribution,
+    pub rating: Distribution,
+
+    /// Upload count keyed by `YYYY-MM`
+    pub uploads_per_month: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Distribution {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub percentiles: Percentiles,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Percentiles {
+    pub p10: f32,
+    pub p25: f32,
+    pub p50: f32,
+    pub p75: f32,
+    pub p90: f32,
+    pub p99: f32,
+}
+
+///
+/// A bucket covering `[start, end)`, the last bucket also includes `end`
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct HistogramBucket {
+    pub start: f32,
+    pub end: f32,
+    pub count: usize,
+}
+
+impl Distribution {
+    pub fn new(mut values: Vec<f32>) -> Distribution {
+        values.retain(|v| v.is_finite());
+        if values.is_empty() {
+            return Distribution::default();
+        }
+
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let min = values[0];
+        let max = values[values.len() - 1];
+        let mean = (values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64) as f32;
+
+        Distribution {
+            count: values.len(),
+            min,
+            max,
+            mean,
+            percentiles: Percentiles {
+                p10: percentile(&values, 10.0),
+                p25: percentile(&values, 25.0),
+                p50: percentile(&values, 50.0),
+                p75: percentile(&values, 75.0),
+                p90: percentile(&values, 90.0),
+                p99: percentile(&values, 99.0),
+            },
+            histogram: histogram(&values, min, max),
+        }
+    }
+}
+
+///
+/// Nearest rank percentile over sorted values
+///
+fn percentile(sorted: &[f32], percentile: f32) -> f32 {
+    let rank = (percentile / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn histogram(sorted: &[f32], min: f32, max: f32) -> Vec<HistogramBucket> {
+    let width = (max - min) / HISTOGRAM_BUCKETS as f32;
+
+    if width <= 0.0 {
+        return vec![HistogramBucket {
+            start: min,
+            end: max,
+            count: sorted.len(),
+        }];
+    }
+
+    let mut buckets: Vec<HistogramBucket> = (0..HISTOGRAM_BUCKETS)
+        .map(|i| HistogramBucket {
+            start: min + width * i as f32,
+            end: min + width * (i + 1) as f32,
+            count: 0,
+        })
+        .collect();
+
+    for value in sorted {
+        let index = (((value - min) / width) as usize).min(HISTOGRAM_BUCKETS - 1);
+        buckets[index].count += 1;
+    }
+
+    buckets
+}
+
+impl BeatStarDataStats {
+    pub fn new(data: &SongDatabase) -> BeatStarDataStats {
+        BeatStarDataStats::from_songs(data.songs())
+    }
+
+    ///
+    /// The statistics of any list of songs, e.g. a database seen through its layers
+    ///
+    pub fn from_songs<'a>(songs: impl IntoIterator<Item = &'a Song>) -> BeatStarDataStats {
+        let mut stats = BeatStarDataStats::default();
+
+        let mut stars = Vec::new();
+        let mut njs = Vec::new();
+        let mut nps = Vec::new();
+        let mut bpm = Vec::new();
+        let mut duration = Vec::new();
+        let mut rating = Vec::new();
+
+        for song in songs {
+            stats.song_count += 1;
+            bpm.push(song.bpm);
+            duration.push(song.duration_secs as f32);
+            rating.push(song.rating);
+
+            if let Some(date) = song.uploaded {
+                *stats
+                    .uploads_per_month
+                    .entry(date.format("%Y-%m").to_string())
+                    .or_insert(0) += 1;
+            }
+
+            if song.is_ranked() {
+                stats.ranked_song_count += 1;
+            }
+
+            for diff in &song.difficulties {
+                stats.difficulty_count += 1;
+                njs.push(diff.effective_njs());
+
+                if song.duration_secs > 0 {
+                    nps.push(diff.notes as f32 / song.duration_secs as f32);
+                }
+
+                if !diff.ranked {
+                    continue;
+                }
+
+                stats.ranked_difficulty_count += 1;
+                stars.push(diff.stars);
+                *stats
+                    .ranked_counts
+                    .entry(diff.characteristic.name().to_string())
+                    .or_default()
+                    .entry(diff.difficulty)
+                    .or_insert(0) += 1;
+            }
+        }
+
+        stats.stars = Distribution::new(stars);
+        stats.njs = Distribution::new(njs);
+        stats.nps = Distribution::new(nps);
+        stats.bpm = Distribution::new(bpm);
+        stats.duration_secs = Distribution::new(duration);
+        stats.rating = Distribution::new(rating);
+
+        stats
+    }
+}
+
+impl SongDatabase {
+    ///
+    /// Gets the statistics of this snapshot, computing them on first use
+    ///
+    pub fn stats(&self) -> &BeatStarDataStats {
+        self.stats.get_or_init(|| BeatStarDataStats::new(self))
+    }
+}
+
+impl BeatStarDataFile {
+    ///
+    /// Gets the statistics of this snapshot as JSON, computing them on first use
+    ///
+    pub fn stats_json(&self) -> &RustCStringWrapper {
+        self.stats_json.get_or_init(|| {
+            RustCStringWrapper::new(
+                serde_json::to_string(self.database().stats()).expect("Stats are always serializable"),
+            )
+        })
+    }
+}
+
+impl BeatStarLayeredDatabase {
+    ///
+    /// Gets the statistics of the merged songs as JSON, computing them on first use
+    ///
+    pub fn stats_json(&self) -> &RustCStringWrapper {
+        self.stats_json.get_or_init(|| {
+            RustCStringWrapper::new(
+                serde_json::to_string(self.view().stats()).expect("Stats are always serializable"),
+            )
+        })
+    }
+}
@@ -0,0 +1,404 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use song_data_core_rust::beatstar::bplist::BPList;
+use song_data_core_rust::beatstar::database::{
+    beatstar_fetch_database_zip, beatstar_write_file_atomically, SCRAPED_SCORE_SABER_URL,
+};
+use song_data_core_rust::beatstar::export::{beatstar_write_csv, beatstar_write_json};
+use song_data_core_rust::beatstar::model::{format_time, Difficulty, Song};
+use song_data_core_rust::beatstar::query::{BeatStarDifficultyFilter, SongDifficultyPair};
+#[cfg(feature = "server")]
+use song_data_core_rust::beatstar::server::{BeatStarServer, BeatStarServerConfig};
+use song_data_core_rust::beatstar::SongDatabase;
+
+///
+/// Inspect and export the scraped Beat Saver/ScoreSaber database
+///
+#[derive(Parser)]
+#[command(name = "songdatacore", version)]
+struct Cli {
+    /// The database zip, downloaded by `download` and read by every other command
+    #[arg(
+        long,
+        global = true,
+        env = "SONGDATACORE_DB",
+        default_value = "combinedScrappedData.zip"
+    )]
+    db: PathBuf,
+
+    /// Print the library's logs to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download the latest database to the --db path
+    Download,
+    /// Show where the database is, how old it is and what it has
+    Info,
+    /// Show a song by hash or Beat Saver key
+    Get {
+        /// The song hash or key
+        id: String,
+        /// Print the song as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find songs by name, author or mapper
+    Search {
+        text: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// List the difficulties matching the filter
+    Query {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Print the database statistics as JSON
+    Stats,
+    /// Export the songs, or only the difficulties matching the filter
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Defaults to stdout, required for sqlite
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The playlist title of the bplist
+        #[arg(long, default_value = "songdatacore")]
+        title: String,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Serve the database as JSON on loopback
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8765")]
+        addr: String,
+        /// Required in the X-Admin-Token header of POST /admin/refresh, which is refused without it
+        #[arg(long, env = "SONGDATACORE_ADMIN_TOKEN")]
+        admin_token: Option<String>,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ExportFormat {
+    Json,
+    Csv,
+    Bplist,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Only ranked difficulties
+    #[arg(long)]
+    ranked: bool,
+    #[arg(long)]
+    min_stars: Option<f32>,
+    #[arg(long)]
+    max_stars: Option<f32>,
+    #[arg(long)]
+    min_njs: Option<f32>,
+    #[arg(long)]
+    max_njs: Option<f32>,
+    #[arg(long)]
+    min_reaction_ms: Option<f32>,
+    #[arg(long)]
+    max_reaction_ms: Option<f32>,
+}
+
+impl FilterArgs {
+    fn is_empty(&self) -> bool {
+        self.filter() == BeatStarDifficultyFilter::default()
+    }
+
+    fn filter(&self) -> BeatStarDifficultyFilter {
+        let default = BeatStarDifficultyFilter::default();
+
+        BeatStarDifficultyFilter {
+            ranked_only: self.ranked,
+            min_stars: self.min_stars.unwrap_or(default.min_stars),
+            max_stars: self.max_stars.unwrap_or(default.max_stars),
+            min_njs: self.min_njs.unwrap_or(default.min_njs),
+            max_njs: self.max_njs.unwrap_or(default.max_njs),
+            min_reaction_time_ms: self.min_reaction_ms.unwrap_or(default.min_reaction_time_ms),
+            max_reaction_time_ms: self.max_reaction_ms.unwrap_or(default.max_reaction_time_ms),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn db_path(cli: &Cli) -> anyhow::Result<&str> {
+    cli.db
+        .to_str()
+        .ok_or_else(|| anyhow!("The database path is not valid UTF-8"))
+}
+
+fn load(cli: &Cli) -> anyhow::Result<SongDatabase> {
+    if !cli.db.exists() {
+        return Err(anyhow!(
+            "{0} does not exist, run `songdatacore download` first",
+            cli.db.display()
+        ));
+    }
+
+    SongDatabase::open(&cli.db)
+        .with_context(|| format!("Unable to load {0}", cli.db.display()))
+}
+
+fn song_title(song: &Song) -> String {
+    let name = if song.song_sub_name.is_empty() {
+        song.song_name.clone()
+    } else {
+        format!("{0} {1}", song.song_name, song.song_sub_name)
+    };
+
+    format!(
+        "{0} - {1} [{2}]",
+        song.song_author_name, name, song.level_author_name
+    )
+}
+
+fn print_song(song: &Song) {
+    println!("{0}", song_title(song));
+    println!("  hash:     {0}", song.hash);
+    println!("  key:      {0}", song.key);
+    println!("  bpm:      {0}", song.bpm);
+    println!("  duration: {0}s", song.duration_secs);
+    println!(
+        "  votes:    +{0} -{1} (rating {2:.3})",
+        song.upvotes, song.downvotes, song.rating
+    );
+    println!("  uploaded: {0}", format_time(song.uploaded));
+    println!("  source:   {0:?}", song.source);
+
+    for diff in &song.difficulties {
+        print_difficulty(diff);
+    }
+}
+
+fn print_difficulty(diff: &Difficulty) {
+    let ranked = if diff.ranked {
+        format!(" {0:.2}* ({1:.0}pp)", diff.stars, diff.approximate_pp)
+    } else {
+        String::new()
+    };
+
+    println!(
+        "  {0} {1}:{2} njs {3} offset {4}, {5} notes, reaction {6:.0}ms",
+        diff.characteristic,
+        diff.name,
+        ranked,
+        diff.njs,
+        diff.njs_offset,
+        diff.notes,
+        diff.jump_info.reaction_time_ms
+    );
+}
+
+fn info(cli: &Cli) -> anyhow::Result<()> {
+    let modified = std::fs::metadata(&cli.db).and_then(|m| m.modified());
+    let database = load(cli)?;
+
+    println!("Database: {0}", cli.db.display());
+    println!("Source:   {0}", SCRAPED_SCORE_SABER_URL);
+    if let Ok(modified) = modified {
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default()
+            .as_secs();
+        println!(
+            "Age:      {0}d {1}h {2}m",
+            age / 86400,
+            age / 3600 % 24,
+            age / 60 % 60
+        );
+    }
+
+    let stats = database.stats();
+    println!(
+        "Songs:        {0} ({1} ranked)",
+        stats.song_count, stats.ranked_song_count
+    );
+    println!(
+        "Difficulties: {0} ({1} ranked)",
+        stats.difficulty_count, stats.ranked_difficulty_count
+    );
+
+    for (name, count) in database.characteristic_counts() {
+        println!("  {0}: {1}", name, count);
+    }
+
+    Ok(())
+}
+
+///
+/// Writes to the file, or to stdout without one
+///
+fn write_output(
+    output: Option<&Path>,
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(
+            File::create(path).with_context(|| format!("Unable to create {0}", path.display()))?,
+        ),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = BufWriter::new(writer);
+
+    write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn export_pairs<'a>(data: &'a SongDatabase, filter: &FilterArgs) -> Vec<SongDifficultyPair<'a>> {
+    if filter.is_empty() {
+        data.difficulties().collect()
+    } else {
+        data.query_difficulties(&filter.filter())
+    }
+}
+
+///
+/// The songs of the pairs, each once
+///
+fn unique_songs<'a>(pairs: &[SongDifficultyPair<'a>]) -> Vec<&'a Song> {
+    let mut songs: Vec<&Song> = pairs.iter().map(|(song, _)| *song).collect();
+    songs.dedup_by(|a, b| a.hash == b.hash);
+    songs
+}
+
+fn export(
+    data: &SongDatabase,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    title: &str,
+    filter: &FilterArgs,
+) -> anyhow::Result<()> {
+    match format {
+        ExportFormat::Json => write_output(output.as_deref(), |writer| {
+            beatstar_write_json(&unique_songs(&export_pairs(data, filter)), writer)
+        }),
+        ExportFormat::Csv => write_output(output.as_deref(), |writer| {
+            beatstar_write_csv(&export_pairs(data, filter), writer)
+        }),
+        ExportFormat::Bplist => write_output(output.as_deref(), |writer| {
+            let pairs = export_pairs(data, filter);
+            let playlist = if filter.is_empty() {
+                BPList::from_songs(title, unique_songs(&pairs))
+            } else {
+                BPList::from_difficulties(title, &pairs)
+            };
+            Ok(serde_json::to_writer_pretty(writer, &playlist)?)
+        }),
+        #[cfg(feature = "sqlite")]
+        ExportFormat::Sqlite => {
+            let path = output.ok_or_else(|| anyhow!("--output is required for sqlite"))?;
+            let duplicates = data.export_sqlite(&path)?;
+            if duplicates > 0 {
+                eprintln!("Skipped {duplicates} duplicate difficulties");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run(cli: &Cli) -> anyhow::Result<()> {
+    match &cli.command {
+        Command::Download => {
+            let bytes = beatstar_fetch_database_zip()?;
+            // Only replace the file with a database the other commands can read
+            let database =
+                SongDatabase::from_zip(&bytes).context("The downloaded database is invalid")?;
+            beatstar_write_file_atomically(&cli.db, &bytes)?;
+            println!("Downloaded {0} songs to {1}", database.len(), cli.db.display());
+        }
+        Command::Info => info(cli)?,
+        Command::Get { id, json } => {
+            let database = load(cli)?;
+            let song = database
+                .get_song_by_id(id)
+                .ok_or_else(|| anyhow!("No song with hash or key {id}"))?;
+
+            if *json {
+                println!("{0}", serde_json::to_string_pretty(song)?);
+            } else {
+                print_song(song);
+            }
+        }
+        Command::Search { text, limit } => {
+            let database = load(cli)?;
+
+            for song in database.search_songs(text, *limit) {
+                println!("{0}  {1}", song.hash, song_title(song));
+            }
+        }
+        Command::Query { filter, limit } => {
+            let database = load(cli)?;
+
+            for (song, diff) in database
+                .query_difficulties(&filter.filter())
+                .iter()
+                .take(*limit)
+            {
+                println!("{0}  {1}", song.hash, song_title(song));
+                print_difficulty(diff);
+            }
+        }
+        Command::Stats => {
+            let database = load(cli)?;
+            println!("{0}", serde_json::to_string_pretty(database.stats())?);
+        }
+        Command::Export {
+            format,
+            output,
+            title,
+            filter,
+        } => export(&load(cli)?, *format, output.clone(), title, filter)?,
+        #[cfg(feature = "server")]
+        Command::Serve { addr, admin_token } => {
+            let server = BeatStarServer::bind(BeatStarServerConfig {
+                addr: addr.clone(),
+                db_path: db_path(cli)?.to_string(),
+                admin_token: admin_token.clone(),
+            })?;
+
+            if let Some(addr) = server.local_addr() {
+                eprintln!("Listening on http://{addr}");
+            }
+            server.run();
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if cli.verbose {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    if let Err(e) = run(&cli) {
+        eprintln!("error: {e:#}");
+        std::process::exit(1);
+    }
+}
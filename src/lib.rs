@@ -1,7 +1,3 @@
-#![feature(layout_for_ptr)]
-#![feature(iter_collect_into)]
-#![feature(cstr_from_bytes_until_nul)]
-
 #[macro_use]
 extern crate lazy_static;
 